@@ -1,12 +1,21 @@
+use std::collections::{HashMap, HashSet};
 use std::mem;
 
-use crate::ics_parser::ics_syntax::{FreqAndRRules, Vevent};
+use crate::const_params::RRULE_MAX_IDLE_PERIODS;
+use crate::ics_parser::ics_syntax::{
+  Freq, FreqAndRRules, ImportWarning, Status, Transp, Vevent,
+};
+use crate::ics_parser::lexer::Token;
 use crate::ics_parser::ICSProcessError;
 use crate::time::date::Date;
 use crate::time::fact::MIN_IN_DAY;
-use crate::time::timezone::ZoneOffset;
+use crate::time::timezone::{Tz, ZoneOffset};
+use crate::time::week::Weekday;
 use crate::time::{date::DateProperty, MinInstant, MinInterval};
 use crate::util_typs::refinement::*;
+
+use super::legacy::{LegacyEvent, LegacyRecurrence};
+
 use serde::{Deserialize, Serialize};
 
 pub type OneOrMore = LowerBoundI64<1>;
@@ -18,36 +27,42 @@ pub type Interval = OneOrMore;
 #[derive(Clone, Serialize, Deserialize)]
 pub enum Pattern {
   Once,
-  Many(DateProperty, Interval, Term),
+  Many(Freq, DateProperty, Interval, Term),
 }
 
-impl TryFrom<Option<FreqAndRRules>> for Pattern {
-  type Error = ICSProcessError;
-
-  /// Attempts to convert the ``FreqAndRRules'' item into some pattern. In
-  /// particular, it makes sure that the ``count'' and ``until'' termination
-  /// rules do not coexist.
-  ///
-  /// [warning] Only weekly - by weekday is implemented.
-  fn try_from(value: Option<FreqAndRRules>) -> Result<Self, Self::Error> {
+impl Pattern {
+  /// Attempts to convert the ``FreqAndRRules'' item of some event starting at
+  /// `dtstart` into some pattern. In particular, it makes sure that the
+  /// ``count'' and ``until'' termination rules do not coexist.
+  pub fn try_new(
+    value: Option<FreqAndRRules>,
+    dtstart: Date,
+  ) -> Result<Self, ICSProcessError> {
     match value {
-      Some(frq) => {
-        let dp = DateProperty::from(frq.content);
-        let itv = OneOrMore::try_new(frq.interval)?;
-        let term = match (frq.count, frq.until) {
-          (None, None) => Term::Never,
-          (None, Some(mi)) => Term::Until(mi),
-          (Some(c), None) => Term::Count(OneOrMore::try_new(c)?),
-          (Some(c), Some(mi)) => {
-            return Err(ICSProcessError::UntilAndCountBothAppear(c, mi))
-          }
-        };
-
-        Ok(Pattern::Many(dp, itv, term))
-      }
+      Some(mut frq) => match frq.span.take() {
+        Some(span) => Self::many(frq, dtstart).map_err(|e| e.at(span)),
+        None => Self::many(frq, dtstart),
+      },
       None => Ok(Pattern::Once),
     }
   }
+
+  /// Creates some repeating pattern from its rules.
+  fn many(frq: FreqAndRRules, dtstart: Date) -> Result<Self, ICSProcessError> {
+    let dp =
+      DateProperty::from_rrules(frq.freq, frq.content, dtstart, frq.wkst)?;
+    let itv = OneOrMore::try_new(frq.interval)?;
+    let term = match (frq.count, frq.until) {
+      (None, None) => Term::Never,
+      (None, Some(mi)) => Term::Until(mi),
+      (Some(c), None) => Term::Count(OneOrMore::try_new(c)?),
+      (Some(c), Some(mi)) => {
+        return Err(ICSProcessError::UntilAndCountBothAppear(c, mi))
+      }
+    };
+
+    Ok(Pattern::Many(frq.freq, dp, itv, term))
+  }
 }
 
 /// Recurrence event termination condition, which is either after a number
@@ -67,75 +82,240 @@ pub struct Recurrence {
   /// Actual time interval of event, ie. 08:30 - 09:50
   event_miv: MinInterval,
 
+  /// Starting time of the first occurrence, which anchors the periods
+  /// counted by `Interval`.
+  dtstart: MinInstant,
+
   /// Indicates that `event_miv` is the nth occurrence. Shall be initialized as 1.
   occurrence_count: OneOrMore,
 
   /// Recurring pattern of some recurrence.
   patt: Pattern,
+
+  /// Starting day of weeks, which decides the weeks counted by `Interval`.
+  #[serde(default)]
+  wkst: Weekday,
+
+  /// Starting times of occurrences to be excluded, ie. `EXDATE`.
+  #[serde(default)]
+  exdates: Vec<MinInstant>,
+
+  /// Starting times of extra occurrences, ie. `RDATE`.
+  #[serde(default)]
+  rdates: Vec<MinInstant>,
+
+  /// Individually modified occurrences, ie. `VEVENT`s with `RECURRENCE-ID`,
+  /// as pairs of original starting time and actual time interval.
+  #[serde(default)]
+  overrides: Vec<(MinInstant, MinInterval)>,
+
+  /// Timezone whose wall-clock time the occurrences keep, ie. that of
+  /// `DTSTART`. Occurrences of a recurrence without timezone are exactly
+  /// some whole days apart.
+  #[serde(default)]
+  zone: Option<Tz>,
+
+  /// Whether occurrences span whole days, ie. holidays.
+  #[serde(default)]
+  all_day: bool,
 }
 
 impl Recurrence {
-  /// Initializes a new recurrence.
-  pub fn new(event_miv: MinInterval, patt: Pattern) -> Self {
+  /// Initializes a new recurrence, with weeks starting on `wkst`.
+  pub fn new(event_miv: MinInterval, patt: Pattern, wkst: Weekday) -> Self {
     Self {
       event_miv,
+      dtstart: event_miv.start,
       occurrence_count: OneOrMore::new(1).unwrap(),
       patt,
+      wkst,
+      exdates: vec![],
+      rdates: vec![],
+      overrides: vec![],
+      zone: None,
+      all_day: false,
     }
   }
 
+  /// Checks whether occurrences span whole days.
+  pub fn all_day(&self) -> bool {
+    self.all_day
+  }
+
+  /// Replaces the occurrence originally starting at `recurrence_id` with
+  /// `miv`. Overriding the same occurrence again discards the earlier one.
+  pub fn add_override(&mut self, recurrence_id: MinInstant, miv: MinInterval) {
+    self.overrides.retain(|(orig, _)| orig != &recurrence_id);
+    self.overrides.push((recurrence_id, miv));
+  }
+
+  /// Cancels the occurrence originally starting at `recurrence_id`, which
+  /// discards any earlier override of it.
+  pub fn cancel(&mut self, recurrence_id: MinInstant) {
+    self.overrides.retain(|(orig, _)| orig != &recurrence_id);
+    self.exdates.push(recurrence_id);
+  }
+
+  /// Checks whether some date is in a period that the recurrence is active
+  /// in, ie. for `FREQ=WEEKLY;INTERVAL=2`, whether the date is an even number
+  /// of weeks after the week of `dtstart`. Under `BYWEEKNO`, years are
+  /// week-numbering years, ie. 2024/Dec/30 is in week 1 of 2025.
+  fn in_active_period(
+    &self,
+    freq: Freq,
+    dp: &DateProperty,
+    itv: Interval,
+    d: Date,
+  ) -> bool {
+    let period_index = |d: Date| match freq {
+      Freq::Yearly if dp.has_week_no() => d.week_yr(self.wkst),
+      _ => d.period_index(freq, self.wkst),
+    };
+    let first_period = period_index(Date::from_min_instant(self.dtstart));
+    (period_index(d) - first_period) % itv.raw() == 0
+  }
+
   /// Computes the next occurrence of the recurrence. If passes termination
   /// condition, returns `None`.
   pub fn next(self) -> Option<Self> {
-    let tmr = self.event_miv.advance_unwrap(MIN_IN_DAY);
+    let tmr = self.event_miv.advance(MIN_IN_DAY).ok()?;
+    let zone = match &self.zone {
+      Some(zone) => zone.clone(),
+      None => Tz::Fixed(self.event_miv.start.offset),
+    };
+    let occurs_on =
+      |freq: Freq, dp: &DateProperty, itv: Interval, d: Date| -> bool {
+        self.in_active_period(freq, dp, itv, d) && dp.check(d)
+      };
+
     let event_miv = match &self.patt {
       Pattern::Once => return None,
-      Pattern::Many(dp, _, Term::Count(n)) => {
-        if self.occurrence_count >= *n {
-          return None;
-        }
-        tmr.advance_until(dp, None).expect("Unreachable: no until")
-      }
-      Pattern::Many(dp, _, Term::Until(term_mi)) => {
-        tmr.advance_until(dp, Some(*term_mi))?
-      }
-      Pattern::Many(dp, _, Term::Never) => {
-        tmr.advance_until(dp, None).expect("Unreachable: no until")
+      Pattern::Many(freq, dp, itv, term) => {
+        let until = match term {
+          Term::Count(n) if self.occurrence_count >= *n => return None,
+          Term::Until(term_mi) => Some(*term_mi),
+          _ => None,
+        };
+
+        // gives up on rules that stop occurring, ie. `INTERVAL=4` on leap
+        // days that started in some odd year.
+        let max_days = u32::try_from(itv.raw())
+          .unwrap_or(u32::MAX)
+          .saturating_mul(freq.max_days() * RRULE_MAX_IDLE_PERIODS);
+        let pred = |d| occurs_on(*freq, dp, *itv, d);
+        tmr.advance_until(pred, until, max_days, &zone)?
       }
     };
 
     // increases the occurrence count.
     let occurrence_count = self.occurrence_count.increment_unwrap();
 
-    Some(Recurrence {
-      event_miv,
-      occurrence_count,
-      patt: self.patt,
-    })
+    Some(Recurrence { event_miv, occurrence_count, ..self })
   }
 
-  /// Computes the number of minutes overlapped with some `MinInterval`.
-  pub fn overlap(self, miv: MinInterval) -> u32 {
-    let mut ret: u32 = 0;
-
+  /// Iterates over the occurrences that overlap with some `MinInterval`.
+  pub fn within(self, miv: MinInterval) -> impl Iterator<Item = MinInterval> {
     let miv = miv.normalize();
+    self
+      .into_iter()
+      .map(MinInterval::normalize)
+      .skip_while(move |rec_miv| rec_miv.end <= miv.start)
+      .take_while(move |rec_miv| rec_miv.start < miv.end)
+  }
 
-    'a: for rec_miv in self {
-      let rec_miv = rec_miv.normalize();
-
-      // skip the non-interacting min-intervals.
-      if rec_miv.end <= miv.start {
-        continue 'a;
+  /// Computes the time span of this recurrence, from the start of its first
+  /// occurrence to the end of its last one, or forever if it never ends.
+  pub fn span(&self) -> (MinInstant, Option<MinInstant>) {
+    let start = self.event_miv.start;
+    let end = self.event_miv.end;
+    let mut occurrences = self.clone().into_iter();
+    match self.patt {
+      Pattern::Many(_, _, _, Term::Never) => {
+        let first = occurrences.next().map_or(start, |miv| miv.start);
+        (first.min(start), None)
       }
-      if rec_miv.start >= miv.end {
-        break 'a;
+      _ => {
+        let (first, last) = occurrences
+          .fold((start, end), |(s, e), miv| (s.min(miv.start), e.max(miv.end)));
+        (first, Some(last))
       }
+    }
+  }
 
-      ret = ret
-        .checked_add(rec_miv.overlap_duration(miv))
-        .expect("Overflowed while computing recurrence and miv overlap");
+  /// Converts the pattern of this recurrence back into its `FreqAndRRules`
+  /// item, which is the inverse of `Pattern::try_new()`. The count excludes
+  /// the occurrences before `event_miv`.
+  fn to_rrules(&self) -> Result<Option<FreqAndRRules>, ICSProcessError> {
+    let Pattern::Many(freq, dp, itv, term) = &self.patt else {
+      return Ok(None);
+    };
+    let content = dp.to_rrules().ok_or_else(|| {
+      ICSProcessError::Other(format!("Cannot export pattern `{}`", dp))
+    })?;
+    let (count, until) = match term {
+      Term::Count(n) => {
+        let done = self.occurrence_count.raw() - 1;
+        (Some((n.raw() - done).max(1) as usize), None)
+      }
+      Term::Until(mi) => (None, Some(*mi)),
+      Term::Never => (None, None),
+    };
+    Ok(Some(FreqAndRRules {
+      freq: *freq,
+      content,
+      interval: itv.raw() as usize,
+      count,
+      until,
+      wkst: self.wkst,
+      floating_until: false,
+      span: None,
+    }))
+  }
+
+  /// Converts this recurrence into `VEVENT`s with the properties of `event`,
+  /// which is the inverse of `TryFrom<Vevent>`. The first one is the
+  /// recurring event, followed by one for each overridden occurrence.
+  fn to_vevents(
+    &self,
+    event: &Event,
+    uid: &str,
+  ) -> Result<Vec<Vevent>, ICSProcessError> {
+    let zone = match &self.zone {
+      Some(zone) => zone.clone(),
+      None => Tz::Fixed(self.event_miv.start.offset),
+    };
+    let vevent = |miv: MinInterval| Vevent {
+      index: 0,
+      repeat: None,
+      miv,
+      summary: event.summary.clone(),
+      exdates: vec![],
+      rdates: vec![],
+      uid: Some(uid.to_string()),
+      description: event.description.clone(),
+      location: event.location.clone(),
+      categories: event.categories.clone(),
+      url: event.url.clone(),
+      zone: zone.clone(),
+      all_day: self.all_day,
+      recurrence_id: None,
+      transp: event.transp,
+      status: event.status,
+    };
+
+    let mut ret = vec![Vevent {
+      repeat: self.to_rrules()?,
+      exdates: self.exdates.clone(),
+      rdates: self.rdates.clone(),
+      ..vevent(self.event_miv)
+    }];
+    for (recurrence_id, miv) in &self.overrides {
+      ret.push(Vevent {
+        recurrence_id: Some(*recurrence_id),
+        ..vevent(*miv)
+      });
     }
-    ret
+    Ok(ret)
   }
 
   /// Computes whether this recurrence has already ended.
@@ -143,11 +323,10 @@ impl Recurrence {
     let tz = ZoneOffset::utc(); // any timezone works for mi comparison
 
     match self.patt {
-      Pattern::Once => self.event_miv.end < MinInstant::now(tz),
-      Pattern::Many(_, _, Term::Never) => false,
+      Pattern::Many(_, _, _, Term::Never) => false,
       _ => {
         for miv in self.clone() {
-          if miv.end >= MinInstant::now(tz) {
+          if miv.end >= MinInstant::now(&tz) {
             return false;
           }
         }
@@ -162,19 +341,38 @@ impl TryFrom<Vevent> for Recurrence {
 
   /// Converts a parsed vector of rrules into a `Recurrence` instance.
   fn try_from(value: Vevent) -> Result<Self, Self::Error> {
-    Ok(Recurrence::new(value.miv, Pattern::try_from(value.repeat)?))
+    let dtstart = Date::from_min_instant(value.miv.start);
+    let wkst = value.repeat.as_ref().map_or(Weekday::MO, |frq| frq.wkst);
+    let patt = Pattern::try_new(value.repeat, dtstart)?;
+    Ok(Recurrence {
+      exdates: value.exdates,
+      rdates: value.rdates,
+      zone: Some(value.zone),
+      all_day: value.all_day,
+      ..Recurrence::new(value.miv, patt, wkst)
+    })
   }
 }
 
-/// An iterator for the `MinInterval` items in some recurrence.
+/// An iterator for the `MinInterval` items in some recurrence, which merges
+/// the extra and overridden occurrences into, and removes the excluded and
+/// overridden occurrences from, the ones generated by the recurrence pattern,
+/// in chronological order.
 pub struct RecIter {
   rec: Option<Recurrence>,
-}
 
-impl Iterator for RecIter {
-  type Item = MinInterval;
+  /// Extra and overridden occurrences, in reverse chronological order. These
+  /// have already been checked against `skipped`.
+  extras: Vec<MinInterval>,
 
-  fn next(&mut self) -> Option<Self::Item> {
+  /// Starting times of excluded or overridden occurrences.
+  skipped: Vec<MinInstant>,
+}
+
+impl RecIter {
+  /// Takes the current occurrence generated by the recurrence pattern, and
+  /// advances the pattern.
+  fn next_in_pattern(&mut self) -> Option<MinInterval> {
     // This is full of acrobatics......
     let old_rec = mem::replace(&mut self.rec, None);
     let ret = old_rec.as_ref()?.event_miv;
@@ -184,31 +382,329 @@ impl Iterator for RecIter {
   }
 }
 
+impl Iterator for RecIter {
+  type Item = MinInterval;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let patt_start = self.rec.as_ref().map(|r| r.event_miv.start);
+      let extra_start = self.extras.last().map(|miv| miv.start);
+
+      let ret = match (patt_start, extra_start) {
+        (None, None) => return None,
+        (Some(p), Some(e)) if e <= p => {
+          if e == p {
+            self.next_in_pattern(); // duplicated by the extra occurrence
+          }
+          return self.extras.pop();
+        }
+        (Some(_), _) => self.next_in_pattern()?,
+        (None, Some(_)) => return self.extras.pop(),
+      };
+
+      if !self.skipped.contains(&ret.start) {
+        break Some(ret);
+      }
+    }
+  }
+}
+
 impl IntoIterator for Recurrence {
   type Item = MinInterval;
   type IntoIter = RecIter;
 
-  fn into_iter(self) -> Self::IntoIter {
-    RecIter { rec: Some(self) }
+  fn into_iter(mut self) -> Self::IntoIter {
+    let duration = self.event_miv.num_min();
+    let overrides = mem::take(&mut self.overrides);
+
+    let mut skipped = mem::take(&mut self.exdates);
+    skipped.extend(overrides.iter().map(|(orig, _)| *orig));
+
+    let mut extras: Vec<MinInterval> = mem::take(&mut self.rdates)
+      .into_iter()
+      .filter(|mi| !skipped.contains(mi))
+      .filter_map(|mi| Some(MinInterval::new(mi, mi.advance(duration).ok()?)))
+      .chain(overrides.into_iter().map(|(_, miv)| miv))
+      .collect();
+    extras.sort_by_key(|miv| std::cmp::Reverse(miv.start));
+
+    RecIter { rec: Some(self), extras, skipped }
   }
 }
 
-/// A struct that pairs the summary of some event with its `Recurrence`.
+/// A struct that pairs the summary of some event with its `Recurrence`, as
+/// well as its other properties.
 #[derive(Serialize, Deserialize)]
-pub struct Event(pub String, pub Recurrence);
+#[serde(from = "StoredEvent")]
+pub struct Event {
+  pub summary: String,
+  pub recurrence: Recurrence,
+  pub transp: Transp,
+  pub status: Status,
+  pub description: Option<String>,
+  pub location: Option<String>,
+  pub categories: Vec<String>,
+  pub uid: Option<String>,
+  pub url: Option<String>,
+}
+
+/// Stored form of some `Event`, which also accepts the tuple of summary,
+/// recurrence, `TRANSP` and `STATUS` stored by earlier versions, as well as
+/// the legacy events stored by the earliest ones.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredEvent {
+  Current {
+    summary: String,
+    recurrence: Recurrence,
+    #[serde(default)]
+    transp: Transp,
+    #[serde(default)]
+    status: Status,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    categories: Vec<String>,
+    #[serde(default)]
+    uid: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+  },
+  Legacy(
+    String,
+    Recurrence,
+    #[serde(default)] Transp,
+    #[serde(default)] Status,
+  ),
+  Baseline(LegacyEvent),
+}
+
+impl From<StoredEvent> for Event {
+  fn from(value: StoredEvent) -> Self {
+    match value {
+      StoredEvent::Current {
+        summary,
+        recurrence,
+        transp,
+        status,
+        description,
+        location,
+        categories,
+        uid,
+        url,
+      } => Event {
+        summary,
+        recurrence,
+        transp,
+        status,
+        description,
+        location,
+        categories,
+        uid,
+        url,
+      },
+      StoredEvent::Legacy(summary, recurrence, transp, status) => Event {
+        summary,
+        recurrence,
+        transp,
+        status,
+        description: None,
+        location: None,
+        categories: vec![],
+        uid: None,
+        url: None,
+      },
+      StoredEvent::Baseline(event) => event.into(),
+    }
+  }
+}
+
+impl From<LegacyEvent> for Event {
+  fn from(value: LegacyEvent) -> Self {
+    let LegacyEvent(summary, recurrence) = value;
+    let transp = Transp::default();
+    let status = Status::default();
+    StoredEvent::Legacy(summary, recurrence.into(), transp, status).into()
+  }
+}
+
+impl From<LegacyRecurrence> for Recurrence {
+  /// Converts some legacy recurrence, whose first occurrence is taken as
+  /// `DTSTART`, with weeks starting on Monday.
+  fn from(value: LegacyRecurrence) -> Self {
+    Recurrence {
+      occurrence_count: value.occurrence_count,
+      ..Recurrence::new(value.event_miv, value.patt.into(), Weekday::default())
+    }
+  }
+}
 
 impl Event {
   /// Computes whether this event is already ended.
   pub fn ended(&self) -> bool {
-    self.1.ended()
+    self.recurrence.ended()
+  }
+
+  /// Checks whether this event occupies time, ie. it is neither transparent
+  /// nor cancelled.
+  pub fn occupies_time(&self) -> bool {
+    self.transp == Transp::Opaque && self.status != Status::Cancelled
+  }
+
+  /// Checks whether this event spans whole days.
+  pub fn all_day(&self) -> bool {
+    self.recurrence.all_day()
+  }
+
+  /// Checks whether this event is tagged with some category, regardless of
+  /// case.
+  pub fn has_category(&self, category: &str) -> bool {
+    self.categories.iter().any(|c| c.eq_ignore_ascii_case(category))
+  }
+
+  /// Checks whether the summary, description or location of this event
+  /// contains some keyword, regardless of case.
+  pub fn mentions(&self, keyword: &str) -> bool {
+    let keyword = keyword.to_lowercase();
+    [Some(&self.summary), self.description.as_ref(), self.location.as_ref()]
+      .into_iter()
+      .flatten()
+      .any(|s| s.to_lowercase().contains(&keyword))
+  }
+
+  /// Checks whether this event is the same as another one in all of its
+  /// properties, ie. their stored forms are equal.
+  pub fn same_as(&self, other: &Event) -> bool {
+    match (serde_json::to_value(self), serde_json::to_value(other)) {
+      (Ok(this), Ok(other)) => this == other,
+      _ => false,
+    }
+  }
+}
+
+impl Event {
+  /// Converts the `VEVENT`s of some calendar into events. `VEVENT`s with a
+  /// `RECURRENCE-ID` are attached as overrides to the recurring event with
+  /// the same `UID`; those without such a recurring event stand on their own.
+  pub fn from_vevents(
+    vevents: Vec<Vevent>,
+  ) -> Result<Vec<Event>, ICSProcessError> {
+    Event::from_vevents_skipping(vevents, |w| Err(w.reason))
+  }
+
+  /// Similar to `from_vevents()`, but `VEVENT`s that cannot be converted are
+  /// skipped, and returned as warnings. So are the overrides of some skipped
+  /// recurring event, which would otherwise stand on their own.
+  pub fn from_vevents_lenient(
+    vevents: Vec<Vevent>,
+  ) -> (Vec<Event>, Vec<ImportWarning>) {
+    let mut warnings = Vec::<ImportWarning>::new();
+    let events = Event::from_vevents_skipping(vevents, |w| {
+      warnings.push(w);
+      Ok(())
+    })
+    .expect("Lenient conversion never fails");
+    (events, warnings)
+  }
+
+  /// Converts `VEVENT`s into events, where those that cannot be converted are
+  /// handed to `skip`, which decides whether the conversion fails.
+  fn from_vevents_skipping<F>(
+    vevents: Vec<Vevent>,
+    mut skip: F,
+  ) -> Result<Vec<Event>, ICSProcessError>
+  where
+    F: FnMut(ImportWarning) -> Result<(), ICSProcessError>,
+  {
+    let convert = |vevent: Vevent| {
+      let (index, uid) = (vevent.index, vevent.uid.clone());
+      Event::try_from(vevent).map_err(|reason| {
+        let component = Token::VEVENT;
+        ImportWarning { component, index, uid, reason }
+      })
+    };
+
+    let (overrides, masters): (Vec<Vevent>, Vec<Vevent>) = vevents
+      .into_iter()
+      .partition(|v| v.uid.is_some() && v.recurrence_id.is_some());
+
+    let mut uid_idx = HashMap::<String, usize>::new();
+    let mut skipped_uids = HashSet::<String>::new();
+    let mut ret = Vec::<Event>::new();
+    for vevent in masters {
+      match convert(vevent) {
+        Ok(event) => {
+          if let Some(uid) = &event.uid {
+            uid_idx.insert(uid.clone(), ret.len());
+          }
+          ret.push(event);
+        }
+        Err(w) => {
+          skipped_uids.extend(w.uid.clone());
+          skip(w)?;
+        }
+      }
+    }
+
+    for vevent in overrides {
+      let master = vevent.uid.as_ref().and_then(|uid| uid_idx.get(uid));
+      match (master, vevent.recurrence_id, vevent.uid.as_deref()) {
+        (Some(&i), Some(recurrence_id), _) => {
+          if vevent.status == Status::Cancelled {
+            ret[i].recurrence.cancel(recurrence_id);
+          } else {
+            ret[i].recurrence.add_override(recurrence_id, vevent.miv);
+          }
+        }
+        (None, _, Some(uid)) if skipped_uids.contains(uid) => {
+          skip(ImportWarning {
+            component: Token::VEVENT,
+            index: vevent.index,
+            uid: Some(uid.to_string()),
+            reason: ICSProcessError::OrphanedOverride(uid.to_string()),
+          })?;
+        }
+        _ => match convert(vevent) {
+          Ok(event) => ret.push(event),
+          Err(w) => skip(w)?,
+        },
+      }
+    }
+
+    Ok(ret)
+  }
+}
+
+impl Event {
+  /// Converts this event back into `VEVENT`s, which is the inverse of
+  /// `from_vevents()`. Events without `UID` are given `fallback_uid`, which
+  /// ties the overridden occurrences to the recurring event.
+  pub fn to_vevents(
+    &self,
+    fallback_uid: &str,
+  ) -> Result<Vec<Vevent>, ICSProcessError> {
+    let uid = self.uid.as_deref().unwrap_or(fallback_uid);
+    self.recurrence.to_vevents(self, uid)
   }
 }
 
 impl TryFrom<Vevent> for Event {
   type Error = ICSProcessError;
 
-  fn try_from(value: Vevent) -> Result<Self, Self::Error> {
-    Ok(Event(value.summary.clone(), Recurrence::try_from(value)?))
+  fn try_from(mut value: Vevent) -> Result<Self, Self::Error> {
+    Ok(Event {
+      summary: mem::take(&mut value.summary),
+      transp: value.transp,
+      status: value.status,
+      description: value.description.take(),
+      location: value.location.take(),
+      categories: mem::take(&mut value.categories),
+      uid: value.uid.take(),
+      url: value.url.take(),
+      recurrence: Recurrence::try_from(value)?,
+    })
   }
 }
 
@@ -216,13 +712,47 @@ impl TryFrom<Vevent> for Event {
 
 impl std::fmt::Display for Event {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{}\n{}\n", self.0, self.1)
+    write!(f, "{}", self.summary)?;
+    if self.transp == Transp::Transparent {
+      write!(f, " [transparent]")?;
+    }
+    match self.status {
+      Status::Tentative => write!(f, " [tentative]")?,
+      Status::Cancelled => write!(f, " [cancelled]")?,
+      Status::Confirmed => (),
+    }
+    if let Some(location) = &self.location {
+      write!(f, "\nLocation: {}", location)?;
+    }
+    if !self.categories.is_empty() {
+      write!(f, "\nCategories: {}", self.categories.join(", "))?;
+    }
+    if let Some(description) = &self.description {
+      write!(f, "\nDescription: {}", description)?;
+    }
+    if let Some(url) = &self.url {
+      write!(f, "\nURL: {}", url)?;
+    }
+    if let Some(uid) = &self.uid {
+      write!(f, "\nUID: {}", uid)?;
+    }
+    write!(f, "\n{}\n", self.recurrence)
   }
 }
 
 impl std::fmt::Display for Recurrence {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{}\n{}", self.event_miv.as_date_string(), self.patt)
+    write!(f, "{}\n{}", self.event_miv.as_date_string(), self.patt)?;
+    if !self.rdates.is_empty() {
+      write!(f, "\nPlus {} extra date(s)", self.rdates.len())?;
+    }
+    if !self.exdates.is_empty() {
+      write!(f, "\nExcept {} date(s)", self.exdates.len())?;
+    }
+    if !self.overrides.is_empty() {
+      write!(f, "\nMoved {} occurrence(s)", self.overrides.len())?;
+    }
+    Ok(())
   }
 }
 
@@ -230,8 +760,8 @@ impl std::fmt::Display for Pattern {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       Pattern::Once => write!(f, "No repeat"),
-      Pattern::Many(dp, iv, t) => {
-        write!(f, "{}\nOccurs every {} times\n{}", dp, iv, t)
+      Pattern::Many(freq, dp, iv, t) => {
+        write!(f, "{}\nOccurs every {} {}(s)\n{}", dp, iv, freq.unit(), t)
       }
     }
   }
@@ -249,12 +779,55 @@ impl std::fmt::Display for Term {
 
 // --------------------------------- Testing ---------------------------------
 
+#[cfg(test)]
 #[allow(dead_code, unused_imports)]
 mod test {
+  use crate::test_util::{parse_events, parse_ics, parse_ics_in};
   use crate::time::date::DatePropertyElt;
+  use crate::time::interval_set::IntervalSet;
 
   use super::*;
 
+  /// Parses a one-hour event starting at 09:00 UTC on `ymd` with the given
+  /// extra content `lines` into some `Recurrence`.
+  fn parse_event(
+    ymd: &str,
+    lines: &str,
+  ) -> Result<Recurrence, ICSProcessError> {
+    let content = format!(
+      "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:{ymd}T090000Z\n\
+      DTEND:{ymd}T100000Z\n{lines}SUMMARY:test\nEND:VEVENT\n\
+      END:VCALENDAR\n"
+    );
+    Recurrence::try_from(parse_ics(&content)?.content.remove(0))
+  }
+
+  /// Parses a one-hour event starting at 09:00 UTC on `ymd` with the given
+  /// `RRULE` value into some `Recurrence`.
+  fn parse_rec(ymd: &str, rrule: &str) -> Result<Recurrence, ICSProcessError> {
+    parse_event(ymd, &format!("RRULE:{rrule}\n"))
+  }
+
+  /// Lists the starting dates of the first `max` occurrences of some `rec`.
+  fn starts(rec: Recurrence, max: usize) -> Vec<String> {
+    rec
+      .into_iter()
+      .take(max)
+      .map(|miv| Date::from_min_instant(miv.start).no_tz_string())
+      .collect()
+  }
+
+  /// Lists the starting dates of the first `max` occurrences of some event
+  /// constructed by `parse_rec()`.
+  fn occurrences(ymd: &str, rrule: &str, max: usize) -> Vec<String> {
+    starts(parse_rec(ymd, rrule).unwrap(), max)
+  }
+
+  /// Makes a list of expected `no_tz_string` occurrences at 09:00.
+  fn at_nine(days: &[&str]) -> Vec<String> {
+    days.iter().map(|d| format!("{} 09:00", d)).collect()
+  }
+
   #[test]
   fn rec_next() {
     let mi = MinInstant::from_raw_utc(27988182).unwrap();
@@ -266,12 +839,13 @@ mod test {
     let dp = DateProperty::or_vec(weeks);
 
     let p = Pattern::Many(
+      Freq::Weekly,
       dp,
       OneOrMore::new(1).unwrap(),
       Term::Count(OneOrMore::new(12).unwrap()),
     );
 
-    let mut r = Recurrence::new(iv, p);
+    let mut r = Recurrence::new(iv, p, Weekday::MO);
 
     let mut last_string = String::new();
     loop {
@@ -302,6 +876,7 @@ mod test {
     };
 
     let p = Pattern::Many(
+      Freq::Weekly,
       dp,
       OneOrMore::new(1).unwrap(),
       Term::Count(OneOrMore::new(12).unwrap()),
@@ -309,8 +884,15 @@ mod test {
 
     let r = Recurrence {
       event_miv: iv,
+      dtstart: iv.start,
       occurrence_count: OneOrMore::new(1).unwrap(),
       patt: p,
+      wkst: Weekday::MO,
+      exdates: vec![],
+      rdates: vec![],
+      overrides: vec![],
+      zone: None,
+      all_day: false,
     };
 
     let mut it = r.into_iter();
@@ -345,9 +927,659 @@ mod test {
       use crate::time::week::Weekday::*;
       DateProperty::or_vec(vec![MO, WE, FR])
     };
-    let p = Pattern::Many(dp, OneOrMore::new(1).unwrap(), Term::Never);
-    let cls_rec = Recurrence::new(cls, p);
+    let itv = OneOrMore::new(1).unwrap();
+    let p = Pattern::Many(Freq::Weekly, dp, itv, Term::Never);
+    let cls_rec = Recurrence::new(cls, p, Weekday::MO);
+
+    let busy: IntervalSet = cls_rec.within(miv).collect();
+    assert_eq!(302, busy.intersection(&miv.into()).num_min());
+  }
+
+  #[test]
+  fn rfc_every_10_days() {
+    let expected = at_nine(&[
+      "1997/Sep/2",
+      "1997/Sep/12",
+      "1997/Sep/22",
+      "1997/Oct/2",
+      "1997/Oct/12",
+    ]);
+    let actual = occurrences("19970902", "FREQ=DAILY;INTERVAL=10;COUNT=5", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_every_other_week_tu_th() {
+    let expected = at_nine(&[
+      "1997/Sep/2",
+      "1997/Sep/4",
+      "1997/Sep/16",
+      "1997/Sep/18",
+      "1997/Sep/30",
+      "1997/Oct/2",
+      "1997/Oct/14",
+      "1997/Oct/16",
+    ]);
+    let actual =
+      occurrences("19970902", "FREQ=WEEKLY;INTERVAL=2;COUNT=8;BYDAY=TU,TH", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_every_other_week_mo_we_fr_until() {
+    let expected = at_nine(&[
+      "1997/Sep/1",
+      "1997/Sep/3",
+      "1997/Sep/5",
+      "1997/Sep/15",
+      "1997/Sep/17",
+      "1997/Sep/19",
+      "1997/Sep/29",
+      "1997/Oct/1",
+      "1997/Oct/3",
+      "1997/Oct/13",
+      "1997/Oct/15",
+      "1997/Oct/17",
+      "1997/Oct/27",
+      "1997/Oct/29",
+      "1997/Oct/31",
+      "1997/Nov/10",
+      "1997/Nov/12",
+      "1997/Nov/14",
+      "1997/Nov/24",
+      "1997/Nov/26",
+      "1997/Nov/28",
+      "1997/Dec/8",
+      "1997/Dec/10",
+      "1997/Dec/12",
+      "1997/Dec/22",
+    ]);
+    let actual = occurrences(
+      "19970901",
+      "FREQ=WEEKLY;INTERVAL=2;UNTIL=19971224T000000Z;BYDAY=MO,WE,FR",
+      99,
+    );
+    assert_eq!(expected, actual);
+  }
 
-    assert_eq!(302, cls_rec.overlap(miv));
+  #[test]
+  fn rfc_every_tuesday_every_other_month() {
+    let expected = at_nine(&[
+      "1997/Sep/2",
+      "1997/Sep/9",
+      "1997/Sep/16",
+      "1997/Sep/23",
+      "1997/Sep/30",
+      "1997/Nov/4",
+      "1997/Nov/11",
+      "1997/Nov/18",
+      "1997/Nov/25",
+      "1998/Jan/6",
+    ]);
+    let actual =
+      occurrences("19970902", "FREQ=MONTHLY;INTERVAL=2;BYDAY=TU", 10);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_daily_10_times() {
+    let expected = at_nine(&[
+      "1997/Sep/2",
+      "1997/Sep/3",
+      "1997/Sep/4",
+      "1997/Sep/5",
+      "1997/Sep/6",
+      "1997/Sep/7",
+      "1997/Sep/8",
+      "1997/Sep/9",
+      "1997/Sep/10",
+      "1997/Sep/11",
+    ]);
+    assert_eq!(expected, occurrences("19970902", "FREQ=DAILY;COUNT=10", 99));
+  }
+
+  #[test]
+  fn weekly_defaults_to_dtstart_weekday() {
+    let expected = at_nine(&["1997/Sep/2", "1997/Sep/9", "1997/Sep/16"]);
+    assert_eq!(expected, occurrences("19970902", "FREQ=WEEKLY;COUNT=3", 99));
+  }
+
+  #[test]
+  fn monthly_skips_months_without_day() {
+    let expected = at_nine(&[
+      "2023/Jan/31",
+      "2023/Mar/31",
+      "2023/May/31",
+      "2023/Jul/31",
+      "2023/Aug/31",
+    ]);
+    assert_eq!(expected, occurrences("20230131", "FREQ=MONTHLY;COUNT=5", 99));
+  }
+
+  #[test]
+  fn yearly_on_leap_day() {
+    let expected = at_nine(&["2024/Feb/29", "2028/Feb/29", "2032/Feb/29"]);
+    assert_eq!(expected, occurrences("20240229", "FREQ=YEARLY;COUNT=3", 99));
+  }
+
+  #[test]
+  fn rfc_every_other_year() {
+    let expected = at_nine(&["1997/Jun/10", "1999/Jun/10", "2001/Jun/10"]);
+    let actual = occurrences("19970610", "FREQ=YEARLY;INTERVAL=2;COUNT=3", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_monthly_by_month_day() {
+    let expected = at_nine(&[
+      "1997/Sep/30",
+      "1997/Oct/1",
+      "1997/Oct/31",
+      "1997/Nov/1",
+      "1997/Nov/30",
+      "1997/Dec/1",
+      "1997/Dec/31",
+      "1998/Jan/1",
+      "1998/Jan/31",
+      "1998/Feb/1",
+    ]);
+    let actual =
+      occurrences("19970930", "FREQ=MONTHLY;COUNT=10;BYMONTHDAY=1,-1", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_every_18_months() {
+    let expected = at_nine(&[
+      "1997/Sep/10",
+      "1997/Sep/11",
+      "1997/Sep/12",
+      "1997/Sep/13",
+      "1997/Sep/14",
+      "1997/Sep/15",
+      "1999/Mar/10",
+      "1999/Mar/11",
+      "1999/Mar/12",
+      "1999/Mar/13",
+    ]);
+    let actual = occurrences(
+      "19970910",
+      "FREQ=MONTHLY;INTERVAL=18;COUNT=10;BYMONTHDAY=10,11,12,13,14,15",
+      99,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_yearly_by_month() {
+    let expected = at_nine(&[
+      "1997/Jun/10",
+      "1997/Jul/10",
+      "1998/Jun/10",
+      "1998/Jul/10",
+      "1999/Jun/10",
+      "1999/Jul/10",
+    ]);
+    let actual = occurrences("19970610", "FREQ=YEARLY;COUNT=6;BYMONTH=6,7", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_every_third_year_by_year_day() {
+    let expected = at_nine(&[
+      "1997/Jan/1",
+      "1997/Apr/10",
+      "1997/Jul/19",
+      "2000/Jan/1",
+      "2000/Apr/9",
+      "2000/Jul/18",
+      "2003/Jan/1",
+      "2003/Apr/10",
+      "2003/Jul/19",
+      "2006/Jan/1",
+    ]);
+    let actual = occurrences(
+      "19970101",
+      "FREQ=YEARLY;INTERVAL=3;COUNT=10;BYYEARDAY=1,100,200",
+      99,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_monday_of_week_20() {
+    let expected = at_nine(&["1997/May/12", "1998/May/11", "1999/May/17"]);
+    let actual = occurrences("19970512", "FREQ=YEARLY;BYWEEKNO=20;BYDAY=MO", 3);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn monday_of_week_1_every_other_year() {
+    // week 1 of 2025 starts on 2024/Dec/30, and that of 2031 on 2030/Dec/30.
+    let expected =
+      at_nine(&["2024/Dec/30", "2027/Jan/4", "2029/Jan/1", "2030/Dec/30"]);
+    let actual =
+      occurrences("20241230", "FREQ=YEARLY;INTERVAL=2;BYWEEKNO=1;BYDAY=MO", 4);
+    assert_eq!(expected, actual);
+
+    let expected = at_nine(&["2024/Dec/30", "2025/Dec/29", "2027/Jan/4"]);
+    let actual = occurrences("20241230", "FREQ=YEARLY;BYWEEKNO=1;BYDAY=MO", 3);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_every_thursday_in_march() {
+    let expected = at_nine(&[
+      "1997/Mar/13",
+      "1997/Mar/20",
+      "1997/Mar/27",
+      "1998/Mar/5",
+      "1998/Mar/12",
+      "1998/Mar/19",
+      "1998/Mar/26",
+    ]);
+    let actual = occurrences("19970313", "FREQ=YEARLY;BYMONTH=3;BYDAY=TH", 7);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_saturday_after_first_sunday() {
+    let expected = at_nine(&[
+      "1997/Sep/13",
+      "1997/Oct/11",
+      "1997/Nov/8",
+      "1997/Dec/13",
+      "1998/Jan/10",
+    ]);
+    let actual = occurrences(
+      "19970913",
+      "FREQ=MONTHLY;BYDAY=SA;BYMONTHDAY=7,8,9,10,11,12,13",
+      5,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_third_tu_we_th_of_month() {
+    let expected = at_nine(&["1997/Sep/4", "1997/Oct/7", "1997/Nov/6"]);
+    let actual = occurrences(
+      "19970904",
+      "FREQ=MONTHLY;COUNT=3;BYDAY=TU,WE,TH;BYSETPOS=3",
+      99,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_last_work_day_of_month() {
+    let expected = at_nine(&[
+      "1997/Sep/30",
+      "1997/Oct/31",
+      "1997/Nov/28",
+      "1997/Dec/31",
+      "1998/Jan/30",
+      "1998/Feb/27",
+      "1998/Mar/31",
+    ]);
+    let actual = occurrences(
+      "19970930",
+      "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+      7,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_monthly_first_friday() {
+    let expected = at_nine(&[
+      "1997/Sep/5",
+      "1997/Oct/3",
+      "1997/Nov/7",
+      "1997/Dec/5",
+      "1998/Jan/2",
+      "1998/Feb/6",
+      "1998/Mar/6",
+      "1998/Apr/3",
+      "1998/May/1",
+      "1998/Jun/5",
+    ]);
+    let actual = occurrences("19970905", "FREQ=MONTHLY;COUNT=10;BYDAY=1FR", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_every_other_month_first_and_last_sunday() {
+    let expected = at_nine(&[
+      "1997/Sep/7",
+      "1997/Sep/28",
+      "1997/Nov/2",
+      "1997/Nov/30",
+      "1998/Jan/4",
+      "1998/Jan/25",
+      "1998/Mar/1",
+      "1998/Mar/29",
+      "1998/May/3",
+      "1998/May/31",
+    ]);
+    let actual = occurrences(
+      "19970907",
+      "FREQ=MONTHLY;INTERVAL=2;COUNT=10;BYDAY=1SU,-1SU",
+      99,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_second_to_last_monday() {
+    let expected = at_nine(&[
+      "1997/Sep/22",
+      "1997/Oct/20",
+      "1997/Nov/17",
+      "1997/Dec/22",
+      "1998/Jan/19",
+      "1998/Feb/16",
+    ]);
+    let actual = occurrences("19970922", "FREQ=MONTHLY;COUNT=6;BYDAY=-2MO", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_every_20th_monday_of_year() {
+    let expected = at_nine(&["1997/May/19", "1998/May/18", "1999/May/17"]);
+    let actual = occurrences("19970519", "FREQ=YEARLY;BYDAY=20MO", 3);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn yearly_ordinal_weekday_in_month() {
+    // US Thanksgiving, ie. the fourth Thursday of November.
+    let expected = at_nine(&["2023/Nov/23", "2024/Nov/28", "2025/Nov/27"]);
+    let actual =
+      occurrences("20231123", "FREQ=YEARLY;BYMONTH=11;BYDAY=+4TH", 3);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_wkst_monday() {
+    let expected =
+      at_nine(&["1997/Aug/5", "1997/Aug/10", "1997/Aug/19", "1997/Aug/24"]);
+    let actual = occurrences(
+      "19970805",
+      "FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=MO",
+      99,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_wkst_sunday() {
+    let expected =
+      at_nine(&["1997/Aug/5", "1997/Aug/17", "1997/Aug/19", "1997/Aug/31"]);
+    let actual = occurrences(
+      "19970805",
+      "FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=SU",
+      99,
+    );
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn rfc_friday_13th_with_exdate() {
+    let expected = at_nine(&[
+      "1998/Feb/13",
+      "1998/Mar/13",
+      "1998/Nov/13",
+      "1999/Aug/13",
+      "2000/Oct/13",
+    ]);
+    let rec = parse_event(
+      "19970902",
+      "EXDATE:19970902T090000Z\nRRULE:FREQ=MONTHLY;BYDAY=FR;BYMONTHDAY=13\n",
+    );
+    assert_eq!(expected, starts(rec.unwrap(), 5));
+  }
+
+  #[test]
+  fn weekly_with_exdate_and_rdates() {
+    let expected = at_nine(&[
+      "2023/Sep/2",
+      "2023/Sep/4",
+      "2023/Sep/13",
+      "2023/Sep/18",
+      "2023/Sep/25",
+    ]);
+    let rec = parse_event(
+      "20230904",
+      "RRULE:FREQ=WEEKLY;COUNT=4\nEXDATE:20230911T090000Z\n\
+      RDATE:20230913T090000Z,20230902T090000Z,20230918T090000Z\n",
+    );
+    assert_eq!(expected, starts(rec.unwrap(), 99));
+  }
+
+  #[test]
+  fn single_event_with_rdate() {
+    let expected = at_nine(&["2023/Sep/4", "2023/Sep/6"]);
+    let rec = parse_event("20230904", "RDATE:20230906T090000Z\n").unwrap();
+    assert_eq!(60, rec.into_iter().last().unwrap().num_min());
+    let rec = parse_event("20230904", "RDATE:20230906T090000Z\n").unwrap();
+    assert_eq!(expected, starts(rec, 99));
+  }
+
+  #[test]
+  fn recurrence_id_overrides_occurrence() {
+    let content = "BEGIN:VCALENDAR\n\
+      BEGIN:VEVENT\nUID:moved@test\nRECURRENCE-ID:20230911T090000Z\n\
+      DTSTART:20230912T140000Z\nDTEND:20230912T153000Z\nSUMMARY:moved\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nUID:moved@test\nDTSTART:20230904T090000Z\n\
+      DTEND:20230904T100000Z\nRRULE:FREQ=WEEKLY;COUNT=3\nSUMMARY:class\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nUID:other@test\nRECURRENCE-ID:20230911T090000Z\n\
+      DTSTART:20230911T090000Z\nDTEND:20230911T100000Z\nSUMMARY:orphan\n\
+      END:VEVENT\n\
+      END:VCALENDAR\n";
+    let mut events = parse_events(content);
+
+    assert_eq!(2, events.len());
+    assert_eq!("orphan", events.pop().unwrap().summary);
+
+    let class = events.pop().unwrap();
+    assert_eq!("class", class.summary);
+    let actual: Vec<String> = class
+      .recurrence
+      .into_iter()
+      .map(|miv| {
+        let start = Date::from_min_instant(miv.start).no_tz_string();
+        format!("{} ({} min)", start, miv.num_min())
+      })
+      .collect();
+    let expected = vec![
+      "2023/Sep/4 09:00 (60 min)",
+      "2023/Sep/12 14:00 (90 min)",
+      "2023/Sep/18 09:00 (60 min)",
+    ];
+    assert_eq!(expected, actual);
+  }
+
+  /// Parses a one-hour event in `America/New_York` starting at local `time`
+  /// on `ymd` with the given `RRULE` value, and lists the UTC starting times
+  /// and durations of its first `max` occurrences.
+  fn new_york_occurrences(
+    ymd: &str,
+    time: &str,
+    rrule: &str,
+    max: usize,
+  ) -> Vec<String> {
+    let start: u32 = time.parse().unwrap();
+    let content = format!(
+      "BEGIN:VCALENDAR\nBEGIN:VEVENT\n\
+      DTSTART;TZID=America/New_York:{ymd}T{start:06}\n\
+      DTEND;TZID=America/New_York:{ymd}T{:06}\n\
+      RRULE:{rrule}\nSUMMARY:test\nEND:VEVENT\nEND:VCALENDAR\n",
+      start + 10000
+    );
+    let mut cal = parse_ics(&content).unwrap();
+    Recurrence::try_from(cal.content.remove(0))
+      .unwrap()
+      .into_iter()
+      .take(max)
+      .map(|miv| {
+        let start = Date::from_min_instant(miv.start.normalize());
+        format!("{} ({} min)", start.no_tz_string(), miv.num_min())
+      })
+      .collect()
+  }
+
+  #[test]
+  fn weekly_keeps_wall_clock_across_dst() {
+    let expected = vec![
+      "2023/Mar/1 14:00 (60 min)",
+      "2023/Mar/8 14:00 (60 min)",
+      "2023/Mar/15 13:00 (60 min)",
+      "2023/Mar/22 13:00 (60 min)",
+    ];
+    let actual = new_york_occurrences("20230301", "090000", "FREQ=WEEKLY", 4);
+    assert_eq!(expected, actual);
+
+    let until = "FREQ=WEEKLY;UNTIL=20230322T130000Z";
+    let actual = new_york_occurrences("20230301", "090000", until, 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn local_until_in_dtstart_zone() {
+    // 09:00 in New York, rather than in UTC which is the calendar default.
+    let until = "FREQ=WEEKLY;UNTIL=20230322T090000";
+    let actual = new_york_occurrences("20230301", "090000", until, 99);
+    assert_eq!(4, actual.len());
+    assert_eq!("2023/Mar/22 13:00 (60 min)", actual[3]);
+
+    // `RRULE` may come before `DTSTART`.
+    let content = "BEGIN:VCALENDAR\nBEGIN:VEVENT\n\
+      RRULE:FREQ=DAILY;UNTIL=20231105T090000\n\
+      DTSTART;TZID=America/New_York:20231103T090000\n\
+      DTEND;TZID=America/New_York:20231103T100000\n\
+      SUMMARY:test\nEND:VEVENT\nEND:VCALENDAR\n";
+    let mut cal = parse_ics(content).unwrap();
+    let rec = Recurrence::try_from(cal.content.remove(0)).unwrap();
+    assert_eq!(3, rec.into_iter().count());
+  }
+
+  #[test]
+  fn daily_across_spring_forward_gap() {
+    // 02:30 does not exist on Mar 12, and is interpreted as 03:30 EDT.
+    let expected = vec![
+      "2023/Mar/11 07:30 (60 min)",
+      "2023/Mar/12 07:30 (60 min)",
+      "2023/Mar/13 06:30 (60 min)",
+    ];
+    let actual =
+      new_york_occurrences("20230311", "023000", "FREQ=DAILY;COUNT=3", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn daily_across_fall_back_overlap() {
+    // 01:30 occurs twice on Nov 5, and is interpreted as 01:30 EDT.
+    let expected = vec![
+      "2023/Nov/4 05:30 (60 min)",
+      "2023/Nov/5 05:30 (60 min)",
+      "2023/Nov/6 06:30 (60 min)",
+    ];
+    let actual =
+      new_york_occurrences("20231104", "013000", "FREQ=DAILY;COUNT=3", 99);
+    assert_eq!(expected, actual);
+  }
+
+  #[test]
+  fn cancelled_override() {
+    let content = "BEGIN:VCALENDAR\n\
+      BEGIN:VEVENT\nUID:class@test\nDTSTART:20230904T090000Z\n\
+      DTEND:20230904T100000Z\nRRULE:FREQ=WEEKLY;COUNT=3\nSUMMARY:class\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nUID:class@test\nRECURRENCE-ID:20230911T090000Z\n\
+      DTSTART:20230911T090000Z\nDTEND:20230911T100000Z\nSUMMARY:class\n\
+      STATUS:CANCELLED\nEND:VEVENT\n\
+      END:VCALENDAR\n";
+    let mut events = parse_events(content);
+
+    assert_eq!(1, events.len());
+    let expected = at_nine(&["2023/Sep/4", "2023/Sep/18"]);
+    assert_eq!(expected, starts(events.pop().unwrap().recurrence, 99));
+  }
+
+  #[test]
+  fn invalid_rrules() {
+    assert!(parse_rec("19970902", "FREQ=WEEKLY;WKST=XX").is_err());
+    assert!(parse_rec("19970902", "FREQ=WEEKLY;BYDAY=1TU").is_err());
+    assert!(parse_rec("19970902", "FREQ=MONTHLY;BYDAY=0TU").is_err());
+    assert!(parse_rec("19970902", "FREQ=MONTHLY;BYDAY=XX").is_err());
+    assert!(parse_rec("19970902", "FREQ=WEEKLY;BYMONTHDAY=3").is_err());
+    assert!(parse_rec("19970902", "FREQ=MONTHLY;BYYEARDAY=3").is_err());
+    assert!(parse_rec("19970902", "FREQ=MONTHLY;BYMONTHDAY=0").is_err());
+    assert!(parse_rec("19970902", "FREQ=YEARLY;BYMONTH=13").is_err());
+    assert!(parse_rec("19970902", "FREQ=DAILY;BYHOUR=9").is_err());
+    assert!(parse_rec("19970902", "FREQ=MONTHLY;BYSETPOS=1").is_err());
+
+    // rules are interpreted after parsing, but still located at `RRULE`.
+    let err = parse_rec("19970902", "FREQ=MONTHLY;BYDAY=XX").err().unwrap();
+    let rrule_line = "RRULE:FREQ=MONTHLY;BYDAY=XX";
+    let carets = "^".repeat(rrule_line.len());
+    assert!(err.to_string().starts_with("test.ics:5:1: "));
+    assert!(err.to_string().ends_with(&format!("{rrule_line}\n  | {carets}")));
+  }
+
+  #[test]
+  fn never_occurring_rrules() {
+    for rrule in [
+      "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30",
+      "FREQ=YEARLY;BYMONTHDAY=31;BYMONTH=4",
+      "FREQ=MONTHLY;BYMONTH=2;BYMONTHDAY=-30",
+      "FREQ=YEARLY;BYYEARDAY=366;BYMONTH=1",
+      "FREQ=YEARLY;BYWEEKNO=1;BYMONTH=6",
+    ] {
+      let err = parse_rec("20240101", rrule).err().unwrap();
+      assert!(err.to_string().contains("rrule never occurs"), "{}", rrule);
+    }
+    // implied by `DTSTART`, which is on the 31st.
+    assert!(parse_rec("20240131", "FREQ=YEARLY;BYMONTH=4").is_err());
+
+    let leap =
+      occurrences("20240229", "FREQ=YEARLY;BYMONTHDAY=29;BYMONTH=2", 3);
+    assert_eq!(at_nine(&["2024/Feb/29", "2028/Feb/29", "2032/Feb/29"]), leap);
+
+    // every 4 years from an odd year never lands on leap days, which ends the
+    // recurrence instead of searching till `MinInstant` overflows.
+    let odd = occurrences(
+      "20230101",
+      "FREQ=YEARLY;INTERVAL=4;BYMONTH=2;BYMONTHDAY=29",
+      3,
+    );
+    assert_eq!(at_nine(&["2023/Jan/1"]), odd);
+  }
+
+  #[test]
+  fn lenient_conversion() {
+    let content = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20231010T090000Z\n\
+      DTEND:20231010T100000Z\nRRULE:FREQ=WEEKLY;BYDAY=1TU\nUID:bad@test\n\
+      END:VEVENT\nBEGIN:VEVENT\nDTSTART:20231010T090000Z\n\
+      DTEND:20231010T100000Z\nSUMMARY:good\nEND:VEVENT\n\
+      BEGIN:VEVENT\nUID:bad@test\nRECURRENCE-ID:20231017T090000Z\n\
+      DTSTART:20231017T110000Z\nDTEND:20231017T120000Z\nSUMMARY:moved\n\
+      END:VEVENT\nEND:VCALENDAR\n";
+    let cal = parse_ics(content).unwrap();
+    let (events, warnings) = Event::from_vevents_lenient(cal.content);
+    assert_eq!(1, events.len());
+    assert_eq!("good", events[0].summary);
+
+    // the override of the skipped recurring event is skipped as well.
+    let skipped: Vec<(usize, Option<&str>)> =
+      warnings.iter().map(|w| (w.index, w.uid.as_deref())).collect();
+    assert_eq!(vec![(0, Some("bad@test")), (2, Some("bad@test"))], skipped);
+    assert_eq!(
+      "VEVENT #3 (UID `bad@test`) skipped: the recurring event with UID \
+      `bad@test` is skipped",
+      warnings[1].to_string()
+    );
   }
 }
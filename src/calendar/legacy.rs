@@ -0,0 +1,196 @@
+//! Stored forms of calendars written by earlier versions of TaggyTime, which
+//! mirror their serde layouts exactly, and are converted into the current
+//! ones upon loading.
+
+use serde::Deserialize;
+
+use crate::ics_parser::ics_syntax::Freq;
+use crate::time::date::{DateProperty, DatePropertyElt};
+use crate::time::week::Weekday;
+use crate::time::MinInterval;
+
+use super::cal_event::{Interval, OneOrMore, Pattern, Term};
+
+/// Property of dates stored before rules other than `BYDAY` are supported.
+#[derive(Deserialize)]
+pub enum LegacyDatePropertyElt {
+  Wd(Weekday),
+}
+
+/// Combination of date properties stored before rules other than `BYDAY` are
+/// supported.
+#[derive(Deserialize)]
+pub enum LegacyDateProperty {
+  Always,
+  Atomic(LegacyDatePropertyElt),
+  Or(Vec<LegacyDateProperty>),
+  And(Vec<LegacyDateProperty>),
+}
+
+impl From<LegacyDateProperty> for DateProperty {
+  fn from(value: LegacyDateProperty) -> Self {
+    let convert = |v: Vec<LegacyDateProperty>| v.into_iter().map(Self::from);
+    match value {
+      LegacyDateProperty::Always => DateProperty::Always,
+      LegacyDateProperty::Atomic(LegacyDatePropertyElt::Wd(wd)) => {
+        DateProperty::Atomic(DatePropertyElt::Wd(wd))
+      }
+      LegacyDateProperty::Or(v) => DateProperty::Or(convert(v).collect()),
+      LegacyDateProperty::And(v) => DateProperty::And(convert(v).collect()),
+    }
+  }
+}
+
+/// Recurrence pattern stored before `FREQ` is recorded. Such patterns only
+/// check the days that events happen on, so the frequency that `INTERVAL`
+/// counts in is inferred.
+#[derive(Deserialize)]
+pub enum LegacyPattern {
+  Once,
+  Many(LegacyDateProperty, Interval, Term),
+}
+
+impl From<LegacyPattern> for Pattern {
+  /// Converts some legacy pattern into one that occurs on the same days.
+  /// Patterns by weekdays are weekly, and those that always hold are daily.
+  fn from(value: LegacyPattern) -> Self {
+    match value {
+      LegacyPattern::Once => Pattern::Once,
+      LegacyPattern::Many(dp, itv, term) => {
+        let freq = match dp {
+          LegacyDateProperty::Always => Freq::Daily,
+          _ => Freq::Weekly,
+        };
+        Pattern::Many(freq, dp.into(), itv, term)
+      }
+    }
+  }
+}
+
+/// Recurrence stored before `DTSTART` and `WKST` are recorded.
+#[derive(Deserialize)]
+pub struct LegacyRecurrence {
+  pub event_miv: MinInterval,
+  pub occurrence_count: OneOrMore,
+  pub patt: LegacyPattern,
+}
+
+/// Event stored as a pair of summary and recurrence, before any other
+/// property is recorded.
+#[derive(Deserialize)]
+pub struct LegacyEvent(pub String, pub LegacyRecurrence);
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::calendar::cal_event::Event;
+  use crate::time::date::Date;
+
+  use super::*;
+
+  /// Lists the starting dates of the first `max` occurrences of some event.
+  fn starts(event: Event, max: usize) -> Vec<String> {
+    event
+      .recurrence
+      .into_iter()
+      .take(max)
+      .map(|miv| Date::from_min_instant(miv.start).no_tz_string())
+      .collect()
+  }
+
+  #[test]
+  fn baseline_env() {
+    // written by the first release, with `AddCal` and `AddTask`.
+    let stored = include_str!("fixtures/baseline_env.json");
+    let tenv: crate::TaggyEnv = serde_json::from_str(stored).unwrap();
+    assert_eq!("-04:00", tenv.tz.to_string());
+    assert!(tenv.todolist.contains("hw"));
+
+    let cal = tenv.calendars.get("school").unwrap();
+    assert!(!cal.all_day_blocking);
+    assert_eq!(None, cal.source);
+    let summaries: Vec<&str> =
+      cal.events.iter().map(|e| e.summary.as_str()).collect();
+    assert_eq!(vec!["class", "exam"], summaries);
+    assert!(cal.events.iter().all(|e| e.occupies_time() && e.uid.is_none()));
+
+    let class = cal.events[0].recurrence.clone();
+    assert_eq!(10, class.into_iter().count());
+  }
+
+  #[test]
+  fn weekly_by_weekday() {
+    let stored = r#"["class",{"event_miv":{"start":{"raw":28282140,
+      "offset":-240},"end":{"raw":28282200,"offset":-240}},
+      "occurrence_count":1,"patt":{"Many":[{"Or":[{"Atomic":{"Wd":"TU"}},
+      {"Atomic":{"Wd":"TH"}}]},1,{"Count":4}]}}]"#;
+    let event: Event = serde_json::from_str(stored).unwrap();
+    assert_eq!("class", event.summary);
+    assert_eq!(
+      vec![
+        "2023/Oct/10 09:00",
+        "2023/Oct/12 09:00",
+        "2023/Oct/17 09:00",
+        "2023/Oct/19 09:00"
+      ],
+      starts(event, 99)
+    );
+  }
+
+  #[test]
+  fn interval_and_once() {
+    // every other week, which earlier versions expanded weekly.
+    let stored = r#"["class",{"event_miv":{"start":{"raw":28282140,
+      "offset":-240},"end":{"raw":28282200,"offset":-240}},
+      "occurrence_count":1,"patt":{"Many":[{"Or":[{"Atomic":{"Wd":"TU"}}]},
+      2,{"Count":3}]}}]"#;
+    let event: Event = serde_json::from_str(stored).unwrap();
+    assert_eq!(
+      vec!["2023/Oct/10 09:00", "2023/Oct/24 09:00", "2023/Nov/7 09:00"],
+      starts(event, 99)
+    );
+
+    let stored = r#"["daily",{"event_miv":{"start":{"raw":28282140,
+      "offset":0},"end":{"raw":28282200,"offset":0}},
+      "occurrence_count":1,"patt":{"Many":["Always",1,"Never"]}}]"#;
+    let event: Event = serde_json::from_str(stored).unwrap();
+    assert_eq!(
+      vec!["2023/Oct/10 09:00", "2023/Oct/11 09:00", "2023/Oct/12 09:00"],
+      starts(event, 3)
+    );
+
+    let stored = r#"["exam",{"event_miv":{"start":{"raw":28285260,
+      "offset":-240},"end":{"raw":28285320,"offset":-240}},
+      "occurrence_count":1,"patt":"Once"}]"#;
+    let event: Event = serde_json::from_str(stored).unwrap();
+    assert_eq!(vec!["2023/Oct/12 13:00"], starts(event, 99));
+  }
+
+  #[test]
+  fn nested_date_properties() {
+    let stored = r#"["class",{"event_miv":{"start":{"raw":28282140,
+      "offset":0},"end":{"raw":28282200,"offset":0}},"occurrence_count":1,
+      "patt":{"Many":[{"And":[{"Or":[{"Atomic":{"Wd":"TU"}},
+      {"Atomic":{"Wd":"WE"}}]},{"Atomic":{"Wd":"WE"}}]},1,"Never"]}}]"#;
+    let event: Event = serde_json::from_str(stored).unwrap();
+    assert_eq!(
+      vec!["2023/Oct/10 09:00", "2023/Oct/11 09:00", "2023/Oct/18 09:00"],
+      starts(event, 3)
+    );
+  }
+
+  #[test]
+  fn missing_wkst() {
+    let stored = r#"["class",{"event_miv":{"start":{"raw":28282140,
+      "offset":0},"end":{"raw":28282200,"offset":0}},
+      "dtstart":{"raw":28282140,"offset":0},"occurrence_count":1,
+      "patt":{"Many":["Weekly",{"Or":[{"Atomic":{"Wd":"TU"}}]},2,
+      {"Count":2}]}}]"#;
+    let event: Event = serde_json::from_str(stored).unwrap();
+    assert_eq!(
+      vec!["2023/Oct/10 09:00", "2023/Oct/24 09:00"],
+      starts(event, 99)
+    );
+  }
+}
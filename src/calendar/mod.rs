@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
-use crate::time::MinInterval;
+use crate::time::{interval_set::IntervalSet, MinInterval};
 
 use self::{
   cal_event::Event,
@@ -10,6 +10,7 @@ use self::{
 use serde::{Deserialize, Serialize};
 
 pub mod cal_event;
+mod legacy;
 pub mod task;
 
 #[derive(Debug)]
@@ -39,6 +40,16 @@ impl<T> NameMap<T> {
     }
   }
 
+  /// Inserts some key-value pair, and returns the replaced value if any.
+  pub fn insert(&mut self, key: &str, val: T) -> Option<T> {
+    self.contents.insert(key.to_string(), val)
+  }
+
+  /// Gets ref.
+  pub fn get(&self, key: &str) -> Option<&T> {
+    self.contents.get(key)
+  }
+
   /// Gets mutable ref.
   pub fn get_mut(&mut self, key: &str) -> Option<&mut T> {
     self.contents.get_mut(key)
@@ -55,27 +66,182 @@ impl<T> NameMap<T> {
   }
 }
 
-impl NameMap<Vec<Event>> {
-  /// Computes the number of minutes overlapped with some `MinInterval`.
-  fn overlap_miv(&self, miv: MinInterval) -> u32 {
-    let mut ret: u32 = 0;
-    for event_vec in self.contents.values() {
-      for event in event_vec {
-        ret =
-          ret.checked_add(event.1.clone().overlap(miv)).expect("Overflowed");
+/// Some loaded calendar, ie. the events of some `.ics` file.
+#[derive(Serialize, Deserialize)]
+#[serde(from = "StoredCalendar")]
+pub struct Calendar {
+  pub events: Vec<Event>,
+
+  /// Whether all-day events occupy whole days, or merely mark them, ie.
+  /// holidays.
+  pub all_day_blocking: bool,
+
+  /// The file that the calendar is loaded from, unless it is read from
+  /// stdin, and thus cannot be synced.
+  pub source: Option<PathBuf>,
+
+  /// Hash of the content of `source` when it is last loaded.
+  pub hash: Option<u64>,
+}
+
+impl Calendar {
+  pub fn new(events: Vec<Event>, all_day_blocking: bool) -> Self {
+    Calendar {
+      events,
+      all_day_blocking,
+      source: None,
+      hash: None,
+    }
+  }
+
+  /// Records the file that the calendar is loaded from, along with the hash
+  /// of its content.
+  pub fn with_source(mut self, source: PathBuf, hash: u64) -> Self {
+    self.source = Some(source);
+    self.hash = Some(hash);
+    self
+  }
+
+  /// Iterates over the events that occupy time, which excludes transparent
+  /// and cancelled events.
+  pub fn blocking_events(&self) -> impl Iterator<Item = &Event> {
+    self
+      .events
+      .iter()
+      .filter(|e| e.occupies_time())
+      .filter(|e| self.all_day_blocking || !e.all_day())
+  }
+
+  /// Computes the busy time within some `MinInterval`, ie. the union of the
+  /// occurrences of blocking events, clipped to it.
+  pub fn busy_set(&self, miv: MinInterval) -> IntervalSet {
+    let occurrences: IntervalSet = self
+      .blocking_events()
+      .flat_map(|event| event.recurrence.clone().within(miv))
+      .collect();
+    occurrences.intersection(&miv.into())
+  }
+}
+
+/// Stored form of some `Calendar`, which also accepts the bare list of events
+/// stored by earlier versions.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredCalendar {
+  Current {
+    events: Vec<Event>,
+    all_day_blocking: bool,
+    #[serde(default)]
+    source: Option<PathBuf>,
+    #[serde(default)]
+    hash: Option<u64>,
+  },
+  Legacy(Vec<Event>),
+}
+
+impl From<StoredCalendar> for Calendar {
+  fn from(value: StoredCalendar) -> Self {
+    match value {
+      StoredCalendar::Current { events, all_day_blocking, source, hash } => {
+        Calendar { events, all_day_blocking, source, hash }
+      }
+      StoredCalendar::Legacy(events) => Calendar::new(events, false),
+    }
+  }
+}
+
+/// How the events of some calendar change when it is synced. Events are
+/// matched by their `UID`s; those without `UID`s are either kept as they are,
+/// or else removed and added.
+#[derive(Default)]
+pub struct EventDiff {
+  pub added: Vec<String>,
+  pub removed: Vec<String>,
+  pub modified: Vec<String>,
+}
+
+impl EventDiff {
+  /// Compares the events of some calendar before and after it is synced.
+  pub fn new(old: &[Event], new: &[Event]) -> Self {
+    let mut ret = EventDiff::default();
+    let old_by_uid: HashMap<&str, &Event> =
+      old.iter().filter_map(|e| Some((e.uid.as_deref()?, e))).collect();
+    let new_by_uid: HashMap<&str, &Event> =
+      new.iter().filter_map(|e| Some((e.uid.as_deref()?, e))).collect();
+
+    for event in new {
+      let found = match event.uid.as_deref() {
+        Some(uid) => old_by_uid.get(uid).copied(),
+        None => old.iter().find(|o| o.uid.is_none() && o.same_as(event)),
+      };
+      match found {
+        Some(old_event) if old_event.same_as(event) => (),
+        Some(_) => ret.modified.push(Self::describe(event)),
+        None => ret.added.push(Self::describe(event)),
+      }
+    }
+    for event in old {
+      let kept = match event.uid.as_deref() {
+        Some(uid) => new_by_uid.contains_key(uid),
+        None => new.iter().any(|n| n.uid.is_none() && n.same_as(event)),
+      };
+      if !kept {
+        ret.removed.push(Self::describe(event));
       }
     }
     ret
   }
 
+  /// Describes some event by its summary and `UID`.
+  fn describe(event: &Event) -> String {
+    match &event.uid {
+      Some(uid) => format!("{} (UID `{}`)", event.summary, uid),
+      None => event.summary.clone(),
+    }
+  }
+
+  /// Checks whether no event changes.
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+  }
+}
+
+impl std::fmt::Display for EventDiff {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} added, {} removed, {} modified",
+      self.added.len(),
+      self.removed.len(),
+      self.modified.len()
+    )?;
+    for (mark, events) in
+      [("+", &self.added), ("-", &self.removed), ("~", &self.modified)]
+    {
+      for event in events {
+        write!(f, "\n  {} {}", mark, event)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl NameMap<Calendar> {
+  /// Computes the busy time within some `MinInterval`, ie. the union of the
+  /// busy time of all calendars, where overlapping events are counted once.
+  pub fn busy_set(&self, miv: MinInterval) -> IntervalSet {
+    self
+      .contents
+      .values()
+      .map(|cal| cal.busy_set(miv))
+      .fold(IntervalSet::default(), |busy, cal_busy| busy.union(&cal_busy))
+  }
+
   /// Givent the collection of events, compute the relative impact of a task.
   pub fn impact(&self, todo: &Task) -> ExpirableImpact {
     let miv = MinInterval::from_now_till(todo.due);
-    let total_time = miv.num_min();
-    let occupied_time = self.overlap_miv(miv);
-    let available_time = total_time
-      .checked_sub(occupied_time)
-      .expect("Occupied time is more than total time");
+    let free = IntervalSet::from(miv).difference(&self.busy_set(miv));
+    let available_time = free.num_min();
     let needed_time = todo.get_remaining_workload().num_min();
 
     ExpirableImpact::from((needed_time as f32) / (available_time as f32))
@@ -83,8 +249,210 @@ impl NameMap<Vec<Event>> {
 
   /// Performs filtration across events.
   pub fn filter_events<F: Fn(&Event) -> bool>(&mut self, f: F) {
-    for (_, v) in &mut self.contents {
-      v.retain(&f);
+    for cal in self.contents.values_mut() {
+      cal.events.retain(&f);
+    }
+  }
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[cfg(test)]
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::test_util::{self, vcalendar, OCT_9_2023};
+  use crate::time::{timezone::ZoneOffset, MinInstant};
+
+  use super::task::Workload;
+
+  use super::*;
+
+  /// Parses the given `VEVENT`s in UTC into events.
+  fn parse_events(vevents: &str) -> Vec<Event> {
+    test_util::parse_events(&vcalendar(vevents))
+  }
+
+  #[test]
+  fn all_day_blocking() {
+    let vevents = "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20231010\n\
+      SUMMARY:holiday\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      SUMMARY:class\nEND:VEVENT\n";
+    let start = MinInstant::from_raw_utc(OCT_9_2023).unwrap();
+    let miv = MinInterval::new(start, start.advance(3 * 1440).unwrap());
+
+    let mut cals = NameMap::<Calendar> { contents: HashMap::new() };
+    cals
+      .unique_insert("cal", Calendar::new(parse_events(vevents), false))
+      .unwrap();
+    assert_eq!(60, cals.busy_set(miv).num_min());
+
+    // the class falls within the holiday, and is not counted again.
+    cals.get_mut("cal").unwrap().all_day_blocking = true;
+    assert_eq!(1440, cals.busy_set(miv).num_min());
+  }
+
+  #[test]
+  fn transparent_and_cancelled() {
+    let vevents = "BEGIN:VEVENT\nDTSTART:20231010T090000Z\n\
+      DTEND:20231010T100000Z\nTRANSP:TRANSPARENT\nSUMMARY:free\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231010T110000Z\nDTEND:20231010T120000Z\n\
+      SUMMARY:cancelled\nSTATUS:CANCELLED\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231010T130000Z\nDTEND:20231010T140000Z\n\
+      TRANSP:OPAQUE\nSUMMARY:busy\nSTATUS:TENTATIVE\nEND:VEVENT\n";
+    let events = parse_events(vevents);
+    assert_eq!(
+      "free [transparent]",
+      events[0].to_string().lines().next().unwrap()
+    );
+    assert_eq!(
+      "cancelled [cancelled]",
+      events[1].to_string().lines().next().unwrap()
+    );
+    assert_eq!(
+      "busy [tentative]",
+      events[2].to_string().lines().next().unwrap()
+    );
+
+    let start = MinInstant::from_raw_utc(OCT_9_2023).unwrap();
+    let miv = MinInterval::new(start, start.advance(3 * 1440).unwrap());
+    let mut cals = NameMap::<Calendar> { contents: HashMap::new() };
+    cals.unique_insert("cal", Calendar::new(events, false)).unwrap();
+    assert_eq!(60, cals.busy_set(miv).num_min());
+  }
+
+  #[test]
+  fn legacy_event() {
+    let events = parse_events(
+      "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      TRANSP:TRANSPARENT\nSUMMARY:class\nEND:VEVENT\n",
+    );
+    let stored = serde_json::json!([events[0].summary, events[0].recurrence]);
+    let event: Event = serde_json::from_value(stored).unwrap();
+    assert!(event.occupies_time());
+    assert!(event.categories.is_empty());
+    assert_eq!(None, event.uid);
+  }
+
+  #[test]
+  fn event_properties() {
+    let events = parse_events(
+      "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      SUMMARY:class\nLOCATION:Room 1\nCATEGORIES:Work,Study\n\
+      DESCRIPTION:bring\\nlaptop\nUID:class@test\nEND:VEVENT\n",
+    );
+    let stored = serde_json::to_string(&events[0]).unwrap();
+    let event: Event = serde_json::from_str(&stored).unwrap();
+    assert_eq!(Some("Room 1".to_string()), event.location);
+    assert_eq!(Some("class@test".to_string()), event.uid);
+    assert!(event.has_category("study"));
+    assert!(!event.has_category("home"));
+    assert!(event.mentions("LAPTOP"));
+    assert!(event.mentions("room"));
+    assert!(!event.mentions("exam"));
+
+    let shown = event.to_string();
+    assert!(shown.starts_with(
+      "class\nLocation: Room 1\nCategories: Work, Study\n\
+      Description: bring\nlaptop\nUID: class@test\n"
+    ));
+  }
+
+  #[test]
+  fn legacy_calendar() {
+    let cal = Calendar::new(
+      parse_events(
+        "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      SUMMARY:class\nEND:VEVENT\n",
+      ),
+      true,
+    );
+    let legacy = serde_json::to_string(&cal.events).unwrap();
+    let cal: Calendar = serde_json::from_str(&legacy).unwrap();
+    assert_eq!(1, cal.events.len());
+    assert!(!cal.all_day_blocking);
+
+    let current =
+      serde_json::to_string(&Calendar::new(cal.events, true)).unwrap();
+    let cal: Calendar = serde_json::from_str(&current).unwrap();
+    assert!(cal.all_day_blocking);
+  }
+
+  #[test]
+  fn event_diff() {
+    let old = parse_events(
+      "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      SUMMARY:class\nUID:class@test\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231011T090000Z\nDTEND:20231011T100000Z\n\
+      SUMMARY:exam\nUID:exam@test\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231012T090000Z\nDTEND:20231012T100000Z\n\
+      SUMMARY:lunch\nEND:VEVENT\n",
+    );
+    let new = parse_events(
+      "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      SUMMARY:class\nUID:class@test\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231011T100000Z\nDTEND:20231011T110000Z\n\
+      SUMMARY:exam\nUID:exam@test\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231012T090000Z\nDTEND:20231012T100000Z\n\
+      SUMMARY:lunch\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231013T090000Z\nDTEND:20231013T100000Z\n\
+      SUMMARY:party\nUID:party@test\nEND:VEVENT\n",
+    );
+    let diff = EventDiff::new(&old, &new);
+    assert_eq!(
+      "1 added, 0 removed, 1 modified\n  + party (UID `party@test`)\n  \
+      ~ exam (UID `exam@test`)",
+      diff.to_string()
+    );
+
+    let diff = EventDiff::new(&new[1..], &old);
+    assert_eq!(vec!["class (UID `class@test`)"], diff.added);
+    assert_eq!(vec!["party (UID `party@test`)"], diff.removed);
+    assert!(EventDiff::new(&old, &old).is_empty());
+  }
+
+  #[test]
+  fn legacy_calendar_source() {
+    let cal = Calendar::new(vec![], false)
+      .with_source(PathBuf::from("/tmp/school.ics"), 42);
+    let stored = serde_json::to_string(&cal).unwrap();
+    let cal: Calendar = serde_json::from_str(&stored).unwrap();
+    assert_eq!(Some(PathBuf::from("/tmp/school.ics")), cal.source);
+    assert_eq!(Some(42), cal.hash);
+
+    let legacy = r#"{"events":[],"all_day_blocking":true}"#;
+    let cal: Calendar = serde_json::from_str(legacy).unwrap();
+    assert_eq!(None, cal.source);
+    assert_eq!(None, cal.hash);
+  }
+
+  #[test]
+  fn overlapping_calendars() {
+    let meeting = "BEGIN:VEVENT\nDTSTART:20231010T090000Z\n\
+      DTEND:20231010T100000Z\nSUMMARY:meeting\nEND:VEVENT\n";
+    let lunch = "BEGIN:VEVENT\nDTSTART:20231010T093000Z\n\
+      DTEND:20231010T110000Z\nSUMMARY:lunch\nEND:VEVENT\n";
+    let start = MinInstant::from_raw_utc(OCT_9_2023).unwrap();
+    let miv = MinInterval::new(start, start.advance(3 * 1440).unwrap());
+
+    let mut cals = NameMap::<Calendar> { contents: HashMap::new() };
+    let work = Calendar::new(parse_events(&format!("{meeting}{lunch}")), false);
+    cals.unique_insert("work", work).unwrap();
+    cals
+      .unique_insert("team", Calendar::new(parse_events(meeting), false))
+      .unwrap();
+    assert_eq!(120, cals.busy_set(miv).num_min());
+
+    // the same event in both calendars used to be counted twice, which made
+    // the occupied time exceed the total time.
+    let all_day = "BEGIN:VEVENT\nDTSTART:20000101T000000Z\n\
+      DTEND:21000101T000000Z\nSUMMARY:busy\nEND:VEVENT\n";
+    for name in ["work", "team"] {
+      cals.insert(name, Calendar::new(parse_events(all_day), false));
     }
+    let due = MinInstant::now(&ZoneOffset::utc()).advance(1440).unwrap();
+    let task = Task::new(due, Workload::from_num_min(60).unwrap());
+    assert_eq!(ExpirableImpact::Expired, cals.impact(&task));
   }
 }
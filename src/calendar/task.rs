@@ -3,6 +3,9 @@
 use std::str::FromStr;
 
 use crate::const_params::TASK_IMPACT_EXPIRE_THRESHOLD;
+use crate::ics_parser::ics_syntax::{TodoStatus, Vtodo};
+use crate::ics_parser::ICSProcessError;
+use crate::time::duration::MinDuration;
 use crate::time::fact::SEC_IN_MIN_U32;
 use crate::time::time_parser::parse_u32;
 use crate::time::*;
@@ -52,8 +55,14 @@ impl Workload {
 
 impl FromStr for Workload {
   type Err = TimeError;
+
+  /// Parses either some number of minutes, ie. `90`, or some duration, ie.
+  /// `PT1H30M`.
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    Workload::from_num_min(parse_u32(s)?)
+    match parse_u32(s) {
+      Ok(num_min) => Workload::from_num_min(num_min),
+      Err(_) => Workload::from_num_min(s.parse::<MinDuration>()?.num_min()),
+    }
   }
 }
 
@@ -134,6 +143,42 @@ impl Task {
     self.completion =
       if tgt_progress.is_overflow() { Percent(100) } else { tgt_progress };
   }
+
+  /// Converts a parsed `VTODO` into a task, like `try_from()`.
+  fn from_vtodo(value: Vtodo) -> Result<Self, ICSProcessError> {
+    let Some(due) = value.due else {
+      return Err(ICSProcessError::MissingDue(value.summary));
+    };
+    let Some(estimate) = value.estimate else {
+      return Err(ICSProcessError::MissingEstimate(value.summary));
+    };
+    let num_min = estimate.num_min();
+    let Ok(workload) = Workload::from_num_min(num_min) else {
+      return Err(ICSProcessError::WorkloadOverflow(value.summary, num_min));
+    };
+
+    let mut task = Task::new(due, workload);
+    match (value.percent, value.status) {
+      (Some(n), _) => task.set_progress(Percent(n)),
+      (None, TodoStatus::Completed) => task.set_progress(Percent(100)),
+      (None, _) => (),
+    }
+    Ok(task)
+  }
+}
+
+impl TryFrom<Vtodo> for Task {
+  type Error = ICSProcessError;
+
+  /// Converts a parsed `VTODO` into a task, whose workload is the estimated
+  /// duration. A completed `VTODO` without percentage is 100% complete.
+  /// Errors are located at the `VTODO`.
+  fn try_from(mut value: Vtodo) -> Result<Self, Self::Error> {
+    match value.span.take() {
+      Some(span) => Task::from_vtodo(value).map_err(|e| e.at(span)),
+      None => Task::from_vtodo(value),
+    }
+  }
 }
 
 // ----------------------------- Displays -----------------------------
@@ -166,3 +211,41 @@ impl std::fmt::Display for ExpirableImpact {
     }
   }
 }
+
+// --------------------------------- Testing ---------------------------------
+
+#[cfg(test)]
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::test_util::{parse_ics, vcalendar};
+
+  use super::*;
+
+  /// Converts the only `VTODO` of some calendar into some task, and renders
+  /// the error.
+  fn todo_err(vtodo: &str) -> String {
+    let mut cal = parse_ics(&vcalendar(vtodo)).unwrap();
+    match Task::try_from(cal.todos.remove(0)) {
+      Ok(_) => panic!("`{}` should not convert", vtodo),
+      Err(e) => e.to_string(),
+    }
+  }
+
+  #[test]
+  fn invalid_vtodos() {
+    let err = todo_err("BEGIN:VTODO\nSUMMARY:essay\nEND:VTODO\n");
+    assert!(err.starts_with("test.ics:2:1: VTODO `essay` missing due\n"));
+
+    let err =
+      todo_err("BEGIN:VTODO\nSUMMARY:essay\nDUE:20231010T090000Z\nEND:VTODO\n");
+    assert!(err.starts_with("test.ics:2:1: VTODO `essay` missing estimated-"));
+
+    let err = todo_err(
+      "BEGIN:VTODO\nSUMMARY:essay\nDUE:20231010T090000Z\n\
+      ESTIMATED-DURATION:P100D\nEND:VTODO\n",
+    );
+    let expected = "test.ics:2:1: estimated-duration of VTODO `essay` is \
+      144000 min, exceeding 59999 min\n";
+    assert!(err.starts_with(expected), "{}", err);
+  }
+}
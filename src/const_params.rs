@@ -1,12 +1,17 @@
 //! Dedicated to store compile-time-known constants that influences
 //! design choices.
 
-pub const ICS_ASSUME_TRANSP_ALWAYS_AFTER_SUMMARY: bool = true;
-pub const ICS_DEFAULT_TIME_IN_DAY: &str = "235900";
+pub const ICS_DATE_START_TIME: &str = "000000";
+pub const ICS_DATE_UNTIL_TIME: &str = "235900";
+pub const ICS_FOLD_OCTETS: usize = 75;
+pub const ICS_PRODID: &str = "-//TaggyTime//TaggyTime//EN";
 pub const MAX_WORKLOAD: u32 = 59_999;
-pub const HANDLE_WKST: bool = false;
-pub const PARSE_DT_LITERAL_TZID: bool = false;
+
+/// The number of periods, ie. years for `FREQ=YEARLY`, without any occurrence
+/// after which some recurrence is deemed ended.
+pub const RRULE_MAX_IDLE_PERIODS: u32 = 100;
 pub const TAGGYENV_RELATIVE_PATH: &str = ".local/taggytime/env.json";
+pub const WATCH_INTERVAL_SECS: u64 = 5;
 
 /// The impact threshold which renders a task undoable (i.e. expired).
 pub const TASK_IMPACT_EXPIRE_THRESHOLD: u16 = 300;
@@ -0,0 +1,305 @@
+//! Content lines of `.ics` files, as per RFC 5545 section 3.1.
+
+use crate::const_params::ICS_FOLD_OCTETS;
+
+/// Some unfolded content line, split into its name, parameters and value, ie.
+/// `DTSTART;TZID=America/New_York:20231010T090000`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContentLine {
+  pub name: String,
+
+  /// Parameters along with their values, which are lists as per
+  /// `param-value *("," param-value)`, ie. `DELEGATED-TO="a","b"`.
+  pub params: Vec<(String, Vec<String>)>,
+
+  pub value: String,
+}
+
+impl ContentLine {
+  /// Splits some unfolded line. Each value of some parameter may be quoted,
+  /// in which case it may contain `;`, `:` and `,`, and is stored without
+  /// quotes. Returns `None` if the line has no value.
+  pub fn parse(line: &str) -> Option<ContentLine> {
+    let name_len = line.find([';', ':'])?;
+    let name = line[..name_len].trim().to_string();
+
+    let mut params = Vec::<(String, Vec<String>)>::new();
+    let mut rest = &line[name_len..];
+    while let Some(param) = rest.strip_prefix(';') {
+      let (param_name, mut after_eq) = param.split_once('=')?;
+
+      let mut param_values = Vec::<String>::new();
+      loop {
+        let (param_value, after_value) = match after_eq.strip_prefix('"') {
+          Some(quoted) => quoted.split_once('"')?,
+          None => after_eq.split_at(after_eq.find([',', ';', ':'])?),
+        };
+        param_values.push(param_value.to_string());
+        match after_value.strip_prefix(',') {
+          Some(next) => after_eq = next,
+          None => {
+            rest = after_value;
+            break;
+          }
+        }
+      }
+      params.push((param_name.trim().to_string(), param_values));
+    }
+
+    let value = rest.strip_prefix(':')?.to_string();
+    Some(ContentLine { name, params, value })
+  }
+}
+
+/// An iterator over the logical lines of some `.ics` content, which unfolds
+/// physical lines that start with a space or a tab into the previous ones.
+/// Both `\r\n` and `\n` line breaks are accepted, and empty lines are skipped.
+pub struct Unfold<I: Iterator> {
+  lines: std::iter::Peekable<I>,
+
+  /// Number of physical lines taken so far.
+  taken: usize,
+
+  /// 1-based number of the physical line where the last logical line starts.
+  line_no: usize,
+}
+
+impl<I, S> Unfold<I>
+where
+  I: Iterator<Item = S>,
+  S: AsRef<str>,
+{
+  pub fn new(lines: I) -> Self {
+    Unfold {
+      lines: lines.peekable(),
+      taken: 0,
+      line_no: 0,
+    }
+  }
+
+  /// Gets the 1-based number of the physical line where the last logical line
+  /// starts.
+  pub fn line_no(&self) -> usize {
+    self.line_no
+  }
+}
+
+/// Checks whether some physical line continues the previous one.
+fn is_folded(line: &str) -> bool {
+  line.starts_with([' ', '\t'])
+}
+
+impl<I, S> Iterator for Unfold<I>
+where
+  I: Iterator<Item = S>,
+  S: AsRef<str>,
+{
+  type Item = String;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let mut ret = loop {
+      let line = self.lines.next()?;
+      self.taken += 1;
+      let line = line.as_ref().trim_end_matches('\r');
+      if !line.trim().is_empty() {
+        break line.to_string();
+      }
+    };
+    self.line_no = self.taken;
+
+    while let Some(line) = self.lines.peek() {
+      let line = line.as_ref().trim_end_matches('\r');
+      if !is_folded(line) {
+        break;
+      }
+      ret.push_str(&line[1..]);
+      self.lines.next();
+      self.taken += 1;
+    }
+    Some(ret)
+  }
+}
+
+/// Splits some list of text values, ie. `CATEGORIES`, at its unescaped
+/// commas.
+pub fn split_list(value: &str) -> Vec<&str> {
+  let mut ret = Vec::<&str>::new();
+  let mut item_start = 0;
+  let mut escaped = false;
+  for (i, c) in value.char_indices() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' => escaped = true,
+      ',' => {
+        ret.push(&value[item_start..i]);
+        item_start = i + 1;
+      }
+      _ => (),
+    }
+  }
+  ret.push(&value[item_start..]);
+  ret
+}
+
+/// Unescapes some text value, ie. `\,`, `\;`, `\\` and `\n`.
+pub fn unescape(value: &str) -> String {
+  let mut ret = String::with_capacity(value.len());
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      ret.push(c);
+      continue;
+    }
+    match chars.next() {
+      Some('n' | 'N') => ret.push('\n'),
+      Some(escaped) => ret.push(escaped),
+      None => ret.push('\\'),
+    }
+  }
+  ret
+}
+
+/// Escapes some text value, which is the inverse of `unescape()`.
+pub fn escape(value: &str) -> String {
+  let mut ret = String::with_capacity(value.len());
+  for c in value.chars() {
+    match c {
+      '\\' | ';' | ',' => {
+        ret.push('\\');
+        ret.push(c);
+      }
+      '\n' => ret.push_str("\\n"),
+      '\r' => (),
+      _ => ret.push(c),
+    }
+  }
+  ret
+}
+
+/// Folds some logical line into physical lines of at most `ICS_FOLD_OCTETS`
+/// octets each, which is the inverse of `Unfold`. Lines are never broken
+/// inside some multi-byte character. Every physical line ends with `\r\n`.
+pub fn fold(line: &str) -> String {
+  let mut ret = String::with_capacity(line.len() + 3);
+  let mut rest = line;
+  let mut limit = ICS_FOLD_OCTETS;
+  loop {
+    if rest.len() <= limit {
+      ret.push_str(rest);
+      ret.push_str("\r\n");
+      break ret;
+    }
+    let mut split_at = limit;
+    while !rest.is_char_boundary(split_at) {
+      split_at -= 1;
+    }
+    ret.push_str(&rest[..split_at]);
+    ret.push_str("\r\n ");
+    rest = &rest[split_at..];
+
+    // the leading space of continuation lines counts towards the limit.
+    limit = ICS_FOLD_OCTETS - 1;
+  }
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn unfold_lines() {
+    let content = "BEGIN:VEVENT\r\nSUMMARY:Long\r\n  summary\r\n\tline\r\n\r\n\
+      DESCRIPTION:a\n b\nEND:VEVENT";
+    let lines: Vec<String> = Unfold::new(content.lines()).collect();
+    let expected = [
+      "BEGIN:VEVENT",
+      "SUMMARY:Long summaryline",
+      "DESCRIPTION:ab",
+      "END:VEVENT",
+    ];
+    assert_eq!(expected.to_vec(), lines);
+
+    let mut unfold = Unfold::new(content.lines());
+    let mut line_nos = Vec::<usize>::new();
+    while unfold.next().is_some() {
+      line_nos.push(unfold.line_no());
+    }
+    assert_eq!(vec![1, 2, 6, 8], line_nos);
+  }
+
+  #[test]
+  fn split_content_line() {
+    let line = ContentLine::parse(
+      "ATTENDEE;CN=\"Doe; John\";ROLE=CHAIR:mailto:jdoe@example.com",
+    )
+    .unwrap();
+    assert_eq!("ATTENDEE", line.name);
+    assert_eq!(
+      vec![
+        ("CN".to_string(), vec!["Doe; John".to_string()]),
+        ("ROLE".to_string(), vec!["CHAIR".to_string()])
+      ],
+      line.params
+    );
+    assert_eq!("mailto:jdoe@example.com", line.value);
+
+    assert!(ContentLine::parse("NO VALUE").is_none());
+    assert!(ContentLine::parse("DTSTART;TZID=\"unclosed:1").is_none());
+  }
+
+  #[test]
+  fn param_value_lists() {
+    let line = ContentLine::parse(
+      "ATTENDEE;MEMBER=\"mailto:a@x\",\"mailto:b@x\";DELEGATED-TO=c,\"d;e\"\
+      :mailto:f@x",
+    )
+    .unwrap();
+    let params: Vec<(&str, Vec<&str>)> = line
+      .params
+      .iter()
+      .map(|(k, v)| (k.as_str(), v.iter().map(String::as_str).collect()))
+      .collect();
+    let expected = vec![
+      ("MEMBER", vec!["mailto:a@x", "mailto:b@x"]),
+      ("DELEGATED-TO", vec!["c", "d;e"]),
+    ];
+    assert_eq!(expected, params);
+    assert_eq!("mailto:f@x", line.value);
+
+    assert!(ContentLine::parse("ATTENDEE;MEMBER=\"a\"b:c").is_none());
+  }
+
+  #[test]
+  fn split_text_list() {
+    assert_eq!(vec!["a", "b\\,c", "", "d"], split_list("a,b\\,c,,d"));
+    assert_eq!(vec!["single\\\\"], split_list("single\\\\"));
+  }
+
+  #[test]
+  fn escape_text() {
+    let text = "Meeting; with, friends\nat C:\\ room";
+    assert_eq!("Meeting\\; with\\, friends\\nat C:\\\\ room", escape(text));
+    assert_eq!(text, unescape(&escape(text)));
+  }
+
+  #[test]
+  fn fold_lines() {
+    assert_eq!("SUMMARY:short\r\n", fold("SUMMARY:short"));
+
+    let long = format!("DESCRIPTION:{}", "é".repeat(60));
+    let folded = fold(&long);
+    assert!(folded.split("\r\n").all(|l| l.len() <= ICS_FOLD_OCTETS));
+    let unfolded: Vec<String> = Unfold::new(folded.lines()).collect();
+    assert_eq!(vec![long], unfolded);
+  }
+
+  #[test]
+  fn unescape_text() {
+    assert_eq!(
+      "Meeting; with, friends\nat C:\\ room",
+      unescape("Meeting\\; with\\, friends\\nat C:\\\\ room")
+    );
+  }
+}
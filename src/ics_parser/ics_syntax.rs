@@ -3,31 +3,140 @@
 //! Note that many details in ICalendar are purposefully omitted, because they
 //! are less relevant to workload calculation.
 
+use std::{collections::HashMap, mem};
+
 use crate::{
-  const_params::{ICS_DEFAULT_TIME_IN_DAY, PARSE_DT_LITERAL_TZID},
-  ics_parser::lexer,
-  time::{date::Date, timezone::ZoneOffset, MinInstant, MinInterval},
+  const_params::{ICS_DATE_START_TIME, ICS_DATE_UNTIL_TIME},
+  time::{
+    date::Date,
+    duration::MinDuration,
+    fact::MIN_IN_HR,
+    timezone::{TimeZone, Tz, ZoneOffset},
+    tzdata,
+    week::Weekday,
+    zone_rules::{Observance, TransitionRule, ZoneRules},
+    MinInstant, MinInterval,
+  },
 };
 
+use serde::{Deserialize, Serialize};
+
 use super::{
-  lexer::{IcsLexer, Token},
+  lexer::{IcsLexer, Span, Token},
   peekbuf::PeekBuffer,
   ICSProcessError,
 };
 
 pub struct ICalendar {
   name: String,
+
+  /// Name of the calendar given by `X-WR-CALNAME`, if any.
+  pub cal_name: Option<String>,
+
   pub content: Vec<Vevent>,
+  pub todos: Vec<Vtodo>,
+
+  /// Timezones defined by `VTIMEZONE`, indexed by their `TZID`.
+  pub zones: HashMap<String, ZoneRules>,
+
+  /// Malformed components skipped in lenient mode.
+  pub warnings: Vec<ImportWarning>,
+}
+
+/// Some component skipped in lenient mode, ie. a malformed `VEVENT`, along
+/// with why it is skipped.
+pub struct ImportWarning {
+  /// Kind of the component, ie. `VEVENT`.
+  pub component: Token,
+
+  /// 0-based position of the component among those of the same kind.
+  pub index: usize,
+
+  pub uid: Option<String>,
+  pub reason: ICSProcessError,
 }
 
 pub struct Vevent {
+  /// 0-based position of this `VEVENT` in its calendar, counting the skipped
+  /// ones, which is set by `ICSParser::parse()`.
+  pub index: usize,
+
   pub repeat: Option<FreqAndRRules>, // corrsponds to `Pattern::Once | Many`.
   pub miv: MinInterval,
   pub summary: String,
+  pub exdates: Vec<MinInstant>,
+  pub rdates: Vec<MinInstant>,
+  pub uid: Option<String>,
+  pub description: Option<String>,
+  pub location: Option<String>,
+  pub categories: Vec<String>,
+  pub url: Option<String>,
+
+  /// Timezone of `DTSTART`, in which the event recurs.
+  pub zone: Tz,
+
+  /// Whether `DTSTART` is a date, ie. `DTSTART;VALUE=DATE:20231010`, which
+  /// makes an all-day event.
+  pub all_day: bool,
+
+  /// Original starting time of the occurrence overridden by this `VEVENT`.
+  pub recurrence_id: Option<MinInstant>,
+
+  pub transp: Transp,
+  pub status: Status,
+}
+
+pub struct Vtodo {
+  pub summary: String,
+  pub uid: Option<String>,
+  pub due: Option<MinInstant>,
+
+  /// Time needed to complete the task, ie. `ESTIMATED-DURATION`.
+  pub estimate: Option<MinDuration>,
+
+  /// Progress of the task, ie. `PERCENT-COMPLETE`.
+  pub percent: Option<u16>,
+
+  pub status: TodoStatus,
+
+  /// Where the `VTODO` begins, to locate errors found once it is converted
+  /// into some task.
+  pub span: Option<Span>,
+}
+
+/// Status of some task, ie. `STATUS` of some `VTODO`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TodoStatus {
+  #[default]
+  NeedsAction,
+  InProcess,
+  Completed,
+  Cancelled,
+}
+
+/// Whether some event occupies time, ie. `TRANSP`.
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum Transp {
+  #[default]
+  Opaque,
+  Transparent,
+}
+
+/// Status of some event, ie. `STATUS`.
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum Status {
+  Tentative,
+  #[default]
+  Confirmed,
+  Cancelled,
 }
 
 /// Frequency of some `RRULE` line.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Freq {
   Daily,
   Weekly,
@@ -35,6 +144,28 @@ pub enum Freq {
   Yearly,
 }
 
+impl Freq {
+  /// Name of the time unit that such a frequency repeats over.
+  pub fn unit(&self) -> &'static str {
+    match self {
+      Freq::Daily => "day",
+      Freq::Weekly => "week",
+      Freq::Monthly => "month",
+      Freq::Yearly => "year",
+    }
+  }
+
+  /// Number of days in the longest period of such a frequency.
+  pub fn max_days(&self) -> u32 {
+    match self {
+      Freq::Daily => 1,
+      Freq::Weekly => 7,
+      Freq::Monthly => 31,
+      Freq::Yearly => 366,
+    }
+  }
+}
+
 /// A single recurrence rule, in the form `BYXXX=item, item, item...`.
 /// Composed of tokens, and may not be valid.
 pub struct RRuleToks {
@@ -45,19 +176,88 @@ pub struct RRuleToks {
 /// A frequency paired with a vec of `RRuleToks`.
 /// Corresponds to `Pattern::Many`. Specifically, `freq` indicates the specific
 /// variant of `Repeat`, `content` encodes the potential rules for such a
-/// variant, `interval` is self explanatory, and `count`, `until` are for
-/// `Term`.
+/// variant, `interval` is self explanatory, `count`, `until` are for
+/// `Term`, and `wkst` is the starting day of weeks.
 pub struct FreqAndRRules {
   pub freq: Freq,
   pub content: Vec<RRuleToks>,
   pub interval: usize,
   pub count: Option<usize>,
   pub until: Option<MinInstant>,
+  pub wkst: Weekday,
+
+  /// Whether `until` is some local time rather than UTC, which shall be in
+  /// the timezone of `DTSTART`, as per RFC 5545.
+  pub floating_until: bool,
+
+  /// Where the `RRULE` line is, to locate errors found once the rules are
+  /// interpreted.
+  pub span: Option<Span>,
+}
+
+/// Digits of some datetime literal, ie. `20231010T090000Z`.
+struct DtDigits {
+  ymd: String,
+
+  /// Time of day, which is absent for some date literal.
+  hms: Option<String>,
+
+  /// Whether the literal ends with `Z`, ie. is in UTC.
+  utc: bool,
+
+  /// Where the whole literal is, to locate errors found once the digits are
+  /// interpreted.
+  span: Option<Span>,
+}
+
+impl DtDigits {
+  /// Interprets the wall-clock time of the literal, where some date literal
+  /// is at `date_hms`. Errors are located at the literal.
+  fn date(&self, date_hms: &str) -> Result<Date, ICSProcessError> {
+    let hms = self.hms.as_deref().unwrap_or(date_hms);
+    Date::from_ics_time_string(&self.ymd, hms, ZoneOffset::utc())
+      .map_err(|e| self.locate(e))
+  }
+
+  /// Gets the time of day of the literal in minutes, which may be of some date
+  /// before the Unix Epoch. Some date literal is at midnight.
+  fn time_of_day(&self) -> Result<u32, ICSProcessError> {
+    let hms = self.hms.as_deref().unwrap_or("000000");
+    let dt = Date::from_ics_time_string("19700101", hms, ZoneOffset::utc())
+      .map_err(|e| self.locate(e))?;
+    Ok(dt.hr * MIN_IN_HR + dt.min)
+  }
+
+  /// Interprets the literal as local time of some timezone, like `date()`.
+  fn localize<Z: TimeZone>(
+    &self,
+    zone: &Z,
+    date_hms: &str,
+  ) -> Result<MinInstant, ICSProcessError> {
+    zone.localize(&self.date(date_hms)?).map_err(|_| {
+      let hms = self.hms.as_deref().unwrap_or(date_hms).to_string();
+      self.locate(ICSProcessError::ICSTimeMalformatted(self.ymd.clone(), hms))
+    })
+  }
+
+  /// Locates some error at the literal.
+  fn locate(&self, e: ICSProcessError) -> ICSProcessError {
+    match &self.span {
+      Some(span) => e.at(span.clone()),
+      None => e,
+    }
+  }
 }
 
 pub struct ICSParser<'a> {
   name: String,
   peekbuf: PeekBuffer<'a>,
+  zones: HashMap<String, ZoneRules>,
+
+  /// Whether malformed `VEVENT`s and `VTODO`s are skipped as warnings, rather
+  /// than failing the whole calendar.
+  lenient: bool,
+  warnings: Vec<ImportWarning>,
 }
 
 impl<'a> ICSParser<'a> {
@@ -68,28 +268,45 @@ impl<'a> ICSParser<'a> {
     ICSParser {
       name: lex.get_name(),
       peekbuf: PeekBuffer::from_lexer(lex),
+      zones: HashMap::new(),
+      lenient: false,
+      warnings: vec![],
     }
   }
 
-  /// Peeks the `pos`th position ahead, with `pos=0` indicating the head.
-  fn peek(&mut self, pos: usize) -> Result<&Token, &ICSProcessError> {
-    self.peekbuf.peek(pos)
+  /// Makes the parser lenient, which skips malformed `VEVENT`s and `VTODO`s,
+  /// and records them as warnings.
+  pub fn lenient(mut self, lenient: bool) -> Self {
+    self.lenient = lenient;
+    self
   }
 
-  /// Similar to peek but copies things
-  fn peek_copy(&mut self, pos: usize) -> Result<Token, ICSProcessError> {
-    let ret = self.peekbuf.peek(pos);
-    match ret {
-      Ok(x) => Ok(x.clone()),
+  /// Peeks the `pos`th position ahead, with `pos=0` indicating the head.
+  fn peek(&mut self, pos: usize) -> Result<Token, ICSProcessError> {
+    match self.peekbuf.peek(pos) {
+      Ok(tok) => Ok(*tok),
       Err(e) => Err(e.clone()),
     }
   }
 
+  /// Tells if the `pos`th position ahead is `Other` with the given text, ie.
+  /// `T` of some datetime literal.
+  fn peek_other(&mut self, pos: usize, text: &str) -> bool {
+    matches!(self.peek(pos), Ok(Token::Other))
+      && self.peekbuf.peek_text(pos) == text
+  }
+
   /// Takes a token from lexer and advance the stream.
   fn token(&mut self) -> Result<Token, ICSProcessError> {
     self.peekbuf.token()
   }
 
+  /// Takes a token from lexer, and returns the string it stands for.
+  fn token_string(&mut self) -> Result<String, ICSProcessError> {
+    let tok = self.token()?;
+    Ok(tok.string(self.peekbuf.text()))
+  }
+
   /// Takes a token from lexer, advances the stream, and checks that the token
   /// is indeed expected.
   fn munch(&mut self, expected_tok: Token) -> Result<(), ICSProcessError> {
@@ -99,7 +316,8 @@ impl<'a> ICSParser<'a> {
     } else {
       Err(ICSProcessError::Other(format!(
         "Expected {}, found {}",
-        expected_tok, tok
+        expected_tok,
+        tok.describe(self.peekbuf.text())
       )))
     }
   }
@@ -116,22 +334,49 @@ impl<'a> ICSParser<'a> {
   where
     F: Fn(&Token) -> bool,
   {
-    while let Ok(t) = self.peek(0) {
-      if cond(t) {
-        return Ok(());
+    loop {
+      match self.peek(0) {
+        Ok(t) if cond(&t) => return Ok(()),
+        Ok(_) => self.skip()?,
+        Err(e) => return Err(e),
       }
-      self.skip()?;
     }
-    Err(ICSProcessError::EOF)
   }
 
-  /// Takes a token, verifies that it is of `Number(..)` variant, and
-  /// returns the corresponding String literal.
+  /// Skips some component nested in another, ie. `VALARM` in `VEVENT`, along
+  /// with the components nested in it.
+  fn skip_component(&mut self) -> Result<(), ICSProcessError> {
+    self.munch(Token::BEGIN)?;
+    self.munch(Token::COLON)?;
+    let tag = self.token()?;
+    loop {
+      self.skip_until_lambda(|t| t == &Token::BEGIN || t == &Token::END)?;
+      if self.peek(0)? == Token::BEGIN {
+        self.skip_component()?;
+        continue;
+      }
+
+      self.munch(Token::END)?;
+      self.munch(Token::COLON)?;
+      let end_tag = self.token()?;
+      if end_tag == tag {
+        break Ok(());
+      }
+      break Err(ICSProcessError::Other(format!(
+        "{} contains unexpected end: {}",
+        tag, end_tag
+      )));
+    }
+  }
+
+  /// Takes a token, verifies that it is of `Number` variant, and returns the
+  /// corresponding String literal.
   fn number(&mut self) -> Result<String, ICSProcessError> {
-    let tok = self.token()?;
-    match tok {
-      Token::Number(s) => Ok(s),
-      bad_tok => Err(ICSProcessError::NaN(bad_tok)),
+    match self.token()? {
+      Token::Number => Ok(self.peekbuf.text().to_string()),
+      bad_tok => {
+        Err(ICSProcessError::NaN(bad_tok.describe(self.peekbuf.text())))
+      }
     }
   }
 
@@ -143,16 +388,14 @@ impl<'a> ICSParser<'a> {
   {
     let mut ret = String::new();
     while let Ok(t) = self.peek(0) {
-      if cond(t) {
+      if cond(&t) {
         break;
       }
-      let head = self.token()?;
-      ret.push_str(&head.cast_as_string());
-
-      // when an ics file string spans to next line, it seens to always start
-      // with an extra space; this needs to be skipped.
-      if let (Token::NEXTLINE, Token::SPACE) = (head, self.peek(0)?) {
-        self.skip()?;
+      let s = self.token_string()?;
+      if ret.is_empty() {
+        ret = s;
+      } else {
+        ret.push_str(&s);
       }
     }
     Ok(ret)
@@ -160,24 +403,117 @@ impl<'a> ICSParser<'a> {
 
   // --------------------------- Main Functionality ---------------------------
 
+  /// Parses every `VCALENDAR` in the stream into one calendar, where errors
+  /// are located at the last token taken, unless the lexer has located them
+  /// already.
   pub fn parse(
     &mut self,
-    calendar_tz: ZoneOffset,
+    calendar_tz: &Tz,
+  ) -> Result<ICalendar, ICSProcessError> {
+    self.calendars(calendar_tz).map_err(|e| match self.peekbuf.span() {
+      Some(span) => e.at(span.clone()),
+      None => e,
+    })
+  }
+
+  /// Parses the `VCALENDAR` components up to the end of file. Their events,
+  /// tasks and timezones are merged, and the first `X-WR-CALNAME` names the
+  /// merged calendar.
+  fn calendars(
+    &mut self,
+    calendar_tz: &Tz,
   ) -> Result<ICalendar, ICSProcessError> {
-    let mut vevents = Vec::<Vevent>::new();
+    let mut cal = ICalendar {
+      name: self.name.clone(),
+      cal_name: None,
+      content: vec![],
+      todos: vec![],
+      zones: HashMap::new(),
+      warnings: vec![],
+    };
+    loop {
+      self.calendar(calendar_tz, &mut cal)?;
+      match self.skip_until_lambda(|t| t == &Token::BEGIN) {
+        Err(ICSProcessError::EOF) => break,
+        Err(e) => return Err(e),
+        Ok(()) => continue,
+      }
+    }
+    cal.zones = mem::take(&mut self.zones);
+    cal.warnings = mem::take(&mut self.warnings);
+    Ok(cal)
+  }
+
+  /// Parses some `VCALENDAR` component into the given calendar. Floating
+  /// times are in the zone given by `X-WR-TIMEZONE`, if any, or else in
+  /// `calendar_tz`.
+  fn calendar(
+    &mut self,
+    calendar_tz: &Tz,
+    cal: &mut ICalendar,
+  ) -> Result<(), ICSProcessError> {
+    let mut wr_timezone: Option<String> = None;
 
     self.munch(Token::BEGIN)?;
     self.munch(Token::COLON)?;
     self.munch(Token::VCALENDAR)?;
 
     loop {
-      self.skip_until_lambda(|c| c == &Token::BEGIN || c == &Token::END)?;
-      match (self.peek_copy(0)?, self.peek_copy(1)?, self.peek_copy(2)?) {
+      self.skip_until_lambda(|c| {
+        matches!(
+          c,
+          Token::BEGIN | Token::END | Token::XWRCALNAME | Token::XWRTIMEZONE
+        )
+      })?;
+
+      // in lenient mode, an unknown `X-WR-TIMEZONE` is dropped as a warning.
+      let tz = match self.default_zone(wr_timezone.as_deref(), calendar_tz) {
+        Ok(tz) => tz,
+        Err(reason) if self.lenient => {
+          self.warnings.push(ImportWarning {
+            component: Token::XWRTIMEZONE,
+            index: 0,
+            uid: None,
+            reason,
+          });
+          wr_timezone = None;
+          calendar_tz.clone()
+        }
+        Err(e) => return Err(e),
+      };
+      match (self.peek(0)?, self.peek(1)?, self.peek(2)?) {
+        (Token::XWRCALNAME, _, _) => {
+          let name = self.text(Token::XWRCALNAME)?;
+          cal.cal_name.get_or_insert(name);
+        }
+        (Token::XWRTIMEZONE, _, _) => {
+          wr_timezone = Some(self.text(Token::XWRTIMEZONE)?);
+        }
         (Token::BEGIN, Token::COLON, Token::VEVENT) => {
-          vevents.push(self.vevent(calendar_tz)?);
+          let index = cal.content.len() + self.n_skipped(&Token::VEVENT);
+          let uid = self.peek_uid(Token::VEVENT);
+          match self.vevent(&tz) {
+            Ok(vevent) => cal.content.push(Vevent { index, ..vevent }),
+            Err(e) => self.skip_malformed(Token::VEVENT, index, uid, e)?,
+          }
+        }
+        (Token::BEGIN, Token::COLON, Token::VTODO) => {
+          let index = cal.todos.len() + self.n_skipped(&Token::VTODO);
+          let uid = self.peek_uid(Token::VTODO);
+          match self.vtodo(&tz) {
+            Ok(vtodo) => cal.todos.push(vtodo),
+            Err(e) => self.skip_malformed(Token::VTODO, index, uid, e)?,
+          }
+        }
+        (Token::BEGIN, Token::COLON, Token::VTIMEZONE) => {
+          let zone = self.vtimezone()?;
+          self.zones.insert(zone.name().to_string(), zone);
         }
         (Token::END, Token::COLON, Token::VCALENDAR) => {
-          break self.end(vevents)
+          self.munch(Token::END)?;
+          self.munch(Token::COLON)?;
+          self.munch(Token::VCALENDAR)?;
+          break Ok(());
         }
         (Token::BEGIN | Token::END, Token::COLON, _) => {
           self.skip()?;
@@ -193,33 +529,122 @@ impl<'a> ICSParser<'a> {
     }
   }
 
-  /// Handles end of `VCALENDAR`.
-  pub fn end(
+  /// Gets the zone of floating times given the `X-WR-TIMEZONE` of the
+  /// calendar, or `calendar_tz` if there is none.
+  fn default_zone(
+    &self,
+    wr_timezone: Option<&str>,
+    calendar_tz: &Tz,
+  ) -> Result<Tz, ICSProcessError> {
+    match wr_timezone {
+      Some(id) => self.named_zone(id),
+      None => Ok(calendar_tz.clone()),
+    }
+  }
+
+  /// Looks up timezone `id` first in the zone table, and then in the
+  /// compiled-in IANA timezones.
+  fn named_zone(&self, id: &str) -> Result<Tz, ICSProcessError> {
+    match self.zones.get(id) {
+      Some(zone) => Ok(Tz::Named(zone.clone())),
+      None => tzdata::lookup(id)
+        .map(Tz::Iana)
+        .ok_or_else(|| ICSProcessError::UnknownTzid(id.to_string())),
+    }
+  }
+
+  /// Counts the components of the given kind skipped so far in lenient mode.
+  fn n_skipped(&self, component: &Token) -> usize {
+    self.warnings.iter().filter(|w| &w.component == component).count()
+  }
+
+  /// In lenient mode, peeks the `UID` of the upcoming component of the given
+  /// kind, so that it is known even if the component turns out malformed.
+  fn peek_uid(&mut self, tag: Token) -> Option<String> {
+    if !self.lenient {
+      return None;
+    }
+    let mut pos = 0;
+    loop {
+      match (self.peek(pos), self.peek(pos + 1)) {
+        (Ok(Token::UID), Ok(Token::COLON)) => match self.peek(pos + 2) {
+          Ok(tok @ Token::Text) => {
+            break Some(tok.string(self.peekbuf.peek_text(pos + 2)))
+          }
+          _ => break None,
+        },
+        (Ok(Token::END), Ok(Token::COLON))
+          if self.peek(pos + 2).is_ok_and(|t| t == tag) =>
+        {
+          break None;
+        }
+        (Ok(_), _) => pos += 1,
+        (Err(_), _) => break None,
+      }
+    }
+  }
+
+  /// Handles some malformed component of the given kind. In lenient mode, the
+  /// error is recorded as a warning, and the parser skips to the next
+  /// top-level component; otherwise, the error is returned.
+  fn skip_malformed(
     &mut self,
-    vevents: Vec<Vevent>,
-  ) -> Result<ICalendar, ICSProcessError> {
-    self.munch(Token::END)?;
-    self.munch(Token::COLON)?;
-    self.munch(Token::VCALENDAR)?;
+    component: Token,
+    index: usize,
+    uid: Option<String>,
+    e: ICSProcessError,
+  ) -> Result<(), ICSProcessError> {
+    if !self.lenient {
+      return Err(e);
+    }
+    let reason = match self.peekbuf.span() {
+      Some(span) => e.at(span.clone()),
+      None => e,
+    };
+    self.warnings.push(ImportWarning { component, index, uid, reason });
 
-    return Ok(ICalendar {
-      name: self.name.clone(),
-      content: vevents,
-    });
+    loop {
+      match (self.peek(0), self.peek(1), self.peek(2)) {
+        (Ok(Token::BEGIN), Ok(Token::COLON), Ok(tag))
+          if [Token::VEVENT, Token::VTODO, Token::VTIMEZONE].contains(&tag) =>
+        {
+          break Ok(());
+        }
+        (Ok(Token::END), Ok(Token::COLON), Ok(Token::VCALENDAR)) => {
+          break Ok(())
+        }
+        (Err(ICSProcessError::EOF), _, _) => break Err(ICSProcessError::EOF),
+        // lexing errors are skipped along with the tokens.
+        _ => {
+          let _ = self.token();
+        }
+      }
+    }
   }
 
   /// Parses some `VEVENT` from calendar. Note that only `DTSTART`, `DTEND`,
-  /// `SUMMARY`, and `RRULE` will be processed; all other components are
-  /// simply discarded.
-  pub fn vevent(&mut self, tz: ZoneOffset) -> Result<Vevent, ICSProcessError> {
+  /// `SUMMARY`, `RRULE`, `EXDATE`, `RDATE`, `UID` and `RECURRENCE-ID` will be
+  /// processed; all other components are simply discarded.
+  pub fn vevent(&mut self, tz: &Tz) -> Result<Vevent, ICSProcessError> {
     self.munch(Token::BEGIN)?;
     self.munch(Token::COLON)?;
     self.munch(Token::VEVENT)?;
 
-    let mut dtstart: Option<MinInstant> = None;
+    let mut dtstart: Option<(MinInstant, Tz, bool)> = None;
     let mut dtend: Option<MinInstant> = None;
+    let mut duration: Option<MinDuration> = None;
     let mut summary = String::new();
     let mut recur: Option<FreqAndRRules> = None;
+    let mut exdates = Vec::<MinInstant>::new();
+    let mut rdates = Vec::<MinInstant>::new();
+    let mut uid: Option<String> = None;
+    let mut description: Option<String> = None;
+    let mut location: Option<String> = None;
+    let mut categories = Vec::<String>::new();
+    let mut url: Option<String> = None;
+    let mut recurrence_id: Option<MinInstant> = None;
+    let mut transp = Transp::default();
+    let mut status = Status::default();
 
     loop {
       match self.peek(0)? {
@@ -229,25 +654,102 @@ impl<'a> ICSParser<'a> {
         Token::DTEND => {
           dtend = Some(self.dtend(tz)?);
         }
+        Token::DURATION => {
+          duration = Some(self.duration(Token::DURATION)?);
+        }
         Token::SUMMARY => {
-          self.munch(Token::SUMMARY)?;
-          self.munch(Token::COLON)?;
-          summary = self.string_until(lexer::not_in_summary)?;
+          summary = self.text(Token::SUMMARY)?;
         }
         Token::RRULE => {
           recur = Some(self.rrules(tz)?);
         }
+        Token::EXDATE => {
+          exdates.append(&mut self.dt_list(Token::EXDATE, tz)?);
+        }
+        Token::RDATE => {
+          rdates.append(&mut self.dt_list(Token::RDATE, tz)?);
+        }
+        Token::UID => {
+          uid = Some(self.text(Token::UID)?);
+        }
+        Token::DESCRIPTION => {
+          description = Some(self.text(Token::DESCRIPTION)?);
+        }
+        Token::LOCATION => {
+          location = Some(self.text(Token::LOCATION)?);
+        }
+        Token::CATEGORIES => {
+          categories.append(&mut self.text_list(Token::CATEGORIES)?);
+        }
+        Token::URL => {
+          url = Some(self.text(Token::URL)?);
+        }
+        Token::TRANSP => {
+          self.munch(Token::TRANSP)?;
+          self.munch(Token::COLON)?;
+          transp = match self.string_until(|t| t == &Token::NEXTLINE)?.trim() {
+            "TRANSPARENT" => Transp::Transparent,
+            _ => Transp::Opaque,
+          };
+        }
+        Token::STATUS => {
+          self.munch(Token::STATUS)?;
+          self.munch(Token::COLON)?;
+          status = match self.string_until(|t| t == &Token::NEXTLINE)?.trim() {
+            "TENTATIVE" => Status::Tentative,
+            "CANCELLED" => Status::Cancelled,
+            _ => Status::Confirmed,
+          };
+        }
+        Token::RECURRENCEID => {
+          self.munch(Token::RECURRENCEID)?;
+          recurrence_id = Some(self.dt_possible_timezone(tz)?);
+        }
+        Token::BEGIN => {
+          self.skip_component()?;
+        }
         Token::END => {
           self.munch(Token::END)?;
           self.munch(Token::COLON)?;
           let end_tag = self.token()?;
           if end_tag == Token::VEVENT {
+            // an event without `DTEND` lasts for its `DURATION`, or for one
+            // day if it is an all-day event.
+            if let (Some((start, zone, all_day)), None) = (&dtstart, dtend) {
+              dtend = match (duration, all_day) {
+                (Some(dur), _) => dur.after(*start, zone).ok(),
+                (None, true) => MinDuration::new(1, 0)
+                  .and_then(|one_day| one_day.after(*start, zone))
+                  .ok(),
+                (None, false) => None,
+              };
+            }
+            // a local `UNTIL` is in the timezone of `DTSTART`, which may
+            // come after `RRULE`.
+            if let (Some((_, zone, _)), Some(frq)) = (&dtstart, &mut recur) {
+              if let Some(mi) = frq.until.filter(|_| frq.floating_until) {
+                frq.until = Some(mi.localize(zone));
+              }
+            }
             match (dtstart, dtend) {
-              (Some(start), Some(end)) => {
+              (Some((start, zone, all_day)), Some(end)) => {
                 return Ok(Vevent {
+                  index: 0,
                   repeat: recur,
                   miv: MinInterval::new(start, end),
+                  zone,
+                  all_day,
                   summary,
+                  exdates,
+                  rdates,
+                  uid,
+                  description,
+                  location,
+                  categories,
+                  url,
+                  recurrence_id,
+                  transp,
+                  status,
                 });
               }
               (None, _) => {
@@ -258,7 +760,7 @@ impl<'a> ICSParser<'a> {
               }
               _ => {
                 return Err(ICSProcessError::Other(format!(
-                  "VEVENT `{}` missing dtend",
+                  "VEVENT `{}` missing dtend or duration",
                   summary
                 )));
               }
@@ -277,67 +779,367 @@ impl<'a> ICSParser<'a> {
     }
   }
 
-  /// Parses the time associated with some `DTSTART`.
+  /// Parses some `VTODO` from calendar. Note that only `SUMMARY`, `UID`,
+  /// `DUE`, `ESTIMATED-DURATION`, `PERCENT-COMPLETE` and `STATUS` will be
+  /// processed. A date `DUE` means the end of that day.
+  pub fn vtodo(&mut self, tz: &Tz) -> Result<Vtodo, ICSProcessError> {
+    self.munch(Token::BEGIN)?;
+    self.munch(Token::COLON)?;
+    self.munch(Token::VTODO)?;
+
+    let mut todo = Vtodo {
+      summary: String::new(),
+      uid: None,
+      due: None,
+      estimate: None,
+      percent: None,
+      status: TodoStatus::default(),
+      span: self.peekbuf.span().map(Span::whole_line),
+    };
+
+    loop {
+      match self.peek(0)? {
+        Token::SUMMARY => {
+          todo.summary = self.text(Token::SUMMARY)?;
+        }
+        Token::UID => {
+          todo.uid = Some(self.text(Token::UID)?);
+        }
+        Token::DUE => {
+          self.munch(Token::DUE)?;
+          let tzid = self.dt_prefix()?;
+          let (due, _, _) =
+            self.dt_zoned(tzid.as_deref(), tz, ICS_DATE_UNTIL_TIME)?;
+          todo.due = Some(due);
+        }
+        Token::ESTIMATEDDURATION => {
+          todo.estimate = Some(self.duration(Token::ESTIMATEDDURATION)?);
+        }
+        Token::PERCENTCOMPLETE => {
+          let percent = self.text(Token::PERCENTCOMPLETE)?;
+          todo.percent = match percent.trim().parse() {
+            Ok(n) => Some(n),
+            Err(_) => {
+              return Err(ICSProcessError::Other(format!(
+                "`{}` is not percent-complete",
+                percent
+              )))
+            }
+          };
+        }
+        Token::STATUS => {
+          self.munch(Token::STATUS)?;
+          self.munch(Token::COLON)?;
+          todo.status =
+            match self.string_until(|t| t == &Token::NEXTLINE)?.trim() {
+              "IN-PROCESS" => TodoStatus::InProcess,
+              "COMPLETED" => TodoStatus::Completed,
+              "CANCELLED" => TodoStatus::Cancelled,
+              _ => TodoStatus::NeedsAction,
+            };
+        }
+        Token::BEGIN => {
+          self.skip_component()?;
+        }
+        Token::END => {
+          self.munch(Token::END)?;
+          self.munch(Token::COLON)?;
+          break match self.token()? {
+            Token::VTODO => Ok(todo),
+            end_tag => Err(ICSProcessError::Other(format!(
+              "VTODO contains unexpected end: {}",
+              end_tag
+            ))),
+          };
+        }
+        _ => {
+          self.skip()?;
+        }
+      }
+    }
+  }
+
+  /// Parses some `VTIMEZONE` from calendar, which consists of a `TZID` and
+  /// several `STANDARD` or `DAYLIGHT` observances.
+  pub fn vtimezone(&mut self) -> Result<ZoneRules, ICSProcessError> {
+    self.munch(Token::BEGIN)?;
+    self.munch(Token::COLON)?;
+    self.munch(Token::VTIMEZONE)?;
+
+    let mut tzid: Option<String> = None;
+    let mut observances = Vec::<Observance>::new();
+
+    loop {
+      match self.peek(0)? {
+        Token::TZID => {
+          self.munch(Token::TZID)?;
+          self.munch(Token::COLON)?;
+          tzid = Some(self.string_until(|t| t == &Token::NEXTLINE)?);
+        }
+        Token::BEGIN => {
+          observances.push(self.observance()?);
+        }
+        Token::END => {
+          self.munch(Token::END)?;
+          self.munch(Token::COLON)?;
+          self.munch(Token::VTIMEZONE)?;
+          break match tzid {
+            Some(name) => Ok(ZoneRules::new(name.trim(), observances)),
+            None => Err(ICSProcessError::Msg("VTIMEZONE missing tzid")),
+          };
+        }
+        _ => {
+          self.skip()?;
+        }
+      }
+    }
+  }
+
+  /// Parses some `STANDARD` or `DAYLIGHT` observance in `VTIMEZONE`. Note
+  /// that only `TZOFFSETFROM`, `TZOFFSETTO`, `DTSTART`, `RRULE` and `RDATE`
+  /// will be processed.
+  fn observance(&mut self) -> Result<Observance, ICSProcessError> {
+    self.munch(Token::BEGIN)?;
+    self.munch(Token::COLON)?;
+    let kind = match self.token()? {
+      t @ (Token::STANDARD | Token::DAYLIGHT) => t,
+      x => {
+        return Err(ICSProcessError::Other(format!(
+          "Expected STANDARD or DAYLIGHT in VTIMEZONE, found {}",
+          x
+        )))
+      }
+    };
+
+    let mut offset_from: Option<ZoneOffset> = None;
+    let mut offset_to: Option<ZoneOffset> = None;
+    let mut onset: Option<(i64, u32)> = None;
+    let mut recur: Option<FreqAndRRules> = None;
+    let mut rdates = Vec::<i64>::new();
+
+    loop {
+      match self.peek(0)? {
+        Token::TZOFFSETFROM => {
+          self.munch(Token::TZOFFSETFROM)?;
+          self.munch(Token::COLON)?;
+          offset_from = Some(self.utc_offset()?);
+        }
+        Token::TZOFFSETTO => {
+          self.munch(Token::TZOFFSETTO)?;
+          self.munch(Token::COLON)?;
+          offset_to = Some(self.utc_offset()?);
+        }
+        Token::DTSTART => {
+          self.munch(Token::DTSTART)?;
+          self.dt_prefix()?;
+          onset = Some(self.local_onset()?);
+        }
+        Token::RRULE => {
+          recur = Some(self.rrules(&Tz::Fixed(ZoneOffset::utc()))?);
+        }
+        Token::RDATE => {
+          let utc = Tz::Fixed(ZoneOffset::utc());
+          let local_dts = self.dt_list(Token::RDATE, &utc)?;
+          rdates.extend(local_dts.iter().map(|mi| i64::from(mi.raw)));
+        }
+        Token::END => {
+          self.munch(Token::END)?;
+          self.munch(Token::COLON)?;
+          self.munch(kind)?;
+          let (from, to, (onset, time)) = match (offset_from, offset_to, onset)
+          {
+            (Some(from), Some(to), Some(onset)) => (from, to, onset),
+            _ => {
+              return Err(ICSProcessError::Msg(
+                "VTIMEZONE observance missing offsets or dtstart",
+              ))
+            }
+          };
+          let rule = match recur {
+            Some(frq) => Some(TransitionRule::from_rrules(&frq, time)?),
+            None => None,
+          };
+          break Ok(Observance::new(from, to, onset, rule, rdates));
+        }
+        _ => {
+          self.skip()?;
+        }
+      }
+    }
+  }
+
+  /// Parses the local datetime literal of some observance onset, which may
+  /// precede the Unix Epoch, ie. `16010101T020000` exported by Outlook.
+  /// Returns the onset in raw minutes, as well as its time of day in minutes.
+  fn local_onset(&mut self) -> Result<(i64, u32), ICSProcessError> {
+    let digits = self.dt_digits()?;
+    let time = digits.time_of_day()?;
+
+    if digits.ymd.as_str() < "19700101" {
+      return Ok((i64::MIN, time));
+    }
+    let mi = digits.localize(&ZoneOffset::utc(), "000000")?;
+    Ok((i64::from(mi.raw), time))
+  }
+
+  /// Parses some utc offset, in the form of `[+-]hhmm` or `[+-]hhmmss`.
+  fn utc_offset(&mut self) -> Result<ZoneOffset, ICSProcessError> {
+    let sign: i64 = match self.token()? {
+      Token::DASH => -1,
+      Token::Other if self.peekbuf.text() == "+" => 1,
+      x => {
+        return Err(ICSProcessError::Other(format!(
+          "Expected sign of utc offset, found {}",
+          x.describe(self.peekbuf.text())
+        )))
+      }
+    };
+
+    let digits = self.number()?;
+    let bad =
+      || ICSProcessError::Other(format!("`{}` is not utc offset", digits));
+    let (hr, min) = match (digits.get(0..2), digits.get(2..4)) {
+      (Some(h), Some(m)) => (h.parse::<i64>(), m.parse::<i64>()),
+      _ => return Err(bad()),
+    };
+    match (hr, min) {
+      (Ok(h), Ok(m)) => ZoneOffset::new(sign * (h * 60 + m)).map_err(|_| bad()),
+      _ => Err(bad()),
+    }
+  }
+
+  /// Parses the time associated with some `DTSTART`, along with its timezone
+  /// and whether it is a date.
   pub fn dtstart(
     &mut self,
-    tz: ZoneOffset,
-  ) -> Result<MinInstant, ICSProcessError> {
+    tz: &Tz,
+  ) -> Result<(MinInstant, Tz, bool), ICSProcessError> {
     self.munch(Token::DTSTART)?;
-    self.dt_possible_timezone(tz)
+    let tzid = self.dt_prefix()?;
+    self.dt_zoned(tzid.as_deref(), tz, ICS_DATE_START_TIME)
   }
 
-  /// Parses the time associated with some `DTEND`.
-  pub fn dtend(
+  /// Parses the text value of some property, ie. `SUMMARY`. Parameters of
+  /// the property, ie. `LANGUAGE=en`, are discarded.
+  fn text(&mut self, tag: Token) -> Result<String, ICSProcessError> {
+    self.munch(tag)?;
+    self.skip_until_lambda(|t| t == &Token::COLON)?;
+    self.munch(Token::COLON)?;
+    self.string_until(|t| t == &Token::NEXTLINE)
+  }
+
+  /// Parses the comma-separated text values of some property, ie.
+  /// `CATEGORIES`. Parameters of the property are discarded.
+  fn text_list(&mut self, tag: Token) -> Result<Vec<String>, ICSProcessError> {
+    self.munch(tag)?;
+    self.skip_until_lambda(|t| t == &Token::COLON)?;
+    self.munch(Token::COLON)?;
+
+    let mut ret = Vec::<String>::new();
+    loop {
+      let item =
+        self.string_until(|t| t == &Token::COMMA || t == &Token::NEXTLINE)?;
+      if !item.is_empty() {
+        ret.push(item);
+      }
+      if self.token()? == Token::NEXTLINE {
+        break Ok(ret);
+      }
+    }
+  }
+
+  /// Parses the value of some `DURATION` or `ESTIMATED-DURATION`, ie.
+  /// `PT1H30M`.
+  pub fn duration(
     &mut self,
-    tz: ZoneOffset,
-  ) -> Result<MinInstant, ICSProcessError> {
+    tag: Token,
+  ) -> Result<MinDuration, ICSProcessError> {
+    self.munch(tag)?;
+    self.munch(Token::COLON)?;
+    let expr = self.string_until(|t| t == &Token::NEXTLINE)?;
+    expr
+      .parse()
+      .map_err(|_| ICSProcessError::Other(format!("Bad duration `{}`", expr)))
+  }
+
+  /// Parses the time associated with some `DTEND`.
+  pub fn dtend(&mut self, tz: &Tz) -> Result<MinInstant, ICSProcessError> {
     self.munch(Token::DTEND)?;
     self.dt_possible_timezone(tz)
   }
 
+  /// Parses the times associated with some `EXDATE` or `RDATE`, which is a
+  /// comma-separated list of datetime literals.
+  ///
+  /// ### Syntax
+  /// `tag dt_prefix [yyyymmdd]T[hhmmss]Z?, [yyyymmdd]T[hhmmss]Z?, ...`
+  pub fn dt_list(
+    &mut self,
+    tag: Token,
+    tz: &Tz,
+  ) -> Result<Vec<MinInstant>, ICSProcessError> {
+    self.munch(tag)?;
+    let tzid = self.dt_prefix()?;
+
+    let mut ret = vec![self.dt_literal(tzid.as_deref(), tz)?];
+    while self.peek(0)? == Token::COMMA {
+      self.skip()?;
+      ret.push(self.dt_literal(tzid.as_deref(), tz)?);
+    }
+    Ok(ret)
+  }
+
   /// Parses a datetime literal with an optional timezone prefix.
   ///
   /// ### Syntax
   /// `:[yyyymmdd]T[hhmmss]Z | ;TZID=..:[yyyymmdd]T[hhmmss]`
   fn dt_possible_timezone(
     &mut self,
-    default_tz: ZoneOffset,
+    default_tz: &Tz,
   ) -> Result<MinInstant, ICSProcessError> {
-    match self.token()? {
-      // when timezone is specified
-      Token::SEMICOLON => {
-        self.munch(Token::TZID)?;
-        self.munch(Token::EQ)?;
-        let tz_string = self.string_until(|c| c == &Token::COLON)?;
-        self.munch(Token::COLON)?;
-
-        // TODO: implement zones.
+    let tzid = self.dt_prefix()?;
+    self.dt_literal(tzid.as_deref(), default_tz)
+  }
 
-        if PARSE_DT_LITERAL_TZID {
-          todo!("Cannot yet parse TZID in dt literat")
-        } else {
-          return self.dt_literal(true, default_tz);
+  /// Parses the optional parameters before some datetime literal, up to and
+  /// including the colon. Returns the `TZID` if specified. Other parameters,
+  /// ie. `VALUE=DATE`, are discarded, since the literal itself tells whether
+  /// it is a date.
+  ///
+  /// ### Syntax
+  /// `: | (;[param]=..)+:`
+  fn dt_prefix(&mut self) -> Result<Option<String>, ICSProcessError> {
+    let mut tzid: Option<String> = None;
+    loop {
+      match self.token()? {
+        // when some parameter is specified
+        Token::SEMICOLON => {
+          let param = self.token()?;
+          self.munch(Token::EQ)?;
+          let value = self
+            .string_until(|c| c == &Token::COLON || c == &Token::SEMICOLON)?;
+          if param == Token::TZID {
+            tzid = Some(value.trim_matches('"').to_string());
+          }
         }
-      }
 
-      // when timezone is not specified, use default
-      Token::COLON => {
-        return self.dt_literal(false, default_tz);
-      }
+        // when timezone is not specified, use default
+        Token::COLON => break Ok(tzid),
 
-      x => Err(ICSProcessError::Other(format!(
-        "Expected : or ; after dt, found {}",
-        x
-      ))),
+        x => {
+          return Err(ICSProcessError::Other(format!(
+            "Expected : or ; after dt, found {}",
+            x
+          )))
+        }
+      }
     }
   }
 
   /// Parses recurrence rules.
-  fn rrules(
-    &mut self,
-    tz: ZoneOffset,
-  ) -> Result<FreqAndRRules, ICSProcessError> {
+  fn rrules(&mut self, tz: &Tz) -> Result<FreqAndRRules, ICSProcessError> {
     self.munch(Token::RRULE)?;
+    let span = self.peekbuf.span().map(Span::whole_line);
     self.munch(Token::COLON)?;
 
     self.munch(Token::FREQ)?;
@@ -354,6 +1156,8 @@ impl<'a> ICSParser<'a> {
     let mut interval: usize = 1; // default
     let mut count: Option<usize> = None;
     let mut until: Option<MinInstant> = None;
+    let mut floating_until = false;
+    let mut wkst = Weekday::MO; // default
 
     let mut ready_to_rrule: bool = true;
 
@@ -364,7 +1168,16 @@ impl<'a> ICSParser<'a> {
           ready_to_rrule = true;
         }
         Token::NEXTLINE => {
-          break Ok(FreqAndRRules { freq, content, count, interval, until });
+          break Ok(FreqAndRRules {
+            freq,
+            content,
+            count,
+            interval,
+            until,
+            wkst,
+            floating_until,
+            span,
+          });
         }
         Token::INTERVAL => {
           self.skip()?;
@@ -399,7 +1212,22 @@ impl<'a> ICSParser<'a> {
         Token::UNTIL => {
           self.skip()?;
           self.munch(Token::EQ)?;
-          until = Some(self.dt_literal(false, tz)?)
+          let digits = self.dt_digits()?;
+          let zone =
+            if digits.utc { ZoneOffset::utc().into() } else { tz.clone() };
+          until = Some(digits.localize(&zone, ICS_DATE_UNTIL_TIME)?);
+          floating_until = !digits.utc;
+        }
+        Token::WKST => {
+          self.skip()?;
+          self.munch(Token::EQ)?;
+          let wd_string = self.token_string()?;
+          match wd_string.parse() {
+            Ok(wd) => wkst = wd,
+            Err(_) => {
+              return Err(ICSProcessError::InvalidRRule(Token::WKST, wd_string))
+            }
+          }
         }
         t => {
           if ready_to_rrule {
@@ -446,49 +1274,79 @@ impl<'a> ICSParser<'a> {
 
     loop {
       let next_tok = self.peek(0)?;
-      if end(next_tok) {
+      if end(&next_tok) {
         ret.push(entry.clone());
         break Ok(ret);
-      } else if next_tok == sep {
+      } else if &next_tok == sep {
         self.skip()?;
         ret.push(entry.clone());
         entry.clear();
       } else {
-        let tok = self.token()?;
-        entry.push_str(&tok.cast_as_string());
+        entry.push_str(&self.token_string()?);
       }
     }
   }
 
-  /// Parses a datetime literal, in the form of `[yyyymmdd]T[hhmmss]Z`.
-  fn dt_literal(
-    &mut self,
-    zone_specified: bool,
-    tz: ZoneOffset,
-  ) -> Result<MinInstant, ICSProcessError> {
+  /// Parses the digits of some datetime literal, in the form of
+  /// `[yyyymmdd]T[hhmmss]Z`, where the time of day and `Z` are optional.
+  fn dt_digits(&mut self) -> Result<DtDigits, ICSProcessError> {
     let ymd = self.number()?;
-
-    let dt = if self.peek(0)? == &Token::Other("T".to_string()) {
+    let start = self.peekbuf.span().cloned();
+    self.peek(0)?;
+    let (hms, utc) = if self.peek_other(0, "T") {
       self.skip()?;
       let hms = self.number()?;
-
-      // deal with weird ICS format rules: if timezone is not directly
-      // specified, such a literal shall end with 'Z'.
-      if !zone_specified {
-        self.munch(Token::Other("Z".to_string()))?;
+      let utc = self.peek_other(0, "Z");
+      if utc {
+        self.skip()?;
       }
-
-      Date::from_ics_time_string(&ymd, &hms, tz)?
+      (Some(hms), utc)
     } else {
-      // Handle the case where time of day is not specified.
-      let hms = ICS_DEFAULT_TIME_IN_DAY;
-      Date::from_ics_time_string(&ymd, hms, tz)?
+      (None, false)
     };
 
-    match MinInstant::from_date(&dt) {
-      Ok(mi) => Ok(mi),
-      _ => unreachable!("Well-formatted ICS can never overflow MinInstant"),
-    }
+    let span = match (start, self.peekbuf.span()) {
+      (Some(start), Some(end)) => Some(start.to(end)),
+      (start, _) => start,
+    };
+    Ok(DtDigits { ymd, hms, utc, span })
+  }
+
+  /// Parses a datetime literal, in the form of `[yyyymmdd]T[hhmmss]Z`. Such a
+  /// literal is in UTC if it ends with `Z`; otherwise, it is the local time
+  /// of timezone `tzid`, which must be either in the zone table or in the
+  /// compiled-in timezones, or of `tz` if there is no `tzid`. A date literal
+  /// `[yyyymmdd]` is the local time `date_hms` of timezone `tz`. Returns the
+  /// time instant along with its timezone and whether the literal is a date.
+  fn dt_zoned(
+    &mut self,
+    tzid: Option<&str>,
+    tz: &Tz,
+    date_hms: &str,
+  ) -> Result<(MinInstant, Tz, bool), ICSProcessError> {
+    let digits = self.dt_digits()?;
+
+    // Handle the case where time of day is not specified.
+    let is_date = digits.hms.is_none();
+    let tzid = tzid.filter(|_| !is_date);
+    digits.date(date_hms)?; // malformed digits come before unknown timezones.
+
+    let zone = match tzid {
+      _ if digits.utc => Tz::Fixed(ZoneOffset::utc()),
+      Some(id) => self.named_zone(id)?,
+      None => tz.clone(),
+    };
+    Ok((digits.localize(&zone, date_hms)?, zone, is_date))
+  }
+
+  /// Parses a datetime literal, just like `dt_zoned()`, but without its
+  /// timezone.
+  fn dt_literal(
+    &mut self,
+    tzid: Option<&str>,
+    tz: &Tz,
+  ) -> Result<MinInstant, ICSProcessError> {
+    Ok(self.dt_zoned(tzid, tz, ICS_DATE_START_TIME)?.0)
   }
 }
 
@@ -504,6 +1362,8 @@ impl std::fmt::Display for FreqAndRRules {
 
     write!(f, "  interval={}\n", self.interval)?;
 
+    writeln!(f, "  wkst={:?}", self.wkst)?;
+
     if let Some(n) = self.count {
       write!(f, "  count={}\n", n)?;
     }
@@ -520,6 +1380,16 @@ impl std::fmt::Display for FreqAndRRules {
   }
 }
 
+impl std::fmt::Display for ImportWarning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} #{}", self.component, self.index + 1)?;
+    if let Some(uid) = &self.uid {
+      write!(f, " (UID `{}`)", uid)?;
+    }
+    write!(f, " skipped: {}", self.reason)
+  }
+}
+
 impl std::fmt::Display for Vevent {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let repeat_str = match &self.repeat {
@@ -545,3 +1415,362 @@ impl std::fmt::Display for ICalendar {
     write!(f, "\n]")
   }
 }
+
+// --------------------------------- Testing ---------------------------------
+
+#[cfg(test)]
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::test_util::{parse_ics, parse_ics_in, vcalendar};
+
+  use super::*;
+
+  const NEW_YORK: &str = "BEGIN:VTIMEZONE\nTZID:America/New_York\n\
+    BEGIN:STANDARD\nDTSTART:16011104T020000\n\
+    RRULE:FREQ=YEARLY;BYDAY=1SU;BYMONTH=11\n\
+    TZOFFSETFROM:-0400\nTZOFFSETTO:-0500\nEND:STANDARD\n\
+    BEGIN:DAYLIGHT\nDTSTART:16010311T020000\n\
+    RRULE:FREQ=YEARLY;BYDAY=2SU;BYMONTH=3\n\
+    TZOFFSETFROM:-0500\nTZOFFSETTO:-0400\nEND:DAYLIGHT\n\
+    END:VTIMEZONE\n";
+
+  /// Parses a calendar with the `America/New_York` timezone and some events
+  /// described by `vevents`, with `tz` being the default timezone.
+  fn parse_cal(vevents: &str, tz: ZoneOffset) -> ICalendar {
+    let content =
+      format!("BEGIN:VCALENDAR\n{NEW_YORK}{vevents}END:VCALENDAR\n");
+    parse_ics_in(&content, &tz.into(), false).unwrap()
+  }
+
+  /// Prints some `MinInstant` in UTC.
+  fn utc_string(mi: MinInstant) -> String {
+    Date::from_min_instant(mi.normalize()).no_tz_string()
+  }
+
+  #[test]
+  fn vtimezone() {
+    let cal = parse_cal("", ZoneOffset::utc());
+    let ny = cal.zones.get("America/New_York").unwrap();
+    let offset_at_utc = |ymd: &str, hms: &str| {
+      let dt = Date::from_ics_time_string(ymd, hms, ZoneOffset::utc()).unwrap();
+      ny.offset_at(MinInstant::from_date(&dt).unwrap()).raw()
+    };
+
+    assert_eq!(-300, offset_at_utc("20230312", "065900"));
+    assert_eq!(-240, offset_at_utc("20230312", "070000"));
+    assert_eq!(-240, offset_at_utc("20231105", "055900"));
+    assert_eq!(-300, offset_at_utc("20231105", "060000"));
+  }
+
+  #[test]
+  fn tzid_literals() {
+    let vevents = "BEGIN:VEVENT\n\
+      DTSTART;TZID=America/New_York:20230704T090000\n\
+      DTEND;TZID=America/New_York:20231206T090000\n\
+      EXDATE;TZID=America/New_York:20230305T090000,20230312T090000\n\
+      SUMMARY:zoned\nEND:VEVENT\n";
+    let cal = parse_cal(vevents, ZoneOffset::utc());
+    let vevent = &cal.content[0];
+
+    assert_eq!("2023/Jul/4 13:00", utc_string(vevent.miv.start));
+    assert_eq!("2023/Dec/6 14:00", utc_string(vevent.miv.end));
+    assert_eq!("2023/Mar/5 14:00", utc_string(vevent.exdates[0]));
+    assert_eq!("2023/Mar/12 13:00", utc_string(vevent.exdates[1]));
+  }
+
+  #[test]
+  fn utc_and_floating_literals() {
+    let vevents = "BEGIN:VEVENT\nDTSTART:20230704T090000Z\n\
+      DTEND:20230704T090000\nSUMMARY:mixed\nEND:VEVENT\n";
+    let cal = parse_cal(vevents, ZoneOffset::new(-240).unwrap());
+    let vevent = &cal.content[0];
+
+    assert_eq!("2023/Jul/4 09:00", utc_string(vevent.miv.start));
+    assert_eq!("2023/Jul/4 13:00", utc_string(vevent.miv.end));
+  }
+
+  #[test]
+  fn tzid_without_vtimezone() {
+    let vevents = "BEGIN:VEVENT\n\
+      DTSTART;TZID=Europe/London:20230704T090000\n\
+      DTEND;TZID=Europe/London:20231206T090000\n\
+      SUMMARY:compiled-in\nEND:VEVENT\n";
+    let cal = parse_cal(vevents, ZoneOffset::utc());
+    let vevent = &cal.content[0];
+
+    assert_eq!("2023/Jul/4 08:00", utc_string(vevent.miv.start));
+    assert_eq!("2023/Dec/6 09:00", utc_string(vevent.miv.end));
+    assert_eq!("Europe/London", vevent.zone.to_string());
+  }
+
+  #[test]
+  fn date_values() {
+    let vevents = "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20231009\n\
+      DTEND;VALUE=DATE:20231011\nSUMMARY:retreat\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART;VALUE=DATE:20231010\nSUMMARY:holiday\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART;TZID=America/New_York;VALUE=DATE-TIME:\
+      20231010T090000\nDTEND;TZID=America/New_York:20231010T100000\n\
+      SUMMARY:class\nEND:VEVENT\n";
+    let cal = parse_cal(vevents, ZoneOffset::new(-240).unwrap());
+
+    let retreat = &cal.content[0];
+    assert!(retreat.all_day);
+    assert_eq!("2023/Oct/9 04:00", utc_string(retreat.miv.start));
+    assert_eq!("2023/Oct/11 04:00", utc_string(retreat.miv.end));
+
+    let holiday = &cal.content[1];
+    assert!(holiday.all_day);
+    assert_eq!("2023/Oct/10 04:00", utc_string(holiday.miv.start));
+    assert_eq!("2023/Oct/11 04:00", utc_string(holiday.miv.end));
+
+    let class = &cal.content[2];
+    assert!(!class.all_day);
+    assert_eq!("2023/Oct/10 13:00", utc_string(class.miv.start));
+  }
+
+  #[test]
+  fn text_values() {
+    let vevents = "BEGIN:VEVENT\r\nDTSTART:20231010T090000Z\r\n\
+      DTEND:20231010T100000Z\r\nSUMMARY:END of term\\, TRANSP\\; RRULE:\r\n\
+      \x20 FREQ=DAILY\r\nDESCRIPTION:BEGIN:VEVENT\\nEND:VEVENT\r\n\
+      UID:uid@test\r\nEND:VEVENT\r\n";
+    let cal = parse_cal(vevents, ZoneOffset::utc());
+    assert_eq!(1, cal.content.len());
+
+    let vevent = &cal.content[0];
+    assert_eq!("END of term, TRANSP; RRULE: FREQ=DAILY", vevent.summary);
+    assert_eq!(Some("uid@test".to_string()), vevent.uid);
+    assert!(vevent.repeat.is_none());
+  }
+
+  #[test]
+  fn descriptive_properties() {
+    let vevents = "BEGIN:VEVENT\r\nDTSTART:20231010T090000Z\r\n\
+      DTEND:20231010T100000Z\r\nSUMMARY:class\r\n\
+      LOCATION;ALTREP=\"http://example.com/room\":Room 1\\, East\r\n\
+      ATTENDEE;MEMBER=\"mailto:a@x\",\"mailto:b@x\":mailto:c@x\r\n\
+      CATEGORIES:Work,Mee\r\n ting\\,s,,\r\nCATEGORIES:Study\r\n\
+      URL:http://example.com/class\r\nEND:VEVENT\r\n";
+    let cal = parse_cal(vevents, ZoneOffset::utc());
+
+    let vevent = &cal.content[0];
+    assert_eq!(None, vevent.description);
+    assert_eq!(Some("Room 1, East".to_string()), vevent.location);
+    assert_eq!(vec!["Work", "Meeting,s", "Study"], vevent.categories);
+    assert_eq!(Some("http://example.com/class".to_string()), vevent.url);
+  }
+
+  #[test]
+  fn todos() {
+    let components = "BEGIN:VTODO\nSUMMARY:essay\nUID:essay@test\n\
+      DUE;TZID=America/New_York:20231010T235900\nESTIMATED-DURATION:PT2H\n\
+      PERCENT-COMPLETE:30\nBEGIN:VALARM\nTRIGGER:-PT15M\nACTION:DISPLAY\n\
+      END:VALARM\nEND:VTODO\n\
+      BEGIN:VTODO\nSUMMARY:reading\nDUE;VALUE=DATE:20231011\n\
+      STATUS:COMPLETED\nEND:VTODO\n\
+      BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDURATION:PT1H\n\
+      BEGIN:VALARM\nTRIGGER:-PT15M\nEND:VALARM\nSUMMARY:class\nEND:VEVENT\n";
+    let cal = parse_cal(components, ZoneOffset::utc());
+    assert_eq!(1, cal.content.len());
+    assert_eq!("class", cal.content[0].summary);
+    assert_eq!(2, cal.todos.len());
+
+    let essay = &cal.todos[0];
+    assert_eq!("essay", essay.summary);
+    assert_eq!(Some("essay@test".to_string()), essay.uid);
+    assert_eq!("2023/Oct/11 03:59", utc_string(essay.due.unwrap()));
+    assert_eq!(Some(120), essay.estimate.map(|d| d.num_min()));
+    assert_eq!(Some(30), essay.percent);
+    assert_eq!(TodoStatus::NeedsAction, essay.status);
+
+    // a date means the end of that day.
+    let reading = &cal.todos[1];
+    assert_eq!("2023/Oct/11 23:59", utc_string(reading.due.unwrap()));
+    assert!(reading.estimate.is_none());
+    assert_eq!(TodoStatus::Completed, reading.status);
+  }
+
+  #[test]
+  fn durations() {
+    let vevents = "BEGIN:VEVENT\nDTSTART:20231010T090000Z\n\
+      DURATION:PT1H30M\nSUMMARY:lecture\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART;TZID=America/New_York:20231104T090000\n\
+      DURATION:P1DT1H\nSUMMARY:trip\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART;VALUE=DATE:20231010\nDURATION:P1W\n\
+      SUMMARY:break\nEND:VEVENT\n";
+    let cal = parse_cal(vevents, ZoneOffset::utc());
+
+    let lecture = &cal.content[0];
+    assert_eq!("2023/Oct/10 10:30", utc_string(lecture.miv.end));
+
+    // the day is counted in wall-clock time, across the end of DST.
+    let trip = &cal.content[1];
+    assert_eq!("2023/Nov/4 13:00", utc_string(trip.miv.start));
+    assert_eq!("2023/Nov/5 15:00", utc_string(trip.miv.end));
+
+    let break_week = &cal.content[2];
+    assert_eq!("2023/Oct/17 00:00", utc_string(break_week.miv.end));
+
+    let malformed = "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDURATION:PT\n\
+      SUMMARY:bad\nEND:VEVENT\nEND:VCALENDAR\n";
+    assert!(parse_ics(&format!("BEGIN:VCALENDAR\n{malformed}")).is_err());
+  }
+
+  /// Parses the given calendar content, and renders the error.
+  fn parse_err(content: &str) -> String {
+    match parse_ics(content) {
+      Ok(_) => panic!("`{}` should not parse", content),
+      Err(e) => e.to_string(),
+    }
+  }
+
+  #[test]
+  fn error_locations() {
+    let content = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:a\n \
+      long one\nDTSTART;TZID=\"Europe/Paris\":20231010T090000\n\
+      RRULE:FREQ=WEEKLY;INTERVAL=X\nEND:VEVENT\nEND:VCALENDAR\n";
+    let expected = "test.ics:6:28: `Other(X)` is not a number\n  \
+      |\n\
+      6 | RRULE:FREQ=WEEKLY;INTERVAL=X\n  \
+      |                            ^";
+    assert_eq!(expected, parse_err(content));
+
+    let content = "BEGIN:VCALENDAR\nBEGIN:VEVENT\n\
+      DTSTART;TZID=\"x;y\":20231010T090000\nEND:VEVENT\nEND:VCALENDAR\n";
+    let expected = "test.ics:3:29: `x;y` is neither a VTIMEZONE nor an IANA \
+      timezone\n  \
+      |\n\
+      3 | DTSTART;TZID=\"x;y\":20231010T090000\n  \
+      |                             ^^^^^^";
+    assert_eq!(expected, parse_err(content));
+
+    let content = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nbad line\nEND:VEVENT\n";
+    let err = parse_err(content);
+    assert!(err.starts_with("test.ics:3:1: "));
+    assert!(err.ends_with("3 | bad line\n  | ^^^^^^^^"));
+
+    // spans of tokens after quoted parameters and folded lines.
+    let content = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:a\n \
+      long one\nDTSTART;TZID=\"x;y\":2023101T0900\nEND:VEVENT\n";
+    let expected =
+      "test.ics:5:20: Cannot parse `2023101/0900` as valid time\n  \
+      |\n\
+      5 | DTSTART;TZID=\"x;y\":2023101T0900\n  \
+      |                    ^^^^^^^^^^^^";
+    assert_eq!(expected, parse_err(content));
+
+    // out-of-range datetimes are located at the whole literal as well.
+    let content = "BEGIN:VCALENDAR\nBEGIN:VEVENT\n\
+      DTSTART:19690101T090000Z\nEND:VEVENT\nEND:VCALENDAR\n";
+    let expected =
+      "test.ics:3:9: Cannot parse `19690101/090000` as valid time\n  \
+      |\n\
+      3 | DTSTART:19690101T090000Z\n  \
+      |         ^^^^^^^^^^^^^^^^";
+    assert_eq!(expected, parse_err(content));
+  }
+
+  #[test]
+  fn lenient_mode() {
+    let vevents = "BEGIN:VEVENT\nDTSTART:20231010T090000Z\n\
+      DTEND:20231010T100000Z\nRRULE:FREQ=HOURLY\nUID:hourly@test\n\
+      BEGIN:VALARM\nTRIGGER:-PT5M\nEND:VALARM\nEND:VEVENT\n\
+      BEGIN:VTODO\nbroken line\nEND:VTODO\n\
+      BEGIN:VEVENT\nDTSTART:20231011T090000Z\nSUMMARY:no end\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231012T090000Z\nDTEND:20231012T100000Z\n\
+      SUMMARY:fine\nEND:VEVENT\n";
+    let content = vcalendar(vevents);
+    let utc = ZoneOffset::utc().into();
+    assert!(parse_ics(&content).is_err());
+    let cal = parse_ics_in(&content, &utc, true).unwrap();
+    assert_eq!(1, cal.content.len());
+    assert_eq!("fine", cal.content[0].summary);
+    assert_eq!(2, cal.content[0].index);
+
+    let warnings: Vec<(Token, usize, Option<&str>)> = cal
+      .warnings
+      .iter()
+      .map(|w| (w.component, w.index, w.uid.as_deref()))
+      .collect();
+    let expected = vec![
+      (Token::VEVENT, 0, Some("hourly@test")),
+      (Token::VTODO, 0, None),
+      (Token::VEVENT, 1, None),
+    ];
+    assert_eq!(expected, warnings);
+    assert!(cal.warnings[0]
+      .to_string()
+      .starts_with("VEVENT #1 (UID `hourly@test`) skipped: test.ics:5:12: "));
+  }
+
+  #[test]
+  fn times_before_epoch() {
+    let vevents = "BEGIN:VEVENT\nDTSTART:19690101T090000Z\n\
+      DTEND:19690101T100000Z\nSUMMARY:early\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      RRULE:FREQ=DAILY;UNTIL=19690101T000000\nSUMMARY:until\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231011T090000Z\nDTEND:20231011T100000Z\n\
+      SUMMARY:fine\nEND:VEVENT\n";
+    let content = vcalendar(vevents);
+    let err = parse_err(&content);
+    assert!(err.contains("Cannot parse `19690101/090000` as valid time"));
+
+    let cal = parse_ics_in(&content, &ZoneOffset::utc().into(), true).unwrap();
+    assert_eq!(1, cal.content.len());
+    assert_eq!("fine", cal.content[0].summary);
+    assert_eq!(2, cal.warnings.len());
+    assert!(cal.warnings[1]
+      .to_string()
+      .contains("Cannot parse `19690101/000000` as valid time"));
+  }
+
+  #[test]
+  fn unknown_timezones() {
+    let content = "BEGIN:VCALENDAR\nX-WR-TIMEZONE:Mars/Olympus_Mons\n\
+      BEGIN:VEVENT\nDTSTART;TZID=Mars/Olympus_Mons:20231010T090000\n\
+      DTEND;TZID=Mars/Olympus_Mons:20231010T100000\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231011T090000\nDTEND:20231011T100000\n\
+      SUMMARY:floating\nEND:VEVENT\nEND:VCALENDAR\n";
+    assert!(parse_ics(content).is_err());
+
+    // floating times fall back to the default zone.
+    let utc = ZoneOffset::utc().into();
+    let cal = parse_ics_in(content, &utc, true).unwrap();
+    assert_eq!(1, cal.content.len());
+    assert_eq!("2023/Oct/11 09:00", utc_string(cal.content[0].miv.start));
+
+    let warnings: Vec<(Token, usize)> =
+      cal.warnings.iter().map(|w| (w.component, w.index)).collect();
+    assert_eq!(vec![(Token::XWRTIMEZONE, 0), (Token::VEVENT, 0)], warnings);
+    assert!(cal.warnings[1]
+      .to_string()
+      .contains("`Mars/Olympus_Mons` is neither a VTIMEZONE nor an IANA"));
+  }
+
+  #[test]
+  fn concatenated_calendars() {
+    let content = "BEGIN:VCALENDAR\nX-WR-CALNAME:School\n\
+      X-WR-TIMEZONE:America/New_York\n\
+      BEGIN:VEVENT\nDTSTART:20230704T090000\nDTEND:20230704T100000\n\
+      SUMMARY:new york\nEND:VEVENT\nEND:VCALENDAR\n\
+      BEGIN:VCALENDAR\nX-WR-CALNAME:Holidays\n\
+      BEGIN:VEVENT\nDTSTART:20230704T090000\nDTEND:20230704T100000\n\
+      SUMMARY:floating\nEND:VEVENT\n\
+      BEGIN:VTODO\nDUE:20230705T090000\nSUMMARY:task\nEND:VTODO\n\
+      END:VCALENDAR\n";
+    let tz = ZoneOffset::new(60).unwrap().into();
+    let cal = parse_ics_in(content, &tz, false).unwrap();
+
+    assert_eq!(Some("School".to_string()), cal.cal_name);
+    assert_eq!(2, cal.content.len());
+    assert_eq!(1, cal.todos.len());
+    assert_eq!((0, 1), (cal.content[0].index, cal.content[1].index));
+    assert_eq!("2023/Jul/4 13:00", utc_string(cal.content[0].miv.start));
+    assert_eq!("America/New_York", cal.content[0].zone.to_string());
+    assert_eq!("2023/Jul/4 08:00", utc_string(cal.content[1].miv.start));
+
+    let truncated = format!("{content}BEGIN:VCALENDAR\n");
+    assert!(parse_ics_in(&truncated, &tz, false).is_err());
+  }
+}
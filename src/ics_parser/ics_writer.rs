@@ -0,0 +1,507 @@
+//! ICalendar writing, which is the inverse of lexing and parsing.
+//!
+//! Only the properties that `ICSParser` processes are written, along with
+//! those required by RFC 5545, ie. `VERSION`, `PRODID` and `DTSTAMP`.
+
+use crate::{
+  calendar::{cal_event::Event, task::Task},
+  const_params::ICS_PRODID,
+  time::{
+    date::Date,
+    duration::MinDuration,
+    timezone::{Tz, ZoneOffset},
+    week::Weekday,
+    zone_rules::ZoneRules,
+    MinInstant,
+  },
+};
+
+use super::{
+  content_line::{escape, fold},
+  ics_syntax::{FreqAndRRules, Status, Transp, Vevent},
+  ICSProcessError,
+};
+
+/// Writes the content lines of some `VCALENDAR`, folded and with `\r\n` line
+/// breaks.
+pub struct IcsWriter {
+  out: String,
+
+  /// Time of writing, ie. `DTSTAMP`.
+  stamp: MinInstant,
+}
+
+impl IcsWriter {
+  /// Begins some `VCALENDAR`.
+  pub fn new() -> Self {
+    let utc = ZoneOffset::utc();
+    let mut writer = IcsWriter {
+      out: String::new(),
+      stamp: MinInstant::now(&utc),
+    };
+    writer.line("BEGIN:VCALENDAR");
+    writer.line("VERSION:2.0");
+    writer.line(&format!("PRODID:{}", ICS_PRODID));
+    writer
+  }
+
+  /// Ends the `VCALENDAR`, and returns the written content.
+  pub fn finish(mut self) -> String {
+    self.line("END:VCALENDAR");
+    self.out
+  }
+
+  /// Writes some logical line.
+  fn line(&mut self, line: &str) {
+    self.out.push_str(&fold(line));
+  }
+
+  /// Writes some property with a text value, ie. `SUMMARY`.
+  fn text(&mut self, name: &str, value: &str) {
+    self.line(&format!("{}:{}", name, escape(value)));
+  }
+
+  /// Writes some property with a datetime value, ie. `DTSTART`.
+  fn datetime(&mut self, name: &str, mi: MinInstant, zone: &Tz, all_day: bool) {
+    self.line(&format!("{}{}", name, dt_value(mi, zone, all_day)));
+  }
+
+  /// Writes some `VTIMEZONE` with the observances of `zone`.
+  pub fn vtimezone(&mut self, zone: &ZoneRules) {
+    self.line("BEGIN:VTIMEZONE");
+    self.text("TZID", zone.name());
+    for o in zone.observances() {
+      let kind = if o.offset_to().raw() > o.offset_from().raw() {
+        "DAYLIGHT"
+      } else {
+        "STANDARD"
+      };
+      self.line(&format!("BEGIN:{}", kind));
+
+      // onsets before the Unix Epoch are written as the earliest one in use.
+      let onset = match u32::try_from(o.onset()) {
+        Ok(raw) => local_string(raw),
+        Err(_) => {
+          let time = o.rule().map_or(0, |r| r.time());
+          format!("16010101T{:02}{:02}00", time / 60, time % 60)
+        }
+      };
+      self.line(&format!("DTSTART:{}", onset));
+      self.line(&format!("TZOFFSETFROM:{}", offset_string(o.offset_from())));
+      self.line(&format!("TZOFFSETTO:{}", offset_string(o.offset_to())));
+      if let Some(rule) = o.rule() {
+        self.line(&format!("RRULE:{}", rrule_value(&rule.to_rrules(), false)));
+      }
+      for rdate in o.rdates().iter().filter_map(|r| u32::try_from(*r).ok()) {
+        self.line(&format!("RDATE:{}", local_string(rdate)));
+      }
+      self.line(&format!("END:{}", kind));
+    }
+    self.line("END:VTIMEZONE");
+  }
+
+  /// Writes some `VEVENT`.
+  pub fn vevent(&mut self, v: &Vevent) {
+    self.line("BEGIN:VEVENT");
+    if let Some(uid) = &v.uid {
+      self.text("UID", uid);
+    }
+    self.datetime("DTSTAMP", self.stamp, &ZoneOffset::utc().into(), false);
+    self.text("SUMMARY", &v.summary);
+    self.datetime("DTSTART", v.miv.start, &v.zone, v.all_day);
+    self.datetime("DTEND", v.miv.end, &v.zone, v.all_day);
+    if let Some(recurrence_id) = v.recurrence_id {
+      self.datetime("RECURRENCE-ID", recurrence_id, &v.zone, v.all_day);
+    }
+    if let Some(frq) = &v.repeat {
+      self.line(&format!("RRULE:{}", rrule_value(frq, v.all_day)));
+    }
+    for exdate in &v.exdates {
+      self.datetime("EXDATE", *exdate, &v.zone, v.all_day);
+    }
+    for rdate in &v.rdates {
+      self.datetime("RDATE", *rdate, &v.zone, v.all_day);
+    }
+    if let Some(description) = &v.description {
+      self.text("DESCRIPTION", description);
+    }
+    if let Some(location) = &v.location {
+      self.text("LOCATION", location);
+    }
+    if !v.categories.is_empty() {
+      let items: Vec<String> = v.categories.iter().map(|c| escape(c)).collect();
+      self.line(&format!("CATEGORIES:{}", items.join(",")));
+    }
+    if let Some(url) = &v.url {
+      self.line(&format!("URL:{}", url));
+    }
+    if v.transp == Transp::Transparent {
+      self.line("TRANSP:TRANSPARENT");
+    }
+    match v.status {
+      Status::Tentative => self.line("STATUS:TENTATIVE"),
+      Status::Cancelled => self.line("STATUS:CANCELLED"),
+      Status::Confirmed => (),
+    }
+    self.line("END:VEVENT");
+  }
+
+  /// Writes some task named `name` as a `VTODO`, whose workload is written
+  /// as `ESTIMATED-DURATION`.
+  pub fn vtodo(&mut self, name: &str, task: &Task) {
+    let utc: Tz = ZoneOffset::utc().into();
+    self.line("BEGIN:VTODO");
+    self.text("UID", &format!("{}@taggytime", name));
+    self.datetime("DTSTAMP", self.stamp, &utc, false);
+    self.text("SUMMARY", name);
+    self.datetime("DUE", task.due, &utc, false);
+    if let Ok(load) = MinDuration::new(0, task.length.num_min()) {
+      self.line(&format!("ESTIMATED-DURATION:{}", load));
+    }
+    let percent = task.completion.raw();
+    self.line(&format!("PERCENT-COMPLETE:{}", percent));
+    self.line(match percent {
+      0 => "STATUS:NEEDS-ACTION",
+      100 => "STATUS:COMPLETED",
+      _ => "STATUS:IN-PROCESS",
+    });
+    self.line("END:VTODO");
+  }
+}
+
+/// Writes the value of some datetime property, including its parameters. An
+/// all-day time instant is written as its date, ie. `;VALUE=DATE:20231010`.
+/// Otherwise, it is written as the local time of `zone` if `zone` is named,
+/// ie. `;TZID=America/New_York:20231010T090000`, or in UTC if not, ie.
+/// `:20231010T130000Z`.
+fn dt_value(mi: MinInstant, zone: &Tz, all_day: bool) -> String {
+  if all_day {
+    let (ymd, _) = Date::from_min_instant(mi).as_ics_time_string();
+    return format!(";VALUE=DATE:{}", ymd);
+  }
+  match zone.rules() {
+    Some(rules) => {
+      let local = mi.in_zone(rules);
+      let (ymd, hms) = Date::from_min_instant(local).as_ics_time_string();
+      format!(";TZID={}:{}T{}", rules.name(), ymd, hms)
+    }
+    None => format!(":{}", utc_string(mi)),
+  }
+}
+
+/// Writes some time instant in UTC, ie. `20231010T130000Z`.
+fn utc_string(mi: MinInstant) -> String {
+  let (ymd, hms) = Date::from_min_instant(mi.normalize()).as_ics_time_string();
+  format!("{}T{}Z", ymd, hms)
+}
+
+/// Writes some local time in raw minutes, ie. `20231105T020000`.
+fn local_string(raw: u32) -> String {
+  let mi = MinInstant { raw, offset: ZoneOffset::utc() };
+  let (ymd, hms) = Date::from_min_instant(mi).as_ics_time_string();
+  format!("{}T{}", ymd, hms)
+}
+
+/// Writes some utc offset, ie. `-0400`.
+fn offset_string(offset: ZoneOffset) -> String {
+  let sign_char = if offset.raw() >= 0 { '+' } else { '-' };
+  let abs = offset.raw().abs();
+  format!("{}{:02}{:02}", sign_char, abs / 60, abs % 60)
+}
+
+/// Writes the value of some `RRULE`, ie. `FREQ=WEEKLY;COUNT=5;BYDAY=TU,TH`.
+/// As per RFC 5545, `UNTIL` is a date if the event is all-day, or is in UTC
+/// otherwise.
+fn rrule_value(frq: &FreqAndRRules, all_day: bool) -> String {
+  let mut ret = format!("FREQ={}", format!("{:?}", frq.freq).to_uppercase());
+  if frq.interval != 1 {
+    ret.push_str(&format!(";INTERVAL={}", frq.interval));
+  }
+  if let Some(n) = frq.count {
+    ret.push_str(&format!(";COUNT={}", n));
+  }
+  match frq.until {
+    Some(mi) if all_day => {
+      let (ymd, _) = Date::from_min_instant(mi).as_ics_time_string();
+      ret.push_str(&format!(";UNTIL={}", ymd));
+    }
+    Some(mi) => ret.push_str(&format!(";UNTIL={}", utc_string(mi))),
+    None => (),
+  }
+  if frq.wkst != Weekday::MO {
+    ret.push_str(&format!(";WKST={:?}", frq.wkst));
+  }
+  for rrt in &frq.content {
+    ret.push_str(&format!(";{}={}", rrt.tag, rrt.content.join(",")));
+  }
+  ret
+}
+
+/// Writes some events and tasks as the content of some `.ics` file. The
+/// `n`th event of calendar `name` without `UID` is given `name-n@taggytime`.
+/// Named timezones of the events are written as `VTIMEZONE`s ahead of them,
+/// with only the observances in effect while the events happen.
+pub fn export_ics<'a, C, T>(
+  calendars: C,
+  tasks: T,
+) -> Result<String, ICSProcessError>
+where
+  C: IntoIterator<Item = (&'a String, &'a Vec<Event>)>,
+  T: IntoIterator<Item = (&'a String, &'a Task)>,
+{
+  let mut vevents = Vec::<Vevent>::new();
+  let mut zones = Vec::<(ZoneRules, MinInstant, Option<MinInstant>)>::new();
+  for (name, events) in calendars {
+    for (i, event) in events.iter().enumerate() {
+      let fallback_uid = format!("{}-{}@taggytime", name, i);
+      let mut event_vevents = event.to_vevents(&fallback_uid)?;
+
+      // widens the span that the timezone of the event is exported for.
+      let zone = event_vevents.first().and_then(|v| v.zone.rules());
+      if let Some(zone) = zone.filter(|_| !event.all_day()) {
+        let (start, end) = event.recurrence.span();
+        match zones.iter_mut().find(|(z, _, _)| z.name() == zone.name()) {
+          Some((_, s, e)) => {
+            *s = start.min(*s);
+            *e = end.zip(*e).map(|(end, e)| end.max(e));
+          }
+          None => zones.push((zone.clone(), start, end)),
+        }
+      }
+      vevents.append(&mut event_vevents);
+    }
+  }
+
+  let mut writer = IcsWriter::new();
+  for (zone, start, end) in zones {
+    writer.vtimezone(&zone.within(start, end));
+  }
+  for v in &vevents {
+    writer.vevent(v);
+  }
+  for (name, task) in tasks {
+    writer.vtodo(name, task);
+  }
+  Ok(writer.finish())
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[cfg(test)]
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::calendar::task::Workload;
+  use crate::const_params::ICS_FOLD_OCTETS;
+  use crate::test_util::{parse_events, parse_ics, vcalendar, OCT_9_2023};
+  use crate::util_typs::percent::Percent;
+
+  use super::*;
+
+  /// Exports some events as calendar `test`, without tasks.
+  fn export_events(events: &Vec<Event>) -> String {
+    let name = "test".to_string();
+    export_ics([(&name, events)], []).unwrap()
+  }
+
+  /// Lists the first 30 occurrences of some event in UTC, as well as its
+  /// other properties except `UID`, which is generated if absent.
+  fn describe(event: &Event) -> Vec<String> {
+    let utc_string = |mi: MinInstant| Date::from_min_instant(mi.normalize());
+    let mut ret = vec![format!(
+      "{:?} {:?} {:?} {:?} {:?} {:?} {:?}",
+      event.summary,
+      event.transp,
+      event.status,
+      event.description,
+      event.location,
+      event.categories,
+      event.url,
+    )];
+    ret.extend(event.recurrence.clone().into_iter().take(30).map(|miv| {
+      let (start, end) = (utc_string(miv.start), utc_string(miv.end));
+      format!("{} - {}", start.no_tz_string(), end.no_tz_string())
+    }));
+    ret
+  }
+
+  /// Checks that parsing, exporting and parsing again keeps every event.
+  fn assert_round_trip(vevents: &str) {
+    let events = parse_events(&vcalendar(vevents));
+    let exported = export_events(&events);
+    let reparsed = parse_events(&exported);
+
+    assert_eq!(events.len(), reparsed.len(), "{}", exported);
+    for (e, r) in events.iter().zip(&reparsed) {
+      assert_eq!(describe(e), describe(r), "{}", exported);
+      assert!(e.uid.is_none() || e.uid == r.uid);
+    }
+    assert_eq!(exported, export_events(&reparsed));
+  }
+
+  #[test]
+  fn round_trip_properties() {
+    assert_round_trip(
+      "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      SUMMARY:Meeting\\; with\\, friends\nDESCRIPTION:line 1\\nline 2\n\
+      LOCATION:Room 1\\, East\nCATEGORIES:Work,Mee\\,tings\n\
+      URL:http://example.com/a;b\nUID:meeting@test\nTRANSP:TRANSPARENT\n\
+      STATUS:TENTATIVE\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231011T090000Z\nDTEND:20231011T100000Z\n\
+      SUMMARY:no uid\nSTATUS:CANCELLED\nEND:VEVENT\n",
+    );
+  }
+
+  #[test]
+  fn round_trip_rrules() {
+    assert_round_trip(
+      "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=10;BYDAY=TU,TH;WKST=SU\n\
+      SUMMARY:class\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231027T090000Z\nDTEND:20231027T100000Z\n\
+      RRULE:FREQ=MONTHLY;BYDAY=-1FR;UNTIL=20240630T000000Z\n\
+      SUMMARY:review\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231031T090000Z\nDTEND:20231031T100000Z\n\
+      RRULE:FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1\n\
+      SUMMARY:payday\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      RRULE:FREQ=YEARLY;BYMONTH=10;BYMONTHDAY=10\n\
+      SUMMARY:anniversary\nEND:VEVENT\n",
+    );
+  }
+
+  #[test]
+  fn round_trip_exceptions() {
+    assert_round_trip(
+      "BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      RRULE:FREQ=DAILY;COUNT=10\nEXDATE:20231012T090000Z\n\
+      RDATE:20231030T090000Z\nSUMMARY:standup\nUID:standup@test\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231013T150000Z\nDTEND:20231013T160000Z\n\
+      RECURRENCE-ID:20231013T090000Z\nSUMMARY:standup\nUID:standup@test\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART:20231010T090000Z\nDTEND:20231010T100000Z\n\
+      RRULE:FREQ=DAILY;COUNT=5\nSUMMARY:no uid\nEND:VEVENT\n",
+    );
+  }
+
+  #[test]
+  fn round_trip_timezones() {
+    assert_round_trip(
+      "BEGIN:VTIMEZONE\nTZID:Custom/Zone\nBEGIN:STANDARD\n\
+      DTSTART:19701025T030000\nTZOFFSETFROM:+0200\nTZOFFSETTO:+0100\n\
+      RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\nEND:STANDARD\n\
+      BEGIN:DAYLIGHT\nDTSTART:19700329T020000\nTZOFFSETFROM:+0100\n\
+      TZOFFSETTO:+0200\nRRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\n\
+      END:DAYLIGHT\nEND:VTIMEZONE\n\
+      BEGIN:VEVENT\nDTSTART;TZID=America/New_York:20231030T090000\n\
+      DTEND;TZID=America/New_York:20231030T100000\n\
+      RRULE:FREQ=WEEKLY;COUNT=20\nSUMMARY:across dst\nEND:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART;TZID=Custom/Zone:20231020T090000\n\
+      DURATION:PT1H\nRRULE:FREQ=DAILY;UNTIL=20231105T000000Z\n\
+      SUMMARY:custom zone\nEND:VEVENT\n",
+    );
+  }
+
+  #[test]
+  fn vtimezone_within_span() {
+    let exported = export_events(&parse_events(
+      "BEGIN:VCALENDAR\nBEGIN:VEVENT\n\
+      DTSTART;TZID=America/New_York:20231030T090000\n\
+      DTEND;TZID=America/New_York:20231030T100000\n\
+      RRULE:FREQ=WEEKLY;COUNT=20\nSUMMARY:across dst\nEND:VEVENT\n\
+      END:VCALENDAR\n",
+    ));
+    let lines: Vec<&str> = exported
+      .split_terminator("\r\n")
+      .skip_while(|l| l != &"BEGIN:VTIMEZONE")
+      .take_while(|l| l != &"END:VTIMEZONE")
+      .collect();
+    assert!(lines.iter().all(|l| !l.starts_with("RDATE")), "{}", exported);
+    assert_eq!(2, lines.iter().filter(|l| l.starts_with("RRULE")).count());
+  }
+
+  #[test]
+  fn round_trip_all_day() {
+    assert_round_trip(
+      "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20231225\nSUMMARY:holiday\n\
+      RRULE:FREQ=YEARLY;UNTIL=20301231\nEXDATE;VALUE=DATE:20261225\n\
+      END:VEVENT\n\
+      BEGIN:VEVENT\nDTSTART;VALUE=DATE:20231010\nDURATION:P1W\n\
+      SUMMARY:break\nEND:VEVENT\n",
+    );
+  }
+
+  #[test]
+  fn folded_and_escaped() {
+    let summary = format!("{}, and more", "very long summary ".repeat(10));
+    let content = format!(
+      "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20231010T090000Z\n\
+      DTEND:20231010T100000Z\nSUMMARY:{}\nEND:VEVENT\nEND:VCALENDAR\n",
+      summary.replace(',', "\\,")
+    );
+    let exported = export_events(&parse_events(&content));
+
+    assert!(exported.ends_with("END:VCALENDAR\r\n"));
+    for line in exported.split_terminator("\r\n") {
+      assert!(line.len() <= ICS_FOLD_OCTETS, "{}", line);
+    }
+    assert!(exported.contains("summary \\, and more"));
+    assert_eq!(summary, parse_events(&exported)[0].summary);
+  }
+
+  #[test]
+  fn tasks_as_vtodos() {
+    let due = MinInstant::from_raw_utc(OCT_9_2023 + 23 * 60 + 59).unwrap();
+    let mut task = Task::new(due, Workload::from_num_min(90).unwrap());
+    task.set_progress(Percent(40));
+    let name = "essay, draft".to_string();
+    let exported = export_ics([], [(&name, &task)]).unwrap();
+
+    let expected = [
+      "BEGIN:VTODO",
+      "UID:essay\\, draft@taggytime",
+      "SUMMARY:essay\\, draft",
+      "DUE:20231009T235900Z",
+      "ESTIMATED-DURATION:PT1H30M",
+      "PERCENT-COMPLETE:40",
+      "STATUS:IN-PROCESS",
+      "END:VTODO",
+    ];
+    let lines: Vec<&str> = exported
+      .split_terminator("\r\n")
+      .filter(|l| !l.starts_with("DTSTAMP"))
+      .skip_while(|l| l != &"BEGIN:VTODO")
+      .take(expected.len())
+      .collect();
+    assert_eq!(expected.to_vec(), lines);
+    assert!(parse_events(&exported).is_empty());
+  }
+
+  #[test]
+  fn round_trip_tasks() {
+    let due = MinInstant::from_raw_utc(OCT_9_2023 + 23 * 60 + 59).unwrap();
+    let mut essay = Task::new(due, Workload::from_num_min(90).unwrap());
+    essay.set_progress(Percent(100));
+    let reading = Task::new(due, Workload::from_num_min(0).unwrap());
+    let names = ["essay".to_string(), "reading".to_string()];
+    let exported =
+      export_ics([], [(&names[0], &essay), (&names[1], &reading)]).unwrap();
+
+    let cal = parse_ics(&exported).unwrap();
+    let tasks: Vec<(String, Task)> = cal
+      .todos
+      .into_iter()
+      .map(|v| (v.summary.clone(), Task::try_from(v).unwrap()))
+      .collect();
+
+    assert_eq!(2, tasks.len());
+    for ((name, task), (expected_name, expected)) in
+      tasks.iter().zip(names.iter().zip([&essay, &reading]))
+    {
+      assert_eq!(expected_name, name);
+      assert_eq!(expected.due, task.due);
+      assert_eq!(expected.length.num_min(), task.length.num_min());
+      assert_eq!(expected.completion, task.completion);
+    }
+  }
+}
@@ -0,0 +1,375 @@
+//! jCal, the JSON format of ICalendar, as per RFC 7265.
+//!
+//! Rather than being parsed on its own, some jCal calendar is converted into
+//! the content lines of the equivalent `.ics` file, and vice versa, so that
+//! both formats share `ICSParser` and `IcsWriter`.
+
+use serde_json::{json, Map, Value};
+
+use super::{
+  content_line::{escape, fold, split_list, unescape, ContentLine, Unfold},
+  ICSProcessError,
+};
+
+/// Value type of some property when its `VALUE` parameter is absent, ie.
+/// `date-time` for `DTSTART`.
+fn default_type(name: &str) -> &'static str {
+  match name {
+    "DTSTART" | "DTEND" | "DUE" | "RECURRENCE-ID" | "EXDATE" | "RDATE"
+    | "DTSTAMP" | "CREATED" | "LAST-MODIFIED" | "COMPLETED" => "date-time",
+    "DURATION" | "ESTIMATED-DURATION" | "TRIGGER" => "duration",
+    "RRULE" | "EXRULE" => "recur",
+    "TZOFFSETFROM" | "TZOFFSETTO" => "utc-offset",
+    "PERCENT-COMPLETE" | "PRIORITY" | "SEQUENCE" => "integer",
+    "URL" | "TZURL" => "uri",
+    "ATTENDEE" | "ORGANIZER" => "cal-address",
+    _ => "text",
+  }
+}
+
+/// Tells if some text property has a comma-separated list of values.
+fn is_text_list(name: &str) -> bool {
+  ["CATEGORIES", "RESOURCES"].contains(&name)
+}
+
+/// Builds the error of some malformed jCal value.
+fn malformed(what: &str, value: &Value) -> ICSProcessError {
+  ICSProcessError::MalformedJcal(format!("{} `{}`", what, value))
+}
+
+// ------------------------------ jCal to ICS ------------------------------
+
+/// Converts some jCal calendar into the content of the equivalent `.ics`
+/// file. A JSON array of several calendars is accepted as well.
+pub fn to_ics(json: &str) -> Result<String, ICSProcessError> {
+  let value: Value = serde_json::from_str(json)
+    .map_err(|e| ICSProcessError::MalformedJcal(e.to_string()))?;
+
+  let mut ret = String::new();
+  match &value {
+    Value::Array(v) if v.first().is_some_and(Value::is_array) => {
+      for cal in v {
+        component_to_ics(cal, &mut ret)?;
+      }
+    }
+    cal => component_to_ics(cal, &mut ret)?,
+  }
+  Ok(ret)
+}
+
+/// Converts some jCal component, ie. `["vevent", [..], [..]]`, into content
+/// lines.
+fn component_to_ics(
+  component: &Value,
+  out: &mut String,
+) -> Result<(), ICSProcessError> {
+  let (name, props, subs) = match component.as_array().map(Vec::as_slice) {
+    Some([Value::String(name), Value::Array(props), Value::Array(subs)]) => {
+      (name.to_uppercase(), props, subs)
+    }
+    _ => return Err(malformed("component", component)),
+  };
+
+  out.push_str(&fold(&format!("BEGIN:{}", name)));
+  for prop in props {
+    out.push_str(&fold(&property_to_ics(prop)?));
+  }
+  for sub in subs {
+    component_to_ics(sub, out)?;
+  }
+  out.push_str(&fold(&format!("END:{}", name)));
+  Ok(())
+}
+
+/// Converts some jCal property, ie. `["summary", {}, "text", "class"]`, into
+/// some unfolded content line.
+fn property_to_ics(prop: &Value) -> Result<String, ICSProcessError> {
+  let (name, params, typ, values) = match prop.as_array().map(Vec::as_slice) {
+    Some([Value::String(n), Value::Object(p), Value::String(t), vs @ ..])
+      if !vs.is_empty() =>
+    {
+      (n.to_uppercase(), p, t.as_str(), vs)
+    }
+    _ => return Err(malformed("property", prop)),
+  };
+
+  let mut line = name.clone();
+  for (key, value) in params {
+    let value = match value {
+      Value::Array(v) => v.iter().map(scalar_string).collect::<Vec<_>>(),
+      v => vec![scalar_string(v)],
+    };
+    let quoted: Vec<String> = value
+      .into_iter()
+      .map(
+        |s| {
+          if s.contains([':', ';', ',']) {
+            format!("\"{}\"", s)
+          } else {
+            s
+          }
+        },
+      )
+      .collect();
+    line.push_str(&format!(";{}={}", key.to_uppercase(), quoted.join(",")));
+  }
+  if typ != default_type(&name) && typ != "unknown" {
+    line.push_str(&format!(";VALUE={}", typ.to_uppercase()));
+  }
+
+  let values: Vec<String> =
+    values.iter().map(|v| value_to_ics(typ, v)).collect::<Result<_, _>>()?;
+  line.push(':');
+  line.push_str(&values.join(","));
+  Ok(line)
+}
+
+/// Converts some scalar JSON value into a string, without quotes.
+fn scalar_string(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    v => v.to_string(),
+  }
+}
+
+/// Converts some jCal value of type `typ` into its `.ics` form, ie.
+/// `2023-10-10T09:00:00Z` into `20231010T090000Z`.
+fn value_to_ics(typ: &str, value: &Value) -> Result<String, ICSProcessError> {
+  match (typ, value) {
+    ("date" | "date-time" | "time" | "period", Value::String(s)) => {
+      Ok(s.replace(['-', ':'], ""))
+    }
+    ("utc-offset", Value::String(s)) => Ok(s.replace(':', "")),
+    ("text", Value::String(s)) => Ok(escape(s)),
+    ("recur", Value::Object(parts)) => recur_to_ics(parts),
+    (_, Value::String(s)) => Ok(s.clone()),
+    (_, v @ (Value::Number(_) | Value::Bool(_))) => Ok(v.to_string()),
+    (_, v) => Err(malformed("value", v)),
+  }
+}
+
+/// Converts some jCal recurrence rule, ie. `{"freq": "WEEKLY", "byday":
+/// ["TU", "TH"]}`, into its `.ics` form, with `FREQ` first.
+fn recur_to_ics(parts: &Map<String, Value>) -> Result<String, ICSProcessError> {
+  let freq = parts.get("freq").ok_or_else(|| {
+    malformed("recurrence without freq", &Value::Object(parts.clone()))
+  })?;
+  let mut ret = format!("FREQ={}", scalar_string(freq));
+  for (key, value) in parts.iter().filter(|(k, _)| *k != "freq") {
+    let value = match value {
+      Value::Array(v) => v.iter().map(scalar_string).collect::<Vec<_>>(),
+      v => vec![scalar_string(v)],
+    };
+    let value = value.join(",");
+    let value = match key.as_str() {
+      "until" => value.replace(['-', ':'], ""),
+      _ => value,
+    };
+    ret.push_str(&format!(";{}={}", key.to_uppercase(), value));
+  }
+  Ok(ret)
+}
+
+// ------------------------------ ICS to jCal ------------------------------
+
+/// Converts the content of some `.ics` file into the equivalent jCal
+/// calendar, which is pretty-printed.
+pub fn from_ics(ics: &str) -> Result<String, ICSProcessError> {
+  // components being built, each as its name, properties and subcomponents.
+  let mut stack = Vec::<(String, Vec<Value>, Vec<Value>)>::new();
+  let mut cals = Vec::<Value>::new();
+
+  for line in Unfold::new(ics.lines()) {
+    let cl = ContentLine::parse(&line).ok_or_else(|| {
+      ICSProcessError::MalformedJcal(format!("content line `{}`", line))
+    })?;
+    let name = cl.name.to_uppercase();
+    match name.as_str() {
+      "BEGIN" => stack.push((cl.value.to_lowercase(), vec![], vec![])),
+      "END" => {
+        let (comp_name, props, subs) = stack.pop().ok_or_else(|| {
+          ICSProcessError::MalformedJcal(format!("unmatched `{}`", line))
+        })?;
+        let component = json!([comp_name, props, subs]);
+        match stack.last_mut() {
+          Some((_, _, parent_subs)) => parent_subs.push(component),
+          None => cals.push(component),
+        }
+      }
+      _ => match stack.last_mut() {
+        Some((_, props, _)) => props.push(property_from_ics(&name, cl)),
+        None => {
+          return Err(ICSProcessError::MalformedJcal(format!(
+            "property `{}` outside of components",
+            name
+          )))
+        }
+      },
+    }
+  }
+
+  let value = match cals.len() {
+    1 => cals.remove(0),
+    _ => Value::Array(cals),
+  };
+  serde_json::to_string_pretty(&value)
+    .map_err(|e| ICSProcessError::MalformedJcal(e.to_string()))
+}
+
+/// Converts some content line into jCal property, where the `VALUE`
+/// parameter becomes the type.
+fn property_from_ics(name: &str, cl: ContentLine) -> Value {
+  let mut typ = default_type(name).to_string();
+  let mut params = Map::new();
+  for (key, mut values) in cl.params {
+    match (key.to_uppercase().as_str(), values.len()) {
+      ("VALUE", _) => typ = values.join(",").to_lowercase(),
+      (_, 1) => {
+        params.insert(key.to_lowercase(), Value::String(values.remove(0)));
+      }
+      _ => {
+        let values = values.into_iter().map(Value::String).collect();
+        params.insert(key.to_lowercase(), Value::Array(values));
+      }
+    }
+  }
+
+  let mut prop = vec![
+    Value::String(name.to_lowercase()),
+    Value::Object(params),
+    Value::String(typ.clone()),
+  ];
+  match typ.as_str() {
+    "text" if is_text_list(name) => prop.extend(
+      split_list(&cl.value).into_iter().map(|s| Value::String(unescape(s))),
+    ),
+    "text" => prop.push(Value::String(unescape(&cl.value))),
+    "date" | "date-time" => {
+      prop.extend(cl.value.split(',').map(|s| Value::String(iso_date_time(s))))
+    }
+    "utc-offset" if cl.value.len() >= 5 => {
+      let (hr, min) = cl.value.split_at(3);
+      prop.push(Value::String(format!("{}:{}", hr, &min[..2])))
+    }
+    "integer" => match cl.value.trim().parse::<i64>() {
+      Ok(n) => prop.push(json!(n)),
+      Err(_) => prop.push(Value::String(cl.value)),
+    },
+    "recur" => prop.push(recur_from_ics(&cl.value)),
+    _ => prop.push(Value::String(cl.value)),
+  }
+  Value::Array(prop)
+}
+
+/// Converts some `.ics` date or datetime into its jCal form, ie.
+/// `20231010T090000Z` into `2023-10-10T09:00:00Z`.
+fn iso_date_time(s: &str) -> String {
+  let (date, time) = s.split_once('T').unwrap_or((s, ""));
+  if date.len() != 8 || !date.is_char_boundary(4) || !date.is_char_boundary(6) {
+    return s.to_string();
+  }
+  let mut ret = format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..]);
+  if time.len() >= 6 && time.is_char_boundary(2) && time.is_char_boundary(4) {
+    let (hms, utc) = time.split_at(6);
+    ret.push_str(&format!(
+      "T{}:{}:{}{}",
+      &hms[..2],
+      &hms[2..4],
+      &hms[4..],
+      utc
+    ));
+  }
+  ret
+}
+
+/// Converts some `.ics` recurrence rule into its jCal form, where numeric
+/// items are numbers, and rule parts with several items are arrays.
+fn recur_from_ics(value: &str) -> Value {
+  let mut parts = Map::new();
+  for part in value.split(';') {
+    let Some((key, items)) = part.split_once('=') else {
+      continue;
+    };
+    let key = key.to_lowercase();
+    let mut items: Vec<Value> = items
+      .split(',')
+      .map(|item| match (key.as_str(), item.parse::<i64>()) {
+        ("until", _) => Value::String(iso_date_time(item)),
+        (_, Ok(n)) => json!(n),
+        (_, Err(_)) => Value::String(item.to_string()),
+      })
+      .collect();
+    let value = match items.len() {
+      1 => items.remove(0),
+      _ => Value::Array(items),
+    };
+    parts.insert(key, value);
+  }
+  Value::Object(parts)
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use super::*;
+
+  const VEVENT_JCAL: &str = r#"["vcalendar", [["version", {}, "text", "2.0"]],
+    [["vevent", [
+      ["summary", {}, "text", "Meeting; with, friends"],
+      ["dtstart", {"tzid": "America/New_York"}, "date-time",
+        "2023-10-10T09:00:00"],
+      ["duration", {}, "duration", "PT1H"],
+      ["rrule", {}, "recur", {"byday": ["TU", "TH"], "freq": "WEEKLY",
+        "count": 10}],
+      ["exdate", {}, "date-time", "2023-10-12T13:00:00Z",
+        "2023-10-17T13:00:00Z"],
+      ["categories", {}, "text", "Work", "a,b"],
+      ["attendee", {"member": ["mailto:a@x", "mailto:b@x"]}, "cal-address",
+        "mailto:c@x"],
+      ["x-holiday", {}, "date", "2023-12-25"]
+    ], [["valarm", [["trigger", {}, "duration", "-PT15M"]], []]]]]]"#;
+
+  #[test]
+  fn jcal_to_ics() {
+    let expected = [
+      "BEGIN:VCALENDAR",
+      "VERSION:2.0",
+      "BEGIN:VEVENT",
+      "SUMMARY:Meeting\\; with\\, friends",
+      "DTSTART;TZID=America/New_York:20231010T090000",
+      "DURATION:PT1H",
+      "RRULE:FREQ=WEEKLY;BYDAY=TU,TH;COUNT=10",
+      "EXDATE:20231012T130000Z,20231017T130000Z",
+      "CATEGORIES:Work,a\\,b",
+      "ATTENDEE;MEMBER=\"mailto:a@x\",\"mailto:b@x\":mailto:c@x",
+      "X-HOLIDAY;VALUE=DATE:20231225",
+      "BEGIN:VALARM",
+      "TRIGGER:-PT15M",
+      "END:VALARM",
+      "END:VEVENT",
+      "END:VCALENDAR",
+    ];
+    let ics = to_ics(VEVENT_JCAL).unwrap();
+    assert_eq!(
+      expected.to_vec(),
+      ics.split_terminator("\r\n").collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn ics_to_jcal() {
+    let ics = to_ics(VEVENT_JCAL).unwrap();
+    let jcal: Value = serde_json::from_str(&from_ics(&ics).unwrap()).unwrap();
+    let expected: Value = serde_json::from_str(VEVENT_JCAL).unwrap();
+    assert_eq!(expected, jcal);
+  }
+
+  #[test]
+  fn malformed_jcal() {
+    assert!(to_ics("[\"vcalendar\", []]").is_err());
+    assert!(to_ics("[\"vcalendar\", [[\"summary\", {}]], []]").is_err());
+    assert!(to_ics("{\"vcalendar\": 1}").is_err());
+    assert!(to_ics("not json").is_err());
+  }
+}
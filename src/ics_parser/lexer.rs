@@ -1,10 +1,12 @@
-use std::iter::Peekable;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
 use std::path::Path;
-use std::str::Chars;
+use std::rc::Rc;
 
-use crate::const_params::ICS_ASSUME_TRANSP_ALWAYS_AFTER_SUMMARY;
 use crate::util::path2string;
 
+use super::content_line::{split_list, unescape, ContentLine, Unfold};
 use super::ICSProcessError;
 
 pub fn char_after_keyword(c: char) -> bool {
@@ -15,23 +17,35 @@ fn is_boring_whitespace(c: char) -> bool {
   c != ' ' && c.is_whitespace() && c != '\n'
 }
 
-/// Tells if a token cannot appear as part of `SUMMARY` string content.
-///
-/// [todo] Consider the edge case where user puts an ICS tag as part of summary.
-pub fn not_in_summary(t: &Token) -> bool {
+/// Tells if the value of some property is made of tokens that the parser
+/// interprets, ie. `DTSTART` and `RRULE`. Values of all other properties are
+/// text, which never contains structure.
+fn has_structured_value(name: &Token) -> bool {
   use Token::*;
-  if [END].contains(t) {
-    return true;
-  }
-
-  return if ICS_ASSUME_TRANSP_ALWAYS_AFTER_SUMMARY {
-    t == &TRANSP
-  } else {
-    false
-  };
+  [
+    BEGIN,
+    END,
+    DTSTART,
+    DTEND,
+    DURATION,
+    DUE,
+    ESTIMATEDDURATION,
+    EXDATE,
+    RDATE,
+    RECURRENCEID,
+    TZOFFSETFROM,
+    TZOFFSETTO,
+    RRULE,
+    TRANSP,
+    STATUS,
+  ]
+  .contains(name)
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// Some token of an `.ics` file. The strings of `Other`, `Text`, `Param` and
+/// `Number` are not kept in the token, but are read from its `Span` by
+/// `Token::string()`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Token {
   // structures
   BEGIN,
@@ -48,16 +62,29 @@ pub enum Token {
   // times
   DTSTART,
   DTEND,
+  DURATION,
+  DUE,
+  ESTIMATEDDURATION,
   TZID,
+  EXDATE,
+  RDATE,
+  RECURRENCEID,
+  TZOFFSETFROM,
+  TZOFFSETTO,
 
   // ignored strings
-  Other(String),
+  Other,
 
   // reserved keywords for item types
   VCALENDAR,
   VEVENT,
+  VTODO,
+  VTIMEZONE,
+  STANDARD,
+  DAYLIGHT,
   LOCATION,
   TRANSP,
+  STATUS,
 
   // repetitions
   RRULE,
@@ -86,18 +113,32 @@ pub enum Token {
 
   // other info
   SUMMARY,
+  UID,
+  DESCRIPTION,
+  CATEGORIES,
+  URL,
+  PERCENTCOMPLETE,
+
+  // calendar metadata
+  XWRCALNAME,
+  XWRTIMEZONE,
+
+  // escaped text values, and possibly quoted parameter values
+  Text,
+  Param,
 
   // format
   NEXTLINE,
   SPACE,
 
   // numeral
-  Number(String),
+  Number,
 }
 
 impl Token {
-  /// Attempts to cast a token as a str.
-  pub fn cast_as_string(&self) -> String {
+  /// Gets the string that some token stands for, where `raw` is the text of
+  /// its span. `Text` is unescaped, and `Param` is unquoted.
+  pub fn string(self, raw: &str) -> String {
     use Token::*;
     let ret = match self {
       COLON => ":",
@@ -108,32 +149,204 @@ impl Token {
       UNDERSCORE => "_",
       DASH => "-",
       COMMA => ",",
-      Other(s) | Number(s) => &s,
+      Other | Number => raw,
+      Text => return unescape(raw),
+      Param => unquote(raw),
       NEXTLINE => "",
       SPACE => " ",
       tok => return tok.to_string(),
     };
     ret.to_string()
   }
+
+  /// Describes some token for error messages, where `raw` is the text of its
+  /// span, ie. `Other(X)` or `COLON`.
+  pub fn describe(self, raw: &str) -> String {
+    match self {
+      Token::Other | Token::Text | Token::Param | Token::Number => {
+        format!("{:?}({})", self, raw)
+      }
+      tok => tok.to_string(),
+    }
+  }
 }
 
-pub struct IcsLexer<'a> {
-  name: String,
-  stream: Peekable<Chars<'a>>,
+/// Strips the double quotes around some parameter value, if any.
+fn unquote(raw: &str) -> &str {
+  match raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+    Some(unquoted) => unquoted,
+    None => raw,
+  }
 }
 
-impl<'a> IcsLexer<'a> {
-  /// Creates an ics lexer from some string.
-  pub fn new<P: AsRef<Path>>(path: P, content: &'a str) -> IcsLexer<'a> {
-    IcsLexer {
-      name: path2string(path),
-      stream: content.chars().peekable(),
+/// Converts some identifier to its keyword token, or `Other` if it is not
+/// a keyword.
+pub fn keyword(ident: &str) -> Token {
+  match ident {
+    "BEGIN" => Token::BEGIN,
+    "END" => Token::END,
+    "DTSTART" => Token::DTSTART,
+    "DTEND" => Token::DTEND,
+    "DURATION" => Token::DURATION,
+    "DUE" => Token::DUE,
+    "ESTIMATED-DURATION" => Token::ESTIMATEDDURATION,
+    "TZID" => Token::TZID,
+    "EXDATE" => Token::EXDATE,
+    "RDATE" => Token::RDATE,
+    "RECURRENCE-ID" => Token::RECURRENCEID,
+    "TZOFFSETFROM" => Token::TZOFFSETFROM,
+    "TZOFFSETTO" => Token::TZOFFSETTO,
+    "VCALENDAR" => Token::VCALENDAR,
+    "VEVENT" => Token::VEVENT,
+    "VTODO" => Token::VTODO,
+    "VTIMEZONE" => Token::VTIMEZONE,
+    "STANDARD" => Token::STANDARD,
+    "DAYLIGHT" => Token::DAYLIGHT,
+    "LOCATION" => Token::LOCATION,
+    "RRULE" => Token::RRULE,
+    "SUMMARY" => Token::SUMMARY,
+    "UID" => Token::UID,
+    "DESCRIPTION" => Token::DESCRIPTION,
+    "CATEGORIES" => Token::CATEGORIES,
+    "URL" => Token::URL,
+    "PERCENT-COMPLETE" => Token::PERCENTCOMPLETE,
+    "X-WR-CALNAME" => Token::XWRCALNAME,
+    "X-WR-TIMEZONE" => Token::XWRTIMEZONE,
+    "TRANSP" => Token::TRANSP,
+    "STATUS" => Token::STATUS,
+    "FREQ" => Token::FREQ,
+    "INTERVAL" => Token::INTERVAL,
+    "COUNT" => Token::COUNT,
+    "UNTIL" => Token::UNTIL,
+    "SECONDLY" => Token::SECONDLY,
+    "MINUTELY" => Token::MINUTELY,
+    "HOURLY" => Token::HOURLY,
+    "DAILY" => Token::DAILY,
+    "WEEKLY" => Token::WEEKLY,
+    "MONTHLY" => Token::MONTHLY,
+    "YEARLY" => Token::YEARLY,
+    "BYMIN" => Token::BYMIN,
+    "BYHOUR" => Token::BYHOUR,
+    "BYDAY" => Token::BYDAY,
+    "BYMONTHDAY" => Token::BYMONTHDAY,
+    "BYYEARDAY" => Token::BYYEARDAY,
+    "BYWEEKNO" => Token::BYWEEKNO,
+    "BYMONTH" => Token::BYMONTH,
+    "BYSETPOS" => Token::BYSETPOS,
+    "WKST" => Token::WKST,
+    _ => Token::Other,
+  }
+}
+
+/// Some unfolded content line of some `.ics` file, which is shared by the
+/// spans of its tokens for error snippets.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SourceLine {
+  /// Name of the file, ie. its path.
+  pub name: Rc<str>,
+
+  /// 1-based number of the physical line where the content line starts.
+  pub line_no: usize,
+
+  pub text: String,
+}
+
+/// Where some token is in its `.ics` file, ie. the byte range it spans in
+/// its content line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+  pub source: Rc<SourceLine>,
+  pub start: usize,
+  pub end: usize,
+}
+
+impl Span {
+  /// Gets the 1-based number of the physical line where the span starts.
+  pub fn line(&self) -> usize {
+    self.source.line_no
+  }
+
+  /// Gets the 1-based column, in characters, where the span starts.
+  pub fn col(&self) -> usize {
+    char_len(&self.source.text[..self.start]) + 1
+  }
+
+  /// Gets the text that the span covers.
+  pub fn text(&self) -> &str {
+    self.source.text.get(self.start..self.end).unwrap_or("")
+  }
+
+  /// Extends some span to the end of another on the same content line.
+  pub fn to(&self, end: &Span) -> Span {
+    Span {
+      end: end.end.max(self.end),
+      ..self.clone()
     }
   }
 
-  /// Gets the name of ics file
-  pub fn get_name(&self) -> String {
-    self.name.clone()
+  /// Extends some span to its whole content line.
+  pub fn whole_line(&self) -> Span {
+    let end = self.source.text.len();
+    Span { start: 0, end, ..self.clone() }
+  }
+}
+
+impl std::fmt::Display for Span {
+  /// Renders the content line of the span, with carets under the span, ie.
+  ///
+  /// ```text
+  ///    |
+  /// 12 | RRULE:FREQ=WEEKLY;BYDAY=FOO
+  ///    |                         ^^^
+  /// ```
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let text = &self.source.text;
+    let gutter = " ".repeat(self.line().to_string().len());
+    let len = match text.get(self.start..self.end) {
+      Some(spanned) => char_len(spanned).max(1),
+      None => 1,
+    };
+    writeln!(f, "{} |", gutter)?;
+    writeln!(f, "{} | {}", self.line(), text)?;
+    write!(f, "{} | {}{}", gutter, " ".repeat(self.col() - 1), "^".repeat(len))
+  }
+}
+
+/// Some token of a content line, along with the byte range it spans.
+type LineToken = (Token, usize, usize);
+
+/// Counts the characters of some string.
+fn char_len(s: &str) -> usize {
+  s.chars().count()
+}
+
+/// A lexer over the value of some content line, which splits the value into
+/// tokens.
+struct ValueLexer<'b> {
+  value: &'b str,
+
+  /// Byte offset of the rest of the value.
+  pos: usize,
+}
+
+impl<'b> ValueLexer<'b> {
+  fn new(value: &'b str) -> ValueLexer<'b> {
+    ValueLexer { value, pos: 0 }
+  }
+
+  /// Lexes all tokens of the value, whose byte ranges are relative to the
+  /// value.
+  fn tokens(mut self) -> Result<Vec<LineToken>, ICSProcessError> {
+    let mut ret = Vec::<LineToken>::new();
+    loop {
+      self.take_while(is_boring_whitespace);
+      let start = self.pos;
+      match self.token() {
+        Ok(tok) => ret.push((tok, start, self.pos)),
+        Err(ICSProcessError::EOF) => break Ok(ret),
+        Err(e) => break Err(e),
+      }
+    }
   }
 
   /// Advances the lexer and returns a particular token.
@@ -143,117 +356,51 @@ impl<'a> IcsLexer<'a> {
   }
 
   /// Fetches the current character without advancing the lexer stream.
-  pub fn current(&mut self) -> Result<char, ICSProcessError> {
-    match self.stream.peek() {
-      Some(c) => Ok(*c),
-      None => Err(ICSProcessError::EOF),
-    }
-  }
-
-  /// Fetches the current character while advancing the lexer stream.
-  pub fn next(&mut self) -> Result<char, ICSProcessError> {
-    match self.stream.next() {
-      Some(c) => Ok(c),
-      None => Err(ICSProcessError::EOF),
-    }
+  pub fn current(&self) -> Result<char, ICSProcessError> {
+    self.value[self.pos..].chars().next().ok_or(ICSProcessError::EOF)
   }
 
   /// Skips once.
   pub fn skip(&mut self) {
-    self.stream.next();
-  }
-
-  /// Skips while some condition is true.
-  pub fn skip_while<F>(&mut self, pred: F) -> Result<(), ICSProcessError>
-  where
-    F: Fn(char) -> bool,
-  {
-    let mut c = self.current()?;
-    while pred(c) {
-      self.skip();
-      if let Some(&new_c) = self.stream.peek() {
-        c = new_c;
-      } else {
-        break;
-      }
+    if let Ok(c) = self.current() {
+      self.pos += c.len_utf8();
     }
-    Ok(())
   }
 
-  /// Takes while some condition is true.
-  pub fn take_while<F>(&mut self, pred: F) -> Result<String, ICSProcessError>
+  /// Takes while some condition is true, which borrows from the value.
+  pub fn take_while<F>(&mut self, pred: F) -> &'b str
   where
     F: Fn(char) -> bool,
   {
-    let mut ret = String::new();
-    while let Some(&c) = self.stream.peek() {
-      if pred(c) {
-        ret.push(self.next()?);
-      } else {
-        break;
-      }
-    }
-    Ok(ret)
+    let rest = &self.value[self.pos..];
+    let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+    self.pos += len;
+    &rest[..len]
   }
 
-  /// Parses some possibly-keyword identifier
+  /// Parses some possibly-keyword identifier, which may contain dashes, ie.
+  /// `RECURRENCE-ID`.
   pub fn possible_keyword(&mut self) -> Result<Token, ICSProcessError> {
-    let ident_str = self.take_while(|c| c.is_alphabetic())?;
+    let ident_str = self.take_while(|c| c.is_alphabetic() || c == '-');
 
     // handles the case where something looks like a keyword appears as
     // part of normal ident
-    if let Some(c) = self.stream.peek() {
-      if !char_after_keyword(*c) {
-        return Ok(Token::Other(ident_str));
-      }
-    }
-
-    match ident_str.as_str() {
-      "BEGIN" => Ok(Token::BEGIN),
-      "END" => Ok(Token::END),
-      "DTSTART" => Ok(Token::DTSTART),
-      "DTEND" => Ok(Token::DTEND),
-      "TZID" => Ok(Token::TZID),
-      "VCALENDAR" => Ok(Token::VCALENDAR),
-      "VEVENT" => Ok(Token::VEVENT),
-      "LOCATION" => Ok(Token::LOCATION),
-      "RRULE" => Ok(Token::RRULE),
-      "SUMMARY" => Ok(Token::SUMMARY),
-      "TRANSP" => Ok(Token::TRANSP),
-      "FREQ" => Ok(Token::FREQ),
-      "INTERVAL" => Ok(Token::INTERVAL),
-      "COUNT" => Ok(Token::COUNT),
-      "UNTIL" => Ok(Token::UNTIL),
-      "SECONDLY" => Ok(Token::SECONDLY),
-      "MINUTELY" => Ok(Token::MINUTELY),
-      "HOURLY" => Ok(Token::HOURLY),
-      "DAILY" => Ok(Token::DAILY),
-      "WEEKLY" => Ok(Token::WEEKLY),
-      "MONTHLY" => Ok(Token::MONTHLY),
-      "YEARLY" => Ok(Token::YEARLY),
-      "BYMIN" => Ok(Token::BYMIN),
-      "BYHOUR" => Ok(Token::BYHOUR),
-      "BYDAY" => Ok(Token::BYDAY),
-      "BYMONTHDAY" => Ok(Token::BYMONTHDAY),
-      "BYYEARDAY" => Ok(Token::BYYEARDAY),
-      "BYWEEKNO" => Ok(Token::BYWEEKNO),
-      "BYMONTH" => Ok(Token::BYMONTH),
-      "BYSETPOS" => Ok(Token::BYSETPOS),
-      "WKST" => Ok(Token::WKST),
-      _ => Ok(Token::Other(ident_str)),
+    match self.current() {
+      Ok(c) if !char_after_keyword(c) => Ok(Token::Other),
+      _ => Ok(keyword(ident_str)),
     }
   }
 
   /// Parses some sequence of number.
   pub fn number(&mut self) -> Result<Token, ICSProcessError> {
-    let num_str = self.take_while(|c| c.is_digit(10))?;
-    Ok(Token::Number(num_str))
+    self.take_while(|c| c.is_ascii_digit());
+    Ok(Token::Number)
   }
 
   pub fn token(&mut self) -> Result<Token, ICSProcessError> {
     let curr_char = self.current()?;
     if is_boring_whitespace(curr_char) {
-      self.skip_while(is_boring_whitespace)?;
+      self.take_while(is_boring_whitespace);
       self.token()
     } else {
       match curr_char {
@@ -263,13 +410,156 @@ impl<'a> IcsLexer<'a> {
         '/' => self.single(Token::SLASH),
         '_' => self.single(Token::UNDERSCORE),
         '-' => self.single(Token::DASH),
-        '\n' => self.single(Token::NEXTLINE),
         ' ' => self.single(Token::SPACE),
         '.' => self.single(Token::PERIOD),
         ',' => self.single(Token::COMMA),
         'A'..='Z' | 'a'..='z' => self.possible_keyword(),
         '0'..='9' => self.number(),
-        c => self.single(Token::Other(c.to_string())),
+        _ => self.single(Token::Other),
+      }
+    }
+  }
+}
+
+/// Lexes some unfolded content line into tokens, namely its name, parameters,
+/// colon, value and a trailing `NEXTLINE`. Each parameter value becomes a
+/// single `Param` token, and each text value a single `Text` token; the values
+/// of some parameter, as well as the text values in `CATEGORIES`, are
+/// separated by `COMMA`.
+fn lex_line(line: &str) -> Result<Vec<LineToken>, ICSProcessError> {
+  let content_line = match ContentLine::parse(line) {
+    Some(cl) => cl,
+    None => {
+      return Err(ICSProcessError::Other(format!(
+        "Malformed content line `{}`",
+        line
+      )))
+    }
+  };
+
+  let name = keyword(&content_line.name.to_ascii_uppercase());
+  let name_end = line.find([';', ':']).unwrap_or(line.len());
+  let mut ret = vec![(name, 0, name_end)];
+
+  let mut cursor = name_end;
+  for (param_name, param_values) in content_line.params {
+    let eq = cursor + line[cursor..].find('=').unwrap_or(0);
+    ret.push((Token::SEMICOLON, cursor, cursor + 1));
+    ret.push((keyword(&param_name.to_ascii_uppercase()), cursor + 1, eq));
+    ret.push((Token::EQ, eq, eq + 1));
+
+    cursor = eq + 1;
+    for (i, param_value) in param_values.iter().enumerate() {
+      if i > 0 {
+        ret.push((Token::COMMA, cursor, cursor + 1));
+        cursor += 1;
+      }
+      let quotes = if line[cursor..].starts_with('"') { 2 } else { 0 };
+      let value_end = cursor + param_value.len() + quotes;
+      ret.push((Token::Param, cursor, value_end));
+      cursor = value_end;
+    }
+  }
+
+  let value = &content_line.value;
+  let value_start = line.len() - value.len();
+  ret.push((Token::COLON, value_start - 1, value_start));
+
+  if has_structured_value(&name) {
+    for (tok, start, end) in ValueLexer::new(value).tokens()? {
+      ret.push((tok, value_start + start, value_start + end));
+    }
+  } else if name == Token::CATEGORIES {
+    let mut item_start = value_start;
+    for (i, item) in split_list(value).into_iter().enumerate() {
+      if i > 0 {
+        ret.push((Token::COMMA, item_start - 1, item_start));
+      }
+      let item_end = item_start + item.len();
+      ret.push((Token::Text, item_start, item_end));
+      item_start = item_end + 1;
+    }
+  } else if !value.is_empty() {
+    ret.push((Token::Text, value_start, line.len()));
+  }
+  ret.push((Token::NEXTLINE, line.len(), line.len()));
+  Ok(ret)
+}
+
+/// A lexer over the content lines of some `.ics` file, which reads the file
+/// line by line.
+pub struct IcsLexer<'a> {
+  name: Rc<str>,
+  lines: Unfold<Box<dyn Iterator<Item = String> + 'a>>,
+
+  /// The I/O error which ended `lines`, if any.
+  io_error: Rc<Cell<Option<io::Error>>>,
+
+  pending: VecDeque<(Token, Span)>,
+}
+
+impl<'a> IcsLexer<'a> {
+  /// Creates an ics lexer from some string.
+  pub fn new<P: AsRef<Path>>(path: P, content: &'a str) -> IcsLexer<'a> {
+    IcsLexer::from_reader(path, content.as_bytes())
+  }
+
+  /// Creates an ics lexer from some reader, ie. some file or stdin, which is
+  /// only read as far as tokens are taken.
+  pub fn from_reader<P, R>(path: P, reader: R) -> IcsLexer<'a>
+  where
+    P: AsRef<Path>,
+    R: BufRead + 'a,
+  {
+    let io_error = Rc::new(Cell::new(None));
+    let error_slot = io_error.clone();
+    let lines = reader.lines().map_while(move |line| match line {
+      Ok(line) => Some(line),
+      Err(e) => {
+        error_slot.set(Some(e));
+        None
+      }
+    });
+    IcsLexer {
+      name: Rc::from(path2string(path)),
+      lines: Unfold::new(Box::new(lines)),
+      io_error,
+      pending: VecDeque::new(),
+    }
+  }
+
+  /// Gets the name of ics file
+  pub fn get_name(&self) -> String {
+    self.name.to_string()
+  }
+
+  /// Takes the next token along with its span, which lexes the next content
+  /// line when the tokens of the current one run out. Errors in some content
+  /// line are located at the whole line.
+  pub fn token(&mut self) -> Result<(Token, Span), ICSProcessError> {
+    loop {
+      if let Some(tok) = self.pending.pop_front() {
+        return Ok(tok);
+      }
+      let Some(text) = self.lines.next() else {
+        return match self.io_error.take() {
+          Some(e) => Err(ICSProcessError::Io(self.get_name(), e.to_string())),
+          None => Err(ICSProcessError::EOF),
+        };
+      };
+
+      let source = Rc::new(SourceLine {
+        name: self.name.clone(),
+        line_no: self.lines.line_no(),
+        text,
+      });
+      let span =
+        |start: usize, end: usize| Span { source: source.clone(), start, end };
+      match lex_line(&source.text) {
+        Ok(toks) => self.pending.extend(
+          toks.into_iter().map(|(tok, start, end)| (tok, span(start, end))),
+        ),
+        Err(e) => return Err(e.at(span(0, source.text.len()))),
       }
     }
   }
@@ -277,9 +567,6 @@ impl<'a> IcsLexer<'a> {
 
 impl std::fmt::Display for Token {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    match self {
-      Token::Other(s) => write!(f, "Other({})", s),
-      tok => write!(f, "{:?}", tok),
-    }
+    write!(f, "{:?}", self)
   }
 }
@@ -1,19 +1,30 @@
-use std::{fs::File, path::Path};
+use std::{
+  fs::File,
+  io::{BufRead, BufReader},
+  path::Path,
+};
 
 use crate::{
-  time::{timezone::ZoneOffset, MinInstant, TimeError},
+  const_params::MAX_WORKLOAD,
+  time::{
+    timezone::{Tz, ZoneOffset},
+    MinInstant, TimeError,
+  },
   util::path2string,
   util_typs::RefinementError,
 };
 
 use self::{
-  ics_syntax::{ICSParser, ICalendar},
-  lexer::{IcsLexer, Token},
+  ics_syntax::{Freq, ICSParser, ICalendar},
+  lexer::{IcsLexer, Span, Token},
 };
 
 use std::io::Write;
 
+pub mod content_line;
 pub mod ics_syntax;
+pub mod ics_writer;
+pub mod jcal;
 pub mod lexer;
 pub mod peekbuf;
 
@@ -21,14 +32,26 @@ pub mod peekbuf;
 #[derive(Clone)]
 pub enum ICSProcessError {
   NotIcsFile(String),
+  Io(String, String),
   EOF,
-  NaN(Token),
+  NaN(String),
   CannotCastTok(Token),
   ICSTimeMalformatted(String, String),
   MalformedList(Token, Token),
   InvalidFreq(Token),
+  InvalidRRule(Token, String),
+  UnsupportedRRule(Token),
+  RRuleFreqMismatch(Token, Freq),
+  ImpossibleRRule(String),
+  UnknownTzid(String),
+  OrphanedOverride(String),
+  MissingDue(String),
+  MissingEstimate(String),
+  WorkloadOverflow(String, u32),
   UntilAndCountBothAppear(usize, MinInstant),
   Refinement(RefinementError),
+  MalformedJcal(String),
+  At(Span, Box<ICSProcessError>),
   Msg(&'static str),
   Other(String),
 }
@@ -36,7 +59,12 @@ pub enum ICSProcessError {
 impl std::fmt::Display for ICSProcessError {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      ICSProcessError::NotIcsFile(s) => write!(f, "`{}` is not an ics file", s),
+      ICSProcessError::NotIcsFile(s) => {
+        write!(f, "`{}` is neither an ics nor a jcal file", s)
+      }
+      ICSProcessError::Io(name, e) => {
+        write!(f, "Cannot read `{}`: {}", name, e)
+      }
       ICSProcessError::EOF => write!(f, "End of file error"),
       ICSProcessError::CannotCastTok(t) => {
         write!(f, "Cannot cast token `{}` to str", t)
@@ -49,9 +77,52 @@ impl std::fmt::Display for ICSProcessError {
         write!(f, "List malformed with elements {} {}", t1, t2)
       }
       ICSProcessError::InvalidFreq(t) => write!(f, "{} is invalid freq", t),
+      ICSProcessError::InvalidRRule(t, s) => {
+        write!(f, "`{}` is invalid value for rrule {}", s, t)
+      }
+      ICSProcessError::UnsupportedRRule(t) => {
+        write!(f, "rrule {} is not supported", t)
+      }
+      ICSProcessError::RRuleFreqMismatch(t, freq) => {
+        write!(f, "rrule {} cannot be used with freq {:?}", t, freq)
+      }
+      ICSProcessError::ImpossibleRRule(dp) => {
+        write!(f, "rrule never occurs, as no day satisfies `{}`", dp)
+      }
+      ICSProcessError::UnknownTzid(id) => {
+        write!(f, "`{}` is neither a VTIMEZONE nor an IANA timezone", id)
+      }
+      ICSProcessError::OrphanedOverride(uid) => {
+        write!(f, "the recurring event with UID `{}` is skipped", uid)
+      }
+      ICSProcessError::MissingDue(summary) => {
+        write!(f, "VTODO `{}` missing due", summary)
+      }
+      ICSProcessError::MissingEstimate(summary) => {
+        write!(f, "VTODO `{}` missing estimated-duration", summary)
+      }
+      ICSProcessError::WorkloadOverflow(summary, n) => {
+        write!(
+          f,
+          "estimated-duration of VTODO `{}` is {} min, exceeding {} min",
+          summary, n, MAX_WORKLOAD
+        )
+      }
       ICSProcessError::UntilAndCountBothAppear(n, mi) => {
         write!(f, "count=`{}` and until=`{}` cannot both appear", n, mi)
       }
+      ICSProcessError::MalformedJcal(s) => write!(f, "Malformed jCal: {}", s),
+      ICSProcessError::At(span, e) => {
+        write!(
+          f,
+          "{}:{}:{}: {}\n{}",
+          span.source.name,
+          span.line(),
+          span.col(),
+          e,
+          span
+        )
+      }
       ICSProcessError::Msg(s) => write!(f, "ICS err: {}", s),
       ICSProcessError::Other(s) => write!(f, "ICS process error: {}", s),
       ICSProcessError::Refinement(r) => write!(f, "{:?}", r),
@@ -65,6 +136,17 @@ impl std::fmt::Debug for ICSProcessError {
   }
 }
 
+impl ICSProcessError {
+  /// Locates some error at the given span, unless it is already located.
+  /// I/O errors are never located, since they concern the whole file.
+  pub fn at(self, span: Span) -> Self {
+    match self {
+      e @ (ICSProcessError::At(..) | ICSProcessError::Io(..)) => e,
+      e => ICSProcessError::At(span, Box::new(e)),
+    }
+  }
+}
+
 impl<'a> From<&'a ICSProcessError> for ICSProcessError {
   fn from(value: &'a ICSProcessError) -> Self {
     value.clone()
@@ -77,33 +159,64 @@ impl From<RefinementError> for ICSProcessError {
   }
 }
 
-/// Performs lexing plus parsing for the given `.ics` file.
+/// Performs lexing plus parsing for the given `.ics` file, which skips
+/// malformed components as warnings if `lenient`.
 pub fn lex_and_parse<P: AsRef<Path>>(
   path: P,
-  default_tz: ZoneOffset,
+  default_tz: &Tz,
+  lenient: bool,
 ) -> Result<ICalendar, ICSProcessError> {
-  let content = std::fs::read_to_string(&path)
-    .expect(format!("Cannot read from `{}`", path2string(&path)).as_str());
+  let file = File::open(&path)
+    .map_err(|e| ICSProcessError::Io(path2string(&path), e.to_string()))?;
+  lex_and_parse_reader(path, BufReader::new(file), default_tz, lenient)
+}
+
+/// Performs lexing plus parsing for the `.ics` content of some reader, ie.
+/// stdin, where `name` is shown in errors.
+pub fn lex_and_parse_reader<P: AsRef<Path>, R: BufRead>(
+  name: P,
+  reader: R,
+  default_tz: &Tz,
+  lenient: bool,
+) -> Result<ICalendar, ICSProcessError> {
+  let lex = IcsLexer::from_reader(name, reader);
+  ICSParser::from_ics_lexer(lex).lenient(lenient).parse(default_tz)
+}
+
+/// Performs lexing plus parsing for the content of some jCal file, ie.
+/// `.json` or `.jcal`, once converted into `.ics` content, where `name` is
+/// shown in errors.
+pub fn lex_and_parse_jcal<P: AsRef<Path>>(
+  name: P,
+  content: &[u8],
+  default_tz: &Tz,
+  lenient: bool,
+) -> Result<ICalendar, ICSProcessError> {
+  let json = std::str::from_utf8(content)
+    .map_err(|e| ICSProcessError::Io(path2string(&name), e.to_string()))?;
 
-  let lex = IcsLexer::new(&path, &content);
-  ICSParser::from_ics_lexer(lex).parse(default_tz)
+  let content = jcal::to_ics(json)?;
+  lex_and_parse_reader(name, content.as_bytes(), default_tz, lenient)
 }
 
 #[allow(dead_code)]
 pub fn test_lexer<P: AsRef<Path>>(path: P) -> Result<(), ICSProcessError> {
   let content = std::fs::read_to_string(&path)
-    .expect(format!("Cannot read from `{}`", path2string(&path)).as_str());
+    .map_err(|e| ICSProcessError::Io(path2string(&path), e.to_string()))?;
 
   let mut lex = IcsLexer::new(&path, &content);
 
-  let mut out_file = File::create(format!("{}.tokens", path2string(&path)))
-    .expect("Cannot open test lexer file");
+  let out_name = format!("{}.tokens", path2string(&path));
+  let out_err =
+    |e: std::io::Error| ICSProcessError::Io(out_name.clone(), e.to_string());
+  let mut out_file = File::create(&out_name).map_err(out_err)?;
 
   loop {
     match lex.token() {
-      Ok(tok) => {
-        write!(out_file, "{}\n", tok)
-          .expect("Failed to write lexing result to out-file");
+      Ok((tok, span)) => {
+        let tok = tok.describe(span.text());
+        writeln!(out_file, "{}:{} {}", span.line(), span.col(), tok)
+          .map_err(out_err)?;
       }
       Err(ICSProcessError::EOF) => break Ok(()),
       Err(e) => break Err(e),
@@ -113,7 +226,8 @@ pub fn test_lexer<P: AsRef<Path>>(path: P) -> Result<(), ICSProcessError> {
 
 #[allow(dead_code)]
 pub fn test_parser(ics_filename: &str) -> Result<(), TimeError> {
-  let parse_result = lex_and_parse(ics_filename, ZoneOffset::new(-240)?)?;
+  let parse_result =
+    lex_and_parse(ics_filename, &ZoneOffset::new(-240)?.into(), false)?;
 
   let mut out_file = File::create(format!("{}.parsed", ics_filename))
     .expect("Cannot open test parser file");
@@ -123,3 +237,43 @@ pub fn test_parser(ics_filename: &str) -> Result<(), TimeError> {
 
   Ok(())
 }
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn read_from_readers() {
+    let utc: Tz = ZoneOffset::utc().into();
+    let content = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:a\r\n\
+      SUMMARY:Lecture\\, room 2\r\nDTSTART:20230904T090000Z\r\n\
+      DTEND:20230904T095000Z\r\n\
+      DESCRIPTION:Some description\\, which is\r\n  folded\r\n\
+      END:VEVENT\r\nBEGIN:VEVENT\r\nUID:b\r\nSUMMARY:Exam\r\n\
+      DTSTART:20230905T090000Z\r\nDTEND:20230905T100000Z\r\n\
+      END:VEVENT\r\nEND:VCALENDAR\r\n";
+    let cal =
+      lex_and_parse_reader("<stdin>", content.as_bytes(), &utc, false).unwrap();
+    assert_eq!(2, cal.content.len());
+    assert_eq!("Lecture, room 2", cal.content[0].summary);
+    let description = cal.content[0].description.as_deref();
+    assert_eq!(Some("Some description, which is folded"), description);
+    assert_eq!("Exam", cal.content[1].summary);
+
+    // invalid UTF-8 is an I/O error rather than a panic.
+    let bytes = b"BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:\xff\r\n";
+    match lex_and_parse_reader("<stdin>", &bytes[..], &utc, false) {
+      Err(ICSProcessError::Io(name, _)) => assert_eq!("<stdin>", name),
+      Err(e) => panic!("unexpected error {}", e),
+      Ok(_) => panic!("invalid UTF-8 should not parse"),
+    }
+
+    let missing = std::env::temp_dir().join("taggytime_missing.ics");
+    assert!(matches!(
+      lex_and_parse(&missing, &utc, false),
+      Err(ICSProcessError::Io(..))
+    ));
+  }
+}
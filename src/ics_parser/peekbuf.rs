@@ -4,21 +4,25 @@
 
 use std::collections::VecDeque;
 
-use super::lexer::{IcsLexer, Token};
+use super::lexer::{IcsLexer, Span, Token};
 use super::ICSProcessError;
 
 /// A buffer wrapped on top of a lexer that allows arbitrary-depth `peek()`.
 pub struct PeekBuffer<'a> {
-  buf: VecDeque<Result<Token, ICSProcessError>>,
+  buf: VecDeque<Result<(Token, Span), ICSProcessError>>,
   lex: IcsLexer<'a>,
+
+  /// Span of the last token taken.
+  last: Option<Span>,
 }
 
 impl<'a> PeekBuffer<'a> {
   /// Creates a new `PeekBuffer` instance from a `crate::lex::Lexer`.
   pub fn from_lexer(lexer: IcsLexer<'a>) -> Self {
     Self {
-      buf: VecDeque::<Result<Token, ICSProcessError>>::new(),
+      buf: VecDeque::<Result<(Token, Span), ICSProcessError>>::new(),
       lex: lexer,
+      last: None,
     }
   }
 
@@ -35,7 +39,20 @@ impl<'a> PeekBuffer<'a> {
       // so that get() will return Some(_).
       self.buf.push_back(self.lex.token());
     }
-    self.buf.get(n).expect("peekable should have kth element").as_ref()
+    match self.buf.get(n).expect("peekable should have kth element") {
+      Ok((tok, _)) => Ok(tok),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Gets the text of the `n`th item, just like `peek()`, which is empty if
+  /// the item is an error.
+  pub fn peek_text(&mut self, n: usize) -> &str {
+    let _ = self.peek(n);
+    match self.buf.get(n) {
+      Some(Ok((_, span))) => span.text(),
+      _ => "",
+    }
   }
 
   /// Gets the next token. This function always advances the lexer by one token.
@@ -48,6 +65,18 @@ impl<'a> PeekBuffer<'a> {
       None => self.lex.token(),
     };
 
-    ret
+    let (tok, span) = ret?;
+    self.last = Some(span);
+    Ok(tok)
+  }
+
+  /// Gets the span of the last token taken, if any.
+  pub fn span(&self) -> Option<&Span> {
+    self.last.as_ref()
+  }
+
+  /// Gets the text of the last token taken, if any.
+  pub fn text(&self) -> &str {
+    self.last.as_ref().map_or("", Span::text)
   }
 }
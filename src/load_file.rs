@@ -1,29 +1,163 @@
 //! Loads various types of files.
 
-use std::path::Path;
+use std::{io, path::Path};
 
 use crate::{
   calendar::cal_event::Event,
-  ics_parser::{lex_and_parse, ICSProcessError},
-  time::timezone::ZoneOffset,
+  ics_parser::{
+    ics_syntax::{ICalendar, ImportWarning, Vtodo},
+    lex_and_parse, lex_and_parse_jcal, lex_and_parse_reader, ICSProcessError,
+  },
+  time::timezone::Tz,
   util::path2string,
 };
 
-/// Loads the given `.ics` file according to the default timezone offset.
-pub fn load_schedule_ics<P: AsRef<Path>>(
+/// Parses the given `.ics` or jCal file according to the default timezone,
+/// where the format is told by the extension, and `-` stands for some `.ics`
+/// file read from stdin. In lenient mode, malformed components are skipped as
+/// warnings.
+fn parse_ics<P: AsRef<Path>>(
   path: P,
-  default_tz: ZoneOffset,
-) -> Result<Vec<Event>, ICSProcessError> {
-  let bad_extension = Err(ICSProcessError::NotIcsFile(path2string(&path)));
+  default_tz: &Tz,
+  lenient: bool,
+) -> Result<ICalendar, ICSProcessError> {
+  if path.as_ref() == Path::new("-") {
+    let stdin = io::stdin().lock();
+    return lex_and_parse_reader("<stdin>", stdin, default_tz, lenient);
+  }
   match path.as_ref().extension() {
-    Some(ext) => {
-      if ext != "ics" {
-        return bad_extension;
-      }
+    Some(ext) if ext == "ics" => lex_and_parse(path, default_tz, lenient),
+    Some(ext) if ext == "json" || ext == "jcal" => {
+      lex_and_parse_jcal(&path, &read_file(&path)?, default_tz, lenient)
+    }
+    _ => Err(ICSProcessError::NotIcsFile(path2string(&path))),
+  }
+}
 
-      let parse_result = lex_and_parse(path, default_tz)?;
-      parse_result.content.into_iter().map(Event::try_from).collect()
+/// Parses the content read from the given `.ics` or jCal file, like
+/// `parse_ics()`.
+fn parse_content<P: AsRef<Path>>(
+  path: P,
+  content: &[u8],
+  default_tz: &Tz,
+  lenient: bool,
+) -> Result<ICalendar, ICSProcessError> {
+  match path.as_ref().extension() {
+    Some(ext) if ext == "ics" => {
+      lex_and_parse_reader(path, content, default_tz, lenient)
     }
-    None => bad_extension,
+    Some(ext) if ext == "json" || ext == "jcal" => {
+      lex_and_parse_jcal(path, content, default_tz, lenient)
+    }
+    _ => Err(ICSProcessError::NotIcsFile(path2string(&path))),
+  }
+}
+
+/// Reads the whole content of some file, ie. so that it is hashed and parsed
+/// as the same bytes.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, ICSProcessError> {
+  std::fs::read(&path)
+    .map_err(|e| ICSProcessError::Io(path2string(&path), e.to_string()))
+}
+
+/// Events loaded from some `.ics` or jCal file.
+pub struct Schedule {
+  /// Name of the calendar given by `X-WR-CALNAME`, if any.
+  pub name: Option<String>,
+
+  pub events: Vec<Event>,
+
+  /// Components skipped in lenient mode.
+  pub warnings: Vec<ImportWarning>,
+}
+
+/// Loads the given `.ics` file according to the default timezone. In lenient
+/// mode, `VEVENT`s that are malformed or cannot be converted into events are
+/// skipped, and returned as warnings; otherwise, they fail the whole file.
+pub fn load_schedule_ics<P: AsRef<Path>>(
+  path: P,
+  default_tz: &Tz,
+  lenient: bool,
+) -> Result<Schedule, ICSProcessError> {
+  to_schedule(parse_ics(path, default_tz, lenient)?, lenient)
+}
+
+/// Loads the content read from the given `.ics` or jCal file, like
+/// `load_schedule_ics()`.
+pub fn load_schedule_content<P: AsRef<Path>>(
+  path: P,
+  content: &[u8],
+  default_tz: &Tz,
+  lenient: bool,
+) -> Result<Schedule, ICSProcessError> {
+  to_schedule(parse_content(path, content, default_tz, lenient)?, lenient)
+}
+
+/// Converts the `VEVENT`s of some parsed calendar into events.
+fn to_schedule(
+  mut cal: ICalendar,
+  lenient: bool,
+) -> Result<Schedule, ICSProcessError> {
+  let events = if lenient {
+    let (events, mut warnings) = Event::from_vevents_lenient(cal.content);
+    cal.warnings.append(&mut warnings);
+    cal.warnings.sort_by_key(|w| w.index);
+    events
+  } else {
+    Event::from_vevents(cal.content)?
+  };
+  Ok(Schedule {
+    name: cal.cal_name,
+    events,
+    warnings: cal.warnings,
+  })
+}
+
+/// Hashes some content with 64-bit FNV-1a, which, unlike `DefaultHasher`,
+/// stays the same across builds.
+pub fn hash_content(content: &[u8]) -> u64 {
+  const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+  const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+  content.iter().fold(FNV_OFFSET, |hash, byte| {
+    (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+  })
+}
+
+/// Loads the `VTODO`s of the given `.ics` file according to the default
+/// timezone.
+pub fn load_todos_ics<P: AsRef<Path>>(
+  path: P,
+  default_tz: &Tz,
+) -> Result<Vec<Vtodo>, ICSProcessError> {
+  Ok(parse_ics(path, default_tz, false)?.todos)
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::time::timezone::ZoneOffset;
+
+  use super::*;
+
+  #[test]
+  fn hash_and_load_same_content() {
+    // hashes stored by earlier versions stay valid.
+    assert_eq!(0xcbf2_9ce4_8422_2325, hash_content(b""));
+    assert_eq!(0xaf63_dc4c_8601_ec8c, hash_content(b"a"));
+
+    let content = b"BEGIN:VCALENDAR\r\nX-WR-CALNAME:school\r\nBEGIN:VEVENT\r\n\
+      DTSTART:20231010T090000Z\r\nDTEND:20231010T100000Z\r\n\
+      SUMMARY:class\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let utc: Tz = ZoneOffset::utc().into();
+    let schedule =
+      load_schedule_content("school.ics", content, &utc, false).unwrap();
+    assert_eq!(Some("school".to_string()), schedule.name);
+    assert_eq!("class", schedule.events[0].summary);
+    assert!(matches!(
+      load_schedule_content("school.txt", content, &utc, false),
+      Err(ICSProcessError::NotIcsFile(_))
+    ));
   }
 }
@@ -4,26 +4,28 @@ mod const_params;
 mod ics_parser;
 mod load_file;
 mod taggy_cmd;
+#[cfg(test)]
+mod test_util;
 mod time;
 mod util;
 mod util_typs;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
 use const_params::TAGGYENV_RELATIVE_PATH;
 use serde::{Deserialize, Serialize};
 
-use calendar::{cal_event::Event, task::Task, NameMap};
-use time::{timezone::ZoneOffset, TimeError};
+use calendar::{task::Task, Calendar, NameMap};
+use time::{timezone::Tz, TimeError};
 
 use crate::args::*;
 
 /// Taggytime environment, including calendar schedules
 #[derive(Serialize, Deserialize)]
 pub struct TaggyEnv {
-  tz: ZoneOffset,
-  calendars: NameMap<Vec<Event>>,
+  tz: Tz,
+  calendars: NameMap<Calendar>,
   todolist: NameMap<Task>,
 }
 
@@ -42,10 +44,16 @@ fn store_env<P: AsRef<Path>>(
   Ok(std::fs::write(path, s)?)
 }
 
-fn main() {
+/// Gets the path of the interactive environment under the home directory.
+fn taggyenv_path() -> PathBuf {
   let mut tenv_abs_path =
     home::home_dir().expect("Cannot find home directory! ");
   tenv_abs_path.push(TAGGYENV_RELATIVE_PATH);
+  tenv_abs_path
+}
+
+fn main() {
+  let tenv_abs_path = taggyenv_path();
 
   let cli_info = CliInfo::parse();
 
@@ -1,30 +1,332 @@
 //! Handles taggytime commands.
 
-use std::path::{Path, PathBuf};
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  thread,
+  time::Duration,
+};
 
 use crate::{calendar::task::ExpirableImpact, util::truncate};
 
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
 
 use crate::{
-  calendar::task::{Task, Workload},
+  calendar::{
+    cal_event::Event,
+    task::{Task, Workload},
+    Calendar, EventDiff,
+  },
+  const_params::WATCH_INTERVAL_SECS,
+  ics_parser::{
+    ics_syntax::{ImportWarning, TodoStatus},
+    ics_writer::export_ics,
+    jcal,
+  },
   load_file,
-  time::{self, timezone::ZoneOffset, MinInstant, TimeError},
+  time::{self, timezone::Tz, MinInstant, TimeError},
   util::path2string,
   util_typs::percent::{self, Percent},
   TaggyEnv,
 };
 
 /// Given some `.ics` file, loads it to some `TaggyEnv`. If an optional name is
-/// provided, the loaded calendar will be renamed accordingly.
+/// provided, the loaded calendar will be renamed accordingly; otherwise, it is
+/// named by its `X-WR-CALNAME`, or else by its file name. In lenient mode,
+/// malformed events are skipped, and summarized afterwards.
 fn load_ics_to_tenv<P: AsRef<Path>>(
   tenv: &mut TaggyEnv,
   path: P,
-  name: &str,
+  name: Option<&str>,
+  all_day_blocking: bool,
+  lenient: bool,
 ) -> Result<(), TimeError> {
-  let events = load_file::load_schedule_ics(&path, tenv.tz)?;
-  tenv.calendars.unique_insert(name, events)?;
+  let (schedule, hash) = if path.as_ref() == Path::new("-") {
+    (load_file::load_schedule_ics(&path, &tenv.tz, lenient)?, None)
+  } else {
+    let content = load_file::read_file(&path)?;
+    let schedule =
+      load_file::load_schedule_content(&path, &content, &tenv.tz, lenient)?;
+    (schedule, Some(load_file::hash_content(&content)))
+  };
+  let name = match (name, schedule.name) {
+    (Some(name), _) => name.to_string(),
+    (None, Some(cal_name)) => cal_name,
+    (None, None) => default_cal_name(&path),
+  };
+  let mut cal = Calendar::new(schedule.events, all_day_blocking);
+  if let Some(hash) = hash {
+    let source = std::fs::canonicalize(&path)?;
+    cal = cal.with_source(source, hash);
+  }
+  tenv.calendars.unique_insert(&name, cal)?;
   println!("[taggytime] Loaded `{}` as `{}`", path2string(&path), name);
+  print_warnings(&schedule.warnings);
+  Ok(())
+}
+
+/// Prints the components skipped in lenient mode, if any.
+fn print_warnings(warnings: &[ImportWarning]) {
+  if !warnings.is_empty() {
+    println!("[taggytime] Skipped {} malformed component(s):", warnings.len());
+    for warning in warnings {
+      println!("{}", warning);
+    }
+  }
+}
+
+/// Reloads some calendar of some `TaggyEnv` from the file it is loaded from,
+/// if the file has changed since, and prints how its events change. Whether
+/// all-day events are blocking is kept. Returns whether it is reloaded.
+fn sync_cal(
+  tenv: &mut TaggyEnv,
+  name: &str,
+  lenient: bool,
+) -> Result<bool, TimeError> {
+  let Some(cal) = tenv.calendars.get_mut(name) else {
+    println!("[taggytime] There is no calendar `{}`", name);
+    return Ok(false);
+  };
+  let Some(source) = cal.source.clone() else {
+    println!("[taggytime] `{}` has no source file to sync from", name);
+    return Ok(false);
+  };
+
+  let content = load_file::read_file(&source)?;
+  let hash = load_file::hash_content(&content);
+  if cal.hash == Some(hash) {
+    return Ok(false);
+  }
+  let schedule =
+    load_file::load_schedule_content(&source, &content, &tenv.tz, lenient)?;
+  let diff = EventDiff::new(&cal.events, &schedule.events);
+  cal.events = schedule.events;
+  cal.hash = Some(hash);
+  if diff.is_empty() {
+    println!("[taggytime] Synced `{}`, whose events are unchanged", name);
+  } else {
+    println!("[taggytime] Synced `{}`: {}", name, diff);
+  }
+  print_warnings(&schedule.warnings);
+  Ok(true)
+}
+
+/// Gets the names of the calendars of some `TaggyEnv` that have source files
+/// to sync from, in alphabetical order.
+fn synced_cal_names(tenv: &TaggyEnv) -> Vec<String> {
+  let mut names: Vec<String> = tenv
+    .calendars
+    .iter()
+    .filter(|(_, cal)| cal.source.is_some())
+    .map(|(name, _)| name.clone())
+    .collect();
+  names.sort();
+  names
+}
+
+/// Polls the source files of the calendars of the `TaggyEnv` stored at some
+/// path every `interval`, and reloads those that change. Errors are printed
+/// once until they change, so that files being rewritten are simply retried.
+fn watch<P: AsRef<Path>>(path: P, interval: Duration, lenient: bool) {
+  println!(
+    "[taggytime] Watching calendars every {}s, press Ctrl-C to stop",
+    interval.as_secs()
+  );
+  let mut errors = HashMap::<String, String>::new();
+  let mut env_error: Option<String> = None;
+  loop {
+    match watch_once(&path, lenient, &mut errors) {
+      Ok(_) => env_error = None,
+      Err(e) => {
+        let e = format!("{:?}", e);
+        if env_error.as_ref() != Some(&e) {
+          println!("[taggytime] Cannot reload the environment: {}", e);
+          env_error = Some(e);
+        }
+      }
+    }
+    thread::sleep(interval);
+  }
+}
+
+/// Outcome of some poll of `watch_once()`.
+#[derive(Debug, Default, PartialEq)]
+struct Polled {
+  /// Calendars reloaded and written back.
+  synced: Vec<String>,
+
+  /// Calendars that fail to sync with errors not printed before.
+  reported: Vec<String>,
+}
+
+/// Polls the source files once for `watch()`. The environment is read afresh,
+/// so that changes made by other commands meanwhile, ie. new tasks, are
+/// neither missed nor overwritten. Only the reloaded calendars are written
+/// back, after which the impact of tasks is printed.
+fn watch_once<P: AsRef<Path>>(
+  path: P,
+  lenient: bool,
+  errors: &mut HashMap<String, String>,
+) -> Result<Polled, TimeError> {
+  let mut tenv = crate::load_env(&path)?;
+
+  let mut polled = Polled::default();
+  let mut synced = Vec::<(String, Option<PathBuf>, Option<u64>)>::new();
+  for name in synced_cal_names(&tenv) {
+    let before = tenv.calendars.get(&name);
+    let (source, hash) =
+      before.map(|c| (c.source.clone(), c.hash)).unwrap_or_default();
+    match sync_cal(&mut tenv, &name, lenient) {
+      Ok(changed) => {
+        errors.remove(&name);
+        if changed {
+          synced.push((name, source, hash));
+        }
+      }
+      Err(e) => {
+        let e = format!("{:?}", e);
+        if errors.get(&name) != Some(&e) {
+          println!("[taggytime] Cannot sync `{}`: {}", name, e);
+          errors.insert(name.clone(), e);
+          polled.reported.push(name);
+        }
+      }
+    }
+  }
+  if synced.is_empty() {
+    return Ok(polled);
+  }
+
+  // reads the environment again, since parsing may take a while.
+  let mut latest = crate::load_env(&path)?;
+  polled.synced = write_back(&mut latest, &mut tenv, synced);
+  if !polled.synced.is_empty() {
+    crate::store_env(&path, &latest)?;
+    print_impact(&latest)?;
+  }
+  Ok(polled)
+}
+
+/// Moves the calendars synced in `tenv` into `latest`, given their sources
+/// and hashes before syncing. Those that are removed or replaced in `latest`
+/// meanwhile, ie. by `RmCal` and then `AddCal`, are skipped. Returns the names
+/// of those written back.
+fn write_back(
+  latest: &mut TaggyEnv,
+  tenv: &mut TaggyEnv,
+  synced: Vec<(String, Option<PathBuf>, Option<u64>)>,
+) -> Vec<String> {
+  let mut ret = Vec::new();
+  for (name, source, hash) in synced {
+    let unchanged = latest
+      .calendars
+      .get(&name)
+      .is_some_and(|cal| cal.source == source && cal.hash == hash);
+    if !unchanged {
+      continue;
+    }
+    if let Some(cal) = tenv.calendars.remove(&name) {
+      latest.calendars.insert(&name, cal);
+      ret.push(name);
+    }
+  }
+  ret
+}
+
+/// Names some calendar after the stem of its file, ie. `work` for `work.ics`,
+/// or `stdin` if it is read from stdin.
+fn default_cal_name<P: AsRef<Path>>(path: P) -> String {
+  match path.as_ref().file_stem() {
+    Some(stem) if path.as_ref() != Path::new("-") => {
+      stem.to_string_lossy().to_string()
+    }
+    _ => "stdin".to_string(),
+  }
+}
+
+/// Exports the given calendars of some `TaggyEnv`, or all of them if none is
+/// given, to some `.ics` file, or some jCal file if its extension is `.json`
+/// or `.jcal`. Tasks are exported as well if `with_tasks`.
+fn export_tenv_to_ics<P: AsRef<Path>>(
+  tenv: &TaggyEnv,
+  path: P,
+  cal_names: &[String],
+  with_tasks: bool,
+) -> Result<(), TimeError> {
+  let mut calendars = Vec::<(&String, &Vec<Event>)>::new();
+  for (name, cal) in tenv.calendars.iter() {
+    if cal_names.is_empty() || cal_names.contains(name) {
+      calendars.push((name, &cal.events));
+    }
+  }
+  for name in cal_names.iter().filter(|n| !tenv.calendars.contains(n)) {
+    println!("[taggytime] There is no calendar `{}`", name);
+  }
+
+  let tasks = tenv.todolist.iter().filter(|_| with_tasks);
+  let mut content = export_ics(calendars.iter().copied(), tasks)?;
+  if let Some(ext) = path.as_ref().extension() {
+    if ext == "json" || ext == "jcal" {
+      content = jcal::from_ics(&content)?;
+    }
+  }
+  std::fs::write(&path, content)?;
+  println!("[taggytime] Exported to `{}`", path2string(&path));
+  Ok(())
+}
+
+/// Given some `.ics` file, loads its `VTODO`s as tasks to some `TaggyEnv`.
+/// Tasks named after existing ones are handled according to `on_conflict`.
+/// `VTODO`s that are cancelled or cannot be converted are skipped.
+fn load_todos_to_tenv<P: AsRef<Path>>(
+  tenv: &mut TaggyEnv,
+  path: P,
+  on_conflict: OnConflict,
+) -> Result<(), TimeError> {
+  let vtodos = load_file::load_todos_ics(&path, &tenv.tz)?;
+  let (mut added, mut skipped) = (0, 0);
+  for vtodo in vtodos {
+    let name = vtodo.summary.trim().to_string();
+    if name.is_empty() || vtodo.status == TodoStatus::Cancelled {
+      println!("[taggytime] Skipped unnamed or cancelled task `{}`", name);
+      skipped += 1;
+      continue;
+    }
+    let task = match Task::try_from(vtodo) {
+      Ok(task) => task,
+      Err(e) => {
+        println!("[taggytime] Skipped task `{}`: {:?}", name, e);
+        skipped += 1;
+        continue;
+      }
+    };
+
+    let name = match on_conflict {
+      _ if !tenv.todolist.contains(&name) => name,
+      OnConflict::Skip => {
+        println!("[taggytime] Kept existing task `{}`", name);
+        skipped += 1;
+        continue;
+      }
+      OnConflict::Replace => {
+        println!("[taggytime] Replaced task `{}`", name);
+        name
+      }
+      OnConflict::Rename => (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|n| !tenv.todolist.contains(n))
+        .expect("Some numbered name is not taken"),
+    };
+    tenv.todolist.insert(&name, task);
+    added += 1;
+  }
+
+  println!(
+    "[taggytime] Added {} task(s) from `{}`, skipped {}",
+    added,
+    path2string(&path),
+    skipped
+  );
   Ok(())
 }
 
@@ -42,7 +344,7 @@ fn load_todo_to_tenv(
 pub fn prettyprint_task(
   name: &str,
   task: &Task,
-  tz: ZoneOffset,
+  tz: &Tz,
   impact: &ExpirableImpact,
 ) {
   println!(
@@ -56,6 +358,40 @@ pub fn prettyprint_task(
   )
 }
 
+/// Prints the impact of all tasks of some `TaggyEnv`, along with their sum.
+fn print_impact(tenv: &TaggyEnv) -> Result<(), TimeError> {
+  let mut tasks_impacts = Vec::<(&str, &Task, ExpirableImpact)>::new();
+  for (name, task) in tenv.todolist.iter() {
+    tasks_impacts.push((name, task, tenv.calendars.impact(task)));
+  }
+
+  tasks_impacts.sort_by(|(n1, _, l1), (n2, _, l2)| {
+    l2.partial_cmp(l1).unwrap_or(n2.cmp(n1))
+  });
+
+  println!(
+    "\n\
+Task Name         Due (tz={})      Workload    Progress          Impact
+----------------------------------------------------------------------------",
+    tenv.tz
+  );
+
+  let mut percent_sum = Percent(0);
+  let mut num_expired: usize = 0;
+  for (name, task, exp_impact) in &tasks_impacts {
+    prettyprint_task(name, task, &tenv.tz, exp_impact);
+
+    // sum up statistics
+    match exp_impact {
+      ExpirableImpact::Current(p) => percent_sum = (percent_sum + *p)?,
+      ExpirableImpact::Expired => num_expired += 1,
+    }
+  }
+
+  println!("\n∑ Impact:    {}\n# Expired:   {}\n", percent_sum, num_expired);
+  Ok(())
+}
+
 #[derive(Debug)]
 pub enum TaggyCmdError {
   TimeErr(TimeError),
@@ -73,14 +409,78 @@ impl From<percent::PercentError> for TaggyCmdError {
   }
 }
 
+/// How to handle some imported task whose name is already taken.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OnConflict {
+  /// Keeps the existing task.
+  Skip,
+  /// Replaces the existing task.
+  Replace,
+  /// Adds the imported task under a numbered name, ie. `essay (2)`.
+  Rename,
+}
+
 #[derive(Subcommand)]
 pub enum TaggyCmd {
-  /// Loads some .ics calendar and gives it a name.
+  /// Loads some .ics or jCal (.json, .jcal) calendar and gives it a name.
   AddCal {
-    /// Path to .ics file.
+    /// Path to .ics or jCal file, or `-` to read .ics from stdin.
     path: PathBuf,
-    /// Preferred name of calendar.
+    /// Preferred name of calendar. Defaults to its `X-WR-CALNAME`, or else
+    /// the file name.
+    name: Option<String>,
+    /// Whether all-day events occupy whole days, instead of merely marking
+    /// them.
+    #[arg(long)]
+    block_all_day: bool,
+    /// Whether malformed events are skipped with warnings, instead of failing
+    /// the whole calendar.
+    #[arg(long)]
+    lenient: bool,
+  },
+
+  /// Reloads some calendar from its file if the file has changed, and shows
+  /// the added, removed and modified events.
+  SyncCal {
+    /// Name of calendar.
     name: String,
+    /// Whether malformed events are skipped with warnings, instead of failing
+    /// the whole calendar.
+    #[arg(long)]
+    lenient: bool,
+  },
+
+  /// Reloads every calendar whose file has changed.
+  SyncAll {
+    /// Whether malformed events are skipped with warnings, instead of failing
+    /// the whole calendar.
+    #[arg(long)]
+    lenient: bool,
+  },
+
+  /// Keeps polling the files of calendars, and reloads those that change.
+  Watch {
+    /// Seconds between polls, which is at least 1.
+    #[arg(
+      long,
+      default_value_t = WATCH_INTERVAL_SECS,
+      value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    interval: u64,
+    /// Whether malformed events are skipped with warnings, instead of failing
+    /// the whole calendar.
+    #[arg(long)]
+    lenient: bool,
+  },
+
+  /// Sets whether all-day events of some calendar occupy whole days.
+  SetAllDay {
+    /// Name of calendar.
+    name: String,
+    /// Whether all-day events occupy whole days, instead of merely marking
+    /// them.
+    #[arg(action = clap::ArgAction::Set)]
+    blocking: bool,
   },
 
   /// Removes some .ics calendar.
@@ -89,11 +489,33 @@ pub enum TaggyCmd {
     name: String,
   },
 
+  /// Exports calendars and tasks to some .ics file, or some jCal file if the
+  /// extension is .json or .jcal.
+  Export {
+    /// Path to .ics or jCal file.
+    path: PathBuf,
+    /// Name of calendar to export, which may be repeated. Defaults to all
+    /// calendars.
+    #[arg(long = "cal")]
+    cals: Vec<String>,
+    /// Whether tasks are left out.
+    #[arg(long)]
+    no_tasks: bool,
+  },
+
   /// Shows current calendars
   Cals,
 
   /// Shows current events
-  Events,
+  Events {
+    /// Only shows events in some category.
+    #[arg(long)]
+    category: Option<String>,
+    /// Only shows events whose summary, description or location contains
+    /// some keyword.
+    #[arg(long)]
+    keyword: Option<String>,
+  },
 
   /// Shows current time.
   Now,
@@ -103,7 +525,8 @@ pub enum TaggyCmd {
 
   /// Sets TaggyEnv timezone.
   SetTz {
-    /// Timezone string expression, i.e. -4:00 means EDT.
+    /// Timezone string expression, i.e. -4:00 means EDT, and
+    /// America/New_York follows daylight saving time.
     tz_expr: String,
   },
 
@@ -112,8 +535,8 @@ pub enum TaggyCmd {
     /// Name of task.
     task_name: String,
 
-    /// Workload of task in minutes.
-    load: u32,
+    /// Workload of task in minutes, or as some duration like PT1H30M.
+    load: String,
 
     /// Due date in string expression.
     duedate: String,
@@ -125,6 +548,15 @@ pub enum TaggyCmd {
     tz_opt: Option<String>,
   },
 
+  /// Adds the VTODOs of some .ics file as tasks.
+  AddTodos {
+    /// Path to .ics file, or `-` to read from stdin.
+    path: PathBuf,
+    /// What to do with tasks whose names are already taken.
+    #[arg(long, value_enum, default_value_t = OnConflict::Skip)]
+    on_conflict: OnConflict,
+  },
+
   /// Removes some task.
   RmTask {
     /// Name of task.
@@ -153,13 +585,44 @@ impl TaggyCmd {
     // use TaggyCmdError::*;
     match self {
       // calendar / events related operations
-      AddCal { path, name } => {
-        load_ics_to_tenv(tenv, path, name)?;
+      AddCal { path, name, block_all_day, lenient } => {
+        let name = name.as_deref();
+        load_ics_to_tenv(tenv, path, name, *block_all_day, *lenient)?;
+      }
+      SyncCal { name, lenient } => {
+        let syncable =
+          tenv.calendars.get(name).is_some_and(|c| c.source.is_some());
+        if !sync_cal(tenv, name, *lenient)? && syncable {
+          println!("[taggytime] `{}` is up to date", name);
+        }
       }
+      SyncAll { lenient } => {
+        for name in synced_cal_names(tenv) {
+          match sync_cal(tenv, &name, *lenient) {
+            Ok(true) => (),
+            Ok(false) => println!("[taggytime] `{}` is up to date", name),
+            Err(e) => println!("[taggytime] Cannot sync `{}`: {:?}", name, e),
+          }
+        }
+      }
+      Watch { interval, lenient } => {
+        watch(crate::taggyenv_path(), Duration::from_secs(*interval), *lenient);
+      }
+      SetAllDay { name, blocking } => match tenv.calendars.get_mut(name) {
+        Some(cal) => {
+          cal.all_day_blocking = *blocking;
+          let mode = if *blocking { "blocking" } else { "non-blocking" };
+          println!("[taggytime] All-day events of `{}` are {}", name, mode);
+        }
+        None => println!("[taggytime] There is no calendar `{}`", name),
+      },
       RmCal { name } => match tenv.calendars.remove(name) {
         Some(..) => println!("[taggytime] Removed calendar `{}`", name),
         None => println!("[taggytime] There is no calendar `{}`", name),
       },
+      Export { path, cals, no_tasks } => {
+        export_tenv_to_ics(tenv, path, cals, !*no_tasks)?;
+      }
       Truncate => {
         tenv.calendars.filter_events(|e| !e.ended());
       }
@@ -169,18 +632,31 @@ impl TaggyCmd {
           println!("{}", c);
         }
       }
-      Events => {
+      Events { category, keyword } => {
+        let shown = |e: &&Event| {
+          category.as_ref().is_none_or(|c| e.has_category(c))
+            && keyword.as_ref().is_none_or(|k| e.mentions(k))
+        };
+
         println!("[taggytime] Existing events: \n-------------------------\n");
-        for (_, v) in tenv.calendars.iter() {
-          for e in v {
+        for (_, cal) in tenv.calendars.iter() {
+          for e in cal.events.iter().filter(|e| !e.all_day()).filter(shown) {
             println!("{}", e);
           }
         }
+
+        println!("[taggytime] All-day events: \n-------------------------\n");
+        for (_, cal) in tenv.calendars.iter() {
+          let mode = if cal.all_day_blocking { "blocking" } else { "marker" };
+          for e in cal.events.iter().filter(|e| e.all_day()).filter(shown) {
+            println!("({}) {}", mode, e);
+          }
+        }
       }
 
       // time / timezone related operations
       Now => {
-        let mi = time::MinInstant::now(tenv.tz);
+        let mi = time::MinInstant::now(&tenv.tz);
         println!("[taggytime] now is: {}", mi.as_date_string());
       }
       Tz => {
@@ -204,11 +680,14 @@ impl TaggyCmd {
           due_parts.push(tz);
         }
 
-        let load: Workload = Workload::from_num_min(*load)?;
-        let due = MinInstant::parse_from_str(&due_parts, tenv.tz)?;
+        let load: Workload = load.parse()?;
+        let due = MinInstant::parse_from_str(&due_parts, &tenv.tz)?;
         let todo = Task::new(due, load);
         load_todo_to_tenv(tenv, task_name, todo)?;
       }
+      AddTodos { path, on_conflict } => {
+        load_todos_to_tenv(tenv, path, *on_conflict)?;
+      }
       RmTask { taskname: task_name } => match tenv.todolist.remove(task_name) {
         Some(..) => println!("[taggytime] Removed task `{}`", task_name),
         None => println!("[taggytime] There is no task `{}`", task_name),
@@ -223,41 +702,98 @@ impl TaggyCmd {
           None => println!("[taggytime] Task `{}` does not exist", task_name),
         }
       }
-      Impact => {
-        let mut tasks_impacts = Vec::<(&str, &Task, ExpirableImpact)>::new();
-        for (name, task) in tenv.todolist.iter() {
-          tasks_impacts.push((name, task, tenv.calendars.impact(task)));
-        }
+      Impact => print_impact(tenv)?,
+    }
+    Ok(())
+  }
+}
 
-        tasks_impacts.sort_by(|(n1, _, l1), (n2, _, l2)| {
-          l2.partial_cmp(l1).unwrap_or(n2.cmp(n1))
-        });
+// --------------------------------- Testing ---------------------------------
 
-        println!(
-          "\n\
-Task Name         Due (tz={})      Workload    Progress          Impact
-----------------------------------------------------------------------------",
-          tenv.tz
-        );
-
-        let mut percent_sum = Percent(0);
-        let mut num_expired: usize = 0;
-        for (name, task, exp_impact) in &tasks_impacts {
-          prettyprint_task(name, task, tenv.tz, exp_impact);
-
-          // sum up statistics
-          match exp_impact {
-            ExpirableImpact::Current(p) => percent_sum = (percent_sum + *p)?,
-            ExpirableImpact::Expired => num_expired += 1,
-          }
-        }
+#[allow(dead_code, unused_imports)]
+mod test {
+  use super::*;
 
-        println!(
-          "\n∑ Impact:    {}\n# Expired:   {}\n",
-          percent_sum, num_expired
-        )
-      }
-    }
-    Ok(())
+  /// Some `.ics` file with a single event named `summary`.
+  fn ics_with(summary: &str) -> String {
+    format!(
+      "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:a\r\nSUMMARY:{}\r\n\
+      DTSTART:20231010T090000Z\r\nDTEND:20231010T100000Z\r\nEND:VEVENT\r\n\
+      END:VCALENDAR\r\n",
+      summary
+    )
+  }
+
+  /// Stores some environment with calendar `s` loaded from some `.ics` file
+  /// under a fresh directory, and returns the paths of both.
+  fn stored_env(dir: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let (ics, env) = (dir.join("s.ics"), dir.join("env.json"));
+    std::fs::write(&ics, ics_with("class")).unwrap();
+
+    let empty = r#"{"tz":0,"calendars":{"contents":{}},
+      "todolist":{"contents":{}}}"#;
+    let mut tenv: TaggyEnv = serde_json::from_str(empty).unwrap();
+    load_ics_to_tenv(&mut tenv, &ics, Some("s"), false, false).unwrap();
+    crate::store_env(&env, &tenv).unwrap();
+    (ics, env)
+  }
+
+  #[test]
+  fn watch_polls() {
+    let (ics, env) = stored_env("taggytime_watch_polls");
+    let mut errors = HashMap::new();
+    let stored = std::fs::read(&env).unwrap();
+    assert_eq!(
+      Polled::default(),
+      watch_once(&env, false, &mut errors).unwrap()
+    );
+    assert_eq!(stored, std::fs::read(&env).unwrap());
+
+    std::fs::write(&ics, ics_with("lecture")).unwrap();
+    let polled = watch_once(&env, false, &mut errors).unwrap();
+    assert_eq!(vec!["s"], polled.synced);
+    let tenv = crate::load_env(&env).unwrap();
+    assert_eq!("lecture", tenv.calendars.get("s").unwrap().events[0].summary);
+
+    // errors are reported once, until the file is fixed.
+    std::fs::write(&ics, "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n").unwrap();
+    let polled = watch_once(&env, false, &mut errors).unwrap();
+    assert!(polled.synced.is_empty());
+    assert_eq!(vec!["s"], polled.reported);
+    assert_eq!(
+      Polled::default(),
+      watch_once(&env, false, &mut errors).unwrap()
+    );
+    std::fs::write(&ics, ics_with("seminar")).unwrap();
+    let polled = watch_once(&env, false, &mut errors).unwrap();
+    assert_eq!(vec!["s"], polled.synced);
+    assert!(polled.reported.is_empty());
+    assert!(errors.is_empty());
+  }
+
+  #[test]
+  fn write_back_skips_replaced() {
+    let (ics, env) = stored_env("taggytime_write_back");
+    let mut tenv = crate::load_env(&env).unwrap();
+    let cal = tenv.calendars.get("s").unwrap();
+    let synced = vec![("s".to_string(), cal.source.clone(), cal.hash)];
+    std::fs::write(&ics, ics_with("lecture")).unwrap();
+    assert!(sync_cal(&mut tenv, "s", false).unwrap());
+
+    // `s` is removed and added again from another file meanwhile.
+    let mut latest = crate::load_env(&env).unwrap();
+    let other = ics.with_file_name("other.ics");
+    std::fs::write(&other, ics_with("exam")).unwrap();
+    latest.calendars.remove("s");
+    load_ics_to_tenv(&mut latest, &other, Some("s"), false, false).unwrap();
+    assert!(write_back(&mut latest, &mut tenv, synced.clone()).is_empty());
+    assert_eq!("exam", latest.calendars.get("s").unwrap().events[0].summary);
+
+    let mut latest = crate::load_env(&env).unwrap();
+    assert_eq!(vec!["s"], write_back(&mut latest, &mut tenv, synced));
+    assert_eq!("lecture", latest.calendars.get("s").unwrap().events[0].summary);
   }
 }
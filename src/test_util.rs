@@ -0,0 +1,35 @@
+//! Helpers shared by the tests of several modules.
+
+use crate::{
+  calendar::cal_event::Event,
+  ics_parser::{ics_syntax::ICalendar, lex_and_parse_reader, ICSProcessError},
+  time::timezone::{Tz, ZoneOffset},
+};
+
+/// Some UTC minute, ie. 2023/Oct/9 00:00 on a Monday, that tests count from.
+pub const OCT_9_2023: u32 = 28_280_160;
+
+/// Wraps some components, ie. `VEVENT`s, into a calendar.
+pub fn vcalendar(components: &str) -> String {
+  format!("BEGIN:VCALENDAR\n{components}END:VCALENDAR\n")
+}
+
+/// Parses some `.ics` content named `test.ics` according to the default
+/// timezone, where malformed components are skipped if `lenient`.
+pub fn parse_ics_in(
+  content: &str,
+  default_tz: &Tz,
+  lenient: bool,
+) -> Result<ICalendar, ICSProcessError> {
+  lex_and_parse_reader("test.ics", content.as_bytes(), default_tz, lenient)
+}
+
+/// Parses some `.ics` content named `test.ics`, with UTC as default timezone.
+pub fn parse_ics(content: &str) -> Result<ICalendar, ICSProcessError> {
+  parse_ics_in(content, &ZoneOffset::utc().into(), false)
+}
+
+/// Parses some `.ics` content into events, with UTC as default timezone.
+pub fn parse_events(content: &str) -> Vec<Event> {
+  Event::from_vevents(parse_ics(content).unwrap().content).unwrap()
+}
@@ -1,7 +1,6 @@
 //! Structure that represents calendar days.
 
-use crate::const_params::HANDLE_WKST;
-use crate::ics_parser::ics_syntax::RRuleToks;
+use crate::ics_parser::ics_syntax::{Freq, RRuleToks};
 use crate::ics_parser::lexer::Token;
 use crate::time::{month::Month, week::Weekday};
 
@@ -63,14 +62,16 @@ impl Date {
     hms: &str,
     tz: ZoneOffset,
   ) -> Result<Self, ICSProcessError> {
-    // Return error message
-    let bad = Err(ICSProcessError::ICSTimeMalformatted(
-      ymd.to_string(),
-      hms.to_string(),
-    ));
+    // Return error message, which is only built on failure
+    let bad = || {
+      Err(ICSProcessError::ICSTimeMalformatted(
+        ymd.to_string(),
+        hms.to_string(),
+      ))
+    };
 
     if ymd.len() < 8 || hms.len() < 6 {
-      return bad;
+      return bad();
     }
 
     let yr_str = &ymd[0..4];
@@ -89,45 +90,52 @@ impl Date {
       (Ok(y), Ok(m), Ok(d), Ok(h), Ok(mi)) => {
         let yr = match CeYear::new(y) {
           Ok(y) => y,
-          _ => return bad,
+          _ => return bad(),
         };
 
         // since Month::try_from() is 0-indexed
         let mon = if let Some(m0) = m.checked_sub(1) {
           match Month::try_from(m0) {
             Ok(m) => m,
-            _ => return bad,
+            _ => return bad(),
           }
         } else {
-          return bad;
+          return bad();
         };
 
         let day = if d <= mon.num_days(&yr) && d > 0 {
           d
         } else {
-          return bad;
+          return bad();
         };
 
         let hr = if h <= 23 {
           h
         } else {
-          return bad;
+          return bad();
         };
         let min = if mi <= 59 {
           mi
         } else {
-          return bad;
+          return bad();
         };
 
         Ok(Date { yr, mon, day, hr, min, tz })
       }
-      _ => Err(ICSProcessError::ICSTimeMalformatted(
-        ymd.to_string(),
-        hms.to_string(),
-      )),
+      _ => bad(),
     }
   }
 
+  /// Converts this date into two strings, one is of form `yyyymmdd`, and the
+  /// other is of form `hhmmss`. This is the inverse of
+  /// `from_ics_time_string()`.
+  pub fn as_ics_time_string(&self) -> (String, String) {
+    let ymd =
+      format!("{:04}{:02}{:02}", self.yr.raw(), self.mon as u32 + 1, self.day);
+    let hms = format!("{:02}{:02}00", self.hr, self.min);
+    (ymd, hms)
+  }
+
   // ------------- The followings are all attribute functions.  -------------
 
   /// Day in year, starts from 1.
@@ -143,10 +151,120 @@ impl Date {
     ret
   }
 
-  /// Given a default timezone, parses a string as a date.
+  /// Number of whole days between Unix Epoch and this date, in the date's
+  /// own timezone.
+  pub fn days_since_epoch(&self) -> u32 {
+    let mi = MinInstant::from_date(self)
+      .expect("Date converted from MinInstant never overflows");
+    mi.raw / MIN_IN_DAY
+  }
+
+  /// Number of days from the most recent `wkst` till this date, ie. `0` if
+  /// this date itself falls on `wkst`.
+  pub fn days_into_week(&self, wkst: Weekday) -> u32 {
+    (Weekday::from(*self) as u32 + 7 - wkst as u32) % 7
+  }
+
+  /// Computes the week number of this date within its week-numbering year,
+  /// paired with the number of weeks in that year. As per RFC 5545, weeks
+  /// start on `wkst`, and week 1 is the first week that contains at least
+  /// four days of the calendar year.
+  pub fn week_no(&self, wkst: Weekday) -> (i64, i64) {
+    let (_, start, end) = self.week_yr_bounds(wkst);
+    let day = i64::from(self.days_since_epoch());
+    ((day - start) / 7 + 1, (end - start) / 7)
+  }
+
+  /// Week-numbering year that this date falls into, ie. 2025 for 2024/Dec/30
+  /// when weeks start on Monday.
+  pub fn week_yr(&self, wkst: Weekday) -> i64 {
+    self.week_yr_bounds(wkst).0
+  }
+
+  /// Computes the week-numbering year of this date, paired with the days
+  /// since Unix Epoch on which that year starts and on which the next one
+  /// starts.
+  fn week_yr_bounds(&self, wkst: Weekday) -> (i64, i64, i64) {
+    let day = i64::from(self.days_since_epoch());
+    let jan1 = day - i64::from(self.day_in_yr()) + 1;
+    let yr = i64::from(self.yr.raw());
+
+    let this_yr = first_week_start(jan1, wkst);
+    let next_yr = first_week_start(jan1 + days_in_ce_year(yr), wkst);
+    if day < this_yr {
+      let prev_jan1 = jan1 - days_in_ce_year(yr - 1);
+      (yr - 1, first_week_start(prev_jan1, wkst), this_yr)
+    } else if day >= next_yr {
+      let next_next_jan1 = jan1 + days_in_ce_year(yr) + days_in_ce_year(yr + 1);
+      (yr + 1, next_yr, first_week_start(next_next_jan1, wkst))
+    } else {
+      (yr, this_yr, next_yr)
+    }
+  }
+
+  /// Lists all days that fall into the same recurrence period (ie. day, week,
+  /// month or year) as this date, in chronological order.
+  pub fn period_days(&self, freq: Freq, wkst: Weekday) -> Vec<Date> {
+    match freq {
+      Freq::Daily => vec![*self],
+      Freq::Weekly => {
+        let raw = MinInstant::from_date(self)
+          .expect("Date converted from MinInstant never overflows")
+          .raw;
+        let days_into_week = self.days_into_week(wkst);
+
+        // days before Unix Epoch are omitted.
+        (0..7)
+          .filter_map(|i| {
+            let offset = if i < days_into_week {
+              raw.checked_sub((days_into_week - i) * MIN_IN_DAY)?
+            } else {
+              raw.checked_add((i - days_into_week) * MIN_IN_DAY)?
+            };
+            Some(MinInstant { raw: offset, offset: self.tz })
+          })
+          .map(Date::from_min_instant)
+          .collect()
+      }
+      Freq::Monthly => (1..=self.mon.num_days(&self.yr))
+        .map(|day| Date { day, ..*self })
+        .collect(),
+      Freq::Yearly => {
+        let mut ret = Vec::<Date>::new();
+        let mut mon_opt = Some(Month::Jan);
+        while let Some(mon) = mon_opt {
+          for day in 1..=mon.num_days(&self.yr) {
+            ret.push(Date { mon, day, ..*self });
+          }
+          mon_opt = mon.next();
+        }
+        ret
+      }
+    }
+  }
+
+  /// Index of the recurrence period (ie. day, week, month or year) that this
+  /// date falls into, counting from Unix Epoch. Weeks start on `wkst`.
+  pub fn period_index(&self, freq: Freq, wkst: Weekday) -> i64 {
+    match freq {
+      Freq::Daily => i64::from(self.days_since_epoch()),
+      Freq::Weekly => {
+        // Unix Epoch is a Thursday, ie. 3 days after Monday; the extra week
+        // keeps the dividend positive.
+        let days_since_wkst =
+          i64::from(self.days_since_epoch()) + 3 - wkst as i64 + 7;
+        days_since_wkst / 7
+      }
+      Freq::Monthly => i64::from(self.yr.raw()) * 12 + self.mon as i64,
+      Freq::Yearly => i64::from(self.yr.raw()),
+    }
+  }
+
+  /// Given a default timezone, parses a string as a date. The offset of such
+  /// a date is the one in effect at its local time.
   pub fn parse_from_str(
     args: &[&str],
-    default_tz: ZoneOffset,
+    default_tz: &Tz,
   ) -> Result<Self, TimeError> {
     let bad = Err(TimeError::DateParsingErr(format!("{:?}", args)));
 
@@ -154,16 +272,25 @@ impl Date {
       return bad;
     } // too many items
 
-    let tz = match args.get(2) {
+    let tz: Tz = match args.get(2) {
       Some(s) => s.parse()?,
-      None => default_tz,
+      None => default_tz.clone(),
     };
 
     match args[..2] {
       [ymd_str, time] => {
         let (yr, mon, day) = parse_ymd(ymd_str, default_tz)?;
         let (hr, min) = parse_hr_min(time)?;
-        Ok(Date { yr, mon, day, hr, min, tz })
+        let local = Date {
+          yr,
+          mon,
+          day,
+          hr,
+          min,
+          tz: ZoneOffset::utc(),
+        };
+        let tz = tz.offset_at_local(MinInstant::from_date(&local)?.raw);
+        Ok(Date { tz, ..local })
       }
       _ => bad,
     }
@@ -182,6 +309,27 @@ impl Date {
   }
 }
 
+/// Number of days in some CE year, which may precede Unix Epoch.
+fn days_in_ce_year(yr: i64) -> i64 {
+  if (yr % 400 == 0) || (yr % 4 == 0 && yr % 100 != 0) {
+    366
+  } else {
+    365
+  }
+}
+
+/// Given the day number (since Unix Epoch) of some January 1st, computes the
+/// day number at which week 1 of that year starts.
+fn first_week_start(jan1: i64, wkst: Weekday) -> i64 {
+  // Unix Epoch is a Thursday, ie. 3 days after Monday.
+  let days_into_week = (jan1 + 3 - wkst as i64).rem_euclid(7);
+  if days_into_week <= 3 {
+    jan1 - days_into_week
+  } else {
+    jan1 + 7 - days_into_week
+  }
+}
+
 impl std::fmt::Display for Date {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     write!(
@@ -202,12 +350,75 @@ impl std::fmt::Display for Date {
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum DatePropertyElt {
   Wd(Weekday),
+  /// Day in month, where negative numbers count backwards from month end,
+  /// ie. `-1` is the last day of month.
+  MonthDay(i64),
+  Mon(Month),
+  /// Day in year, where negative numbers count backwards from year end.
+  YearDay(i64),
+  /// Week number in year with weeks starting on the given weekday, where
+  /// negative numbers count backwards from year end.
+  WeekNo(i64, Weekday),
+  /// The nth occurrence of some weekday within each month or year (as per
+  /// `Freq::Monthly` or `Freq::Yearly`), where negative numbers count
+  /// backwards from the end; ie. `-1FR` is the last Friday.
+  NthWd(i64, Weekday, Freq),
+}
+
+/// Checks whether the 1-indexed `pos` out of `len` items matches `n`, where
+/// negative `n` counts backwards from the last item.
+fn nth_matches(n: i64, pos: i64, len: i64) -> bool {
+  if n > 0 {
+    pos == n
+  } else {
+    pos == len + 1 + n
+  }
 }
 
 impl DatePropertyElt {
   pub fn chk(&self, d: Date) -> bool {
     match self {
       Self::Wd(wd) => wd == &Weekday::from(d),
+      Self::MonthDay(n) => {
+        let days_in_mon = i64::from(d.mon.num_days(&d.yr));
+        nth_matches(*n, i64::from(d.day), days_in_mon)
+      }
+      Self::Mon(m) => m == &d.mon,
+      Self::YearDay(n) => {
+        let days_in_yr = i64::from(d.yr.days_in_year());
+        nth_matches(*n, i64::from(d.day_in_yr()), days_in_yr)
+      }
+      Self::WeekNo(n, wkst) => {
+        let (week_no, num_weeks) = d.week_no(*wkst);
+        nth_matches(*n, week_no, num_weeks)
+      }
+      Self::NthWd(n, wd, scope) => {
+        if wd != &Weekday::from(d) {
+          return false;
+        }
+        let (day, num_days) = match scope {
+          Freq::Yearly => (d.day_in_yr(), d.yr.days_in_year()),
+          _ => (d.day, d.mon.num_days(&d.yr)),
+        };
+        let pos = (day - 1) / 7 + 1;
+        let num_wd = pos + (num_days - day) / 7;
+        nth_matches(*n, i64::from(pos), i64::from(num_wd))
+      }
+    }
+  }
+}
+
+impl DatePropertyElt {
+  /// Converts this element into the tag and item of some `RRULE` rule part,
+  /// ie. `BYDAY` and `-1FR`.
+  pub fn rrule_item(&self) -> (Token, String) {
+    match self {
+      Self::Wd(wd) => (Token::BYDAY, format!("{:?}", wd)),
+      Self::NthWd(n, wd, _) => (Token::BYDAY, format!("{}{:?}", n, wd)),
+      Self::MonthDay(n) => (Token::BYMONTHDAY, n.to_string()),
+      Self::Mon(m) => (Token::BYMONTH, (*m as u32 + 1).to_string()),
+      Self::YearDay(n) => (Token::BYYEARDAY, n.to_string()),
+      Self::WeekNo(n, _) => (Token::BYWEEKNO, n.to_string()),
     }
   }
 }
@@ -216,6 +427,13 @@ impl std::fmt::Display for DatePropertyElt {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       Self::Wd(wd) => write!(f, "{:?}", wd),
+      Self::MonthDay(n) => write!(f, "day {} of month", n),
+      Self::Mon(m) => write!(f, "{:?}", m),
+      Self::YearDay(n) => write!(f, "day {} of year", n),
+      Self::WeekNo(n, _) => write!(f, "week {} of year", n),
+      Self::NthWd(n, wd, scope) => {
+        write!(f, "{:?} #{} of {}", wd, n, scope.unit())
+      }
     }
   }
 }
@@ -226,6 +444,11 @@ pub enum DateProperty {
   Atomic(DatePropertyElt),
   Or(Vec<DateProperty>),
   And(Vec<DateProperty>),
+
+  /// Among the days of each period that satisfy the inner property, only
+  /// keeps those at the given positions, ie. `BYSETPOS`. Weeks start on the
+  /// given weekday.
+  SetPos(Freq, Weekday, Vec<i64>, Box<DateProperty>),
 }
 
 impl DateProperty {
@@ -243,6 +466,28 @@ impl DateProperty {
         }
         !shortcut_value
       }
+      SetPos(freq, wkst, positions, inner) => {
+        if !inner.check(d) {
+          return false;
+        }
+
+        let day = d.days_since_epoch();
+        let matched_days: Vec<u32> = d
+          .period_days(*freq, *wkst)
+          .into_iter()
+          .filter(|pd| inner.check(*pd))
+          .map(|pd| pd.days_since_epoch())
+          .collect();
+        let len = matched_days.len() as i64;
+        let pos = 1
+          + matched_days
+            .iter()
+            .position(|md| *md == day)
+            .expect("Day that satisfies inner property is in its own period")
+            as i64;
+
+        positions.iter().any(|n| nth_matches(*n, pos, len))
+      }
     }
   }
 
@@ -270,48 +515,258 @@ impl std::fmt::Display for DateProperty {
         }
         write!(f, "{})", v.last().expect("v is not empty"))
       }
+      SetPos(freq, _, positions, inner) => {
+        write!(f, "{} at {:?} of each {}", inner, positions, freq.unit())
+      }
     }
   }
 }
 
-impl From<Vec<RRuleToks>> for DateProperty {
-  /// [todo] consider restriction constraints as per RFC 5545.
-  fn from(value: Vec<RRuleToks>) -> Self {
-    let mut dp = DateProperty::Always;
-    let mut dp_is_always = true;
+/// Parses some `BYDAY` item, which is a weekday with an optional non-zero
+/// ordinal prefix, ie. `MO`, `+2TU` or `-1FR`.
+pub fn parse_byday(s: &str) -> Result<(Option<i64>, Weekday), ICSProcessError> {
+  let bad = || ICSProcessError::InvalidRRule(Token::BYDAY, s.to_string());
+  let split_at = s.len().checked_sub(2).ok_or_else(bad)?;
+  if !s.is_char_boundary(split_at) {
+    return Err(bad());
+  }
+  let (ordinal, wd) = s.split_at(split_at);
+  let wd: Weekday = wd.parse().map_err(|_| bad())?;
+
+  if ordinal.is_empty() {
+    return Ok((None, wd));
+  }
+  match ordinal.parse::<i64>() {
+    Ok(n) if n != 0 && n.abs() <= 53 => Ok((Some(n), wd)),
+    _ => Err(bad()),
+  }
+}
+
+/// Parses every item of some rule part as a non-zero integer in
+/// `-max..=max`.
+pub fn rrule_nums(
+  rrt: &RRuleToks,
+  max: i64,
+) -> Result<Vec<i64>, ICSProcessError> {
+  let parse_one = |s: &String| match s.parse::<i64>() {
+    Ok(n) if n != 0 && n.abs() <= max => Ok(n),
+    _ => Err(ICSProcessError::InvalidRRule(rrt.tag, s.clone())),
+  };
+  rrt.content.iter().map(parse_one).collect()
+}
+
+impl DateProperty {
+  /// Converts the rule parts of some `RRULE` with frequency `freq` into a
+  /// `DateProperty`. Rule parts that are omitted but implied by the frequency
+  /// are taken from `dtstart`, as per RFC 5545; ie. `FREQ=MONTHLY` alone
+  /// occurs on the same day of month as `dtstart`.
+  ///
+  /// Since every rule part narrows down the days in some period, rule parts
+  /// are conjoined, which agrees with both the "expand" and "limit" behaviors
+  /// in RFC 5545 at the granularity of days. Weeks start on `wkst`.
+  pub fn from_rrules(
+    freq: Freq,
+    value: Vec<RRuleToks>,
+    dtstart: Date,
+    wkst: Weekday,
+  ) -> Result<Self, ICSProcessError> {
+    use DatePropertyElt::*;
+    use Freq::*;
+
+    let mut parts = Vec::<DateProperty>::new();
+    let mut tags = Vec::<Token>::new();
+    let mut set_pos: Option<Vec<i64>> = None;
+
+    // ordinal weekdays count within months if `BYMONTH` is present, and are
+    // not allowed alongside `BYWEEKNO`.
+    let has_bymonth = value.iter().any(|rrt| rrt.tag == Token::BYMONTH);
+    let has_byweekno = value.iter().any(|rrt| rrt.tag == Token::BYWEEKNO);
 
     for rrt in value {
-      match rrt.tag {
+      // checks applicability of rule part against the RFC 5545 table.
+      let applicable_freqs: &[Freq] = match rrt.tag {
+        Token::BYMONTHDAY => &[Daily, Monthly, Yearly],
+        Token::BYYEARDAY | Token::BYWEEKNO => &[Yearly],
+        _ => &[Daily, Weekly, Monthly, Yearly],
+      };
+      if !applicable_freqs.contains(&freq) {
+        return Err(ICSProcessError::RRuleFreqMismatch(rrt.tag, freq));
+      }
+
+      tags.push(rrt.tag);
+      let elts: Vec<DatePropertyElt> = match rrt.tag {
         Token::BYDAY => {
-          let v: Vec<Weekday> =
-            rrt.content.iter().map(|s| Weekday::from(s.as_str())).collect();
-          dp = if dp_is_always {
-            dp_is_always = false;
-            DateProperty::or_vec(v)
-          } else {
-            todo!("Did not yet impl anything other than BYDAY")
-          };
+          let mut v = Vec::<DatePropertyElt>::new();
+          for s in &rrt.content {
+            match (parse_byday(s)?, freq) {
+              ((None, wd), _) => v.push(Wd(wd)),
+              ((Some(n), wd), Monthly) => v.push(NthWd(n, wd, Monthly)),
+              ((Some(n), wd), Yearly) if !has_byweekno => {
+                let scope = if has_bymonth { Monthly } else { Yearly };
+                v.push(NthWd(n, wd, scope))
+              }
+              _ => {
+                return Err(ICSProcessError::InvalidRRule(rrt.tag, s.clone()))
+              }
+            }
+          }
+          v
+        }
+        Token::BYMONTHDAY => {
+          rrule_nums(&rrt, 31)?.into_iter().map(MonthDay).collect()
         }
-        Token::BYHOUR
-        | Token::BYMIN
-        | Token::BYMONTH
-        | Token::BYMONTHDAY
-        | Token::BYSETPOS
-        | Token::BYWEEKNO
-        | Token::BYYEARDAY => {
-          unimplemented!()
+        Token::BYYEARDAY => {
+          rrule_nums(&rrt, 366)?.into_iter().map(YearDay).collect()
         }
-        Token::WKST => {
-          if HANDLE_WKST {
-            todo!("Needs to handle WKST tag")
+        Token::BYWEEKNO => {
+          rrule_nums(&rrt, 53)?.into_iter().map(|n| WeekNo(n, wkst)).collect()
+        }
+        Token::BYMONTH => {
+          let mut v = Vec::<DatePropertyElt>::new();
+          for (n, s) in rrule_nums(&rrt, 12)?.into_iter().zip(&rrt.content) {
+            match u32::try_from(n - 1).map(Month::try_from) {
+              Ok(Ok(m)) => v.push(Mon(m)),
+              _ => {
+                return Err(ICSProcessError::InvalidRRule(rrt.tag, s.clone()))
+              }
+            }
           }
+          v
+        }
+        Token::BYSETPOS => {
+          set_pos = Some(rrule_nums(&rrt, 366)?);
+          continue;
         }
-        t => {
-          unreachable!("Encountered unexpected rrule tag: {}", t)
+        t => return Err(ICSProcessError::UnsupportedRRule(t)),
+      };
+      parts
+        .push(DateProperty::Or(elts.into_iter().map(Self::Atomic).collect()));
+    }
+
+    // fills in the day-level rule parts implied by `dtstart`.
+    let has_day_rule =
+      [Token::BYWEEKNO, Token::BYYEARDAY, Token::BYMONTHDAY, Token::BYDAY]
+        .iter()
+        .any(|t| tags.contains(t));
+    if !has_day_rule {
+      let month_day = Self::Atomic(MonthDay(dtstart.day.into()));
+      match freq {
+        Daily => {}
+        Weekly => parts.push(Self::or_vec(vec![Weekday::from(dtstart)])),
+        Monthly => parts.push(month_day),
+        Yearly => {
+          if !tags.contains(&Token::BYMONTH) {
+            parts.push(Self::Atomic(Mon(dtstart.mon)));
+          }
+          parts.push(month_day);
         }
       }
     }
-    dp
+
+    let dp = match parts.len() {
+      0 => DateProperty::Always,
+      1 => parts.remove(0),
+      _ => DateProperty::And(parts),
+    };
+    if !dp.occurs_in_some_year(dtstart.tz) {
+      return Err(ICSProcessError::ImpossibleRRule(dp.to_string()));
+    }
+
+    match set_pos {
+      Some(_) if tags.len() == 1 => {
+        Err(ICSProcessError::UnsupportedRRule(Token::BYSETPOS))
+      }
+      Some(positions) => {
+        Ok(DateProperty::SetPos(freq, wkst, positions, Box::new(dp)))
+      }
+      None => Ok(dp),
+    }
+  }
+}
+
+impl DateProperty {
+  /// Checks whether this property constrains week numbers, ie. `BYWEEKNO`,
+  /// under which yearly periods are week-numbering years.
+  pub fn has_week_no(&self) -> bool {
+    use DateProperty::*;
+    match self {
+      Always => false,
+      Atomic(dpe) => matches!(dpe, DatePropertyElt::WeekNo(..)),
+      Or(v) | And(v) => v.iter().any(DateProperty::has_week_no),
+      SetPos(_, _, _, inner) => inner.has_week_no(),
+    }
+  }
+
+  /// Checks whether any day satisfies this property, ie. not `BYMONTH=2;
+  /// BYMONTHDAY=30`. Since the calendar repeats every 28 years, save for
+  /// century years, the years 2000 to 2027 cover every kind of year, namely
+  /// every weekday that years start on, with or without leap days.
+  fn occurs_in_some_year(&self, tz: ZoneOffset) -> bool {
+    (2000..2028).any(|yr| {
+      let Ok(yr) = CeYear::new(yr) else {
+        return false;
+      };
+      let jan1 = Date {
+        yr,
+        mon: Month::Jan,
+        day: 1,
+        hr: 0,
+        min: 0,
+        tz,
+      };
+      jan1
+        .period_days(Freq::Yearly, Weekday::MO)
+        .into_iter()
+        .any(|d| self.check(d))
+    })
+  }
+}
+
+impl DateProperty {
+  /// Converts some `DateProperty` back into the rule parts of some `RRULE`,
+  /// which is the inverse of `from_rrules()`, except that rule parts implied
+  /// by `dtstart` are spelled out. Returns `None` if the property cannot be
+  /// expressed as rule parts, ie. some disjunction of different rule parts.
+  pub fn to_rrules(&self) -> Option<Vec<RRuleToks>> {
+    use DateProperty::*;
+    match self {
+      Always => Some(vec![]),
+      Atomic(dpe) => {
+        let (tag, item) = dpe.rrule_item();
+        Some(vec![RRuleToks { tag, content: vec![item] }])
+      }
+      Or(v) => {
+        let mut tag: Option<Token> = None;
+        let mut content = Vec::<String>::new();
+        for dp in v {
+          let Atomic(dpe) = dp else { return None };
+          let (item_tag, item) = dpe.rrule_item();
+          if tag.get_or_insert(item_tag) != &item_tag {
+            return None;
+          }
+          content.push(item);
+        }
+        Some(vec![RRuleToks { tag: tag?, content }])
+      }
+      And(v) => {
+        let mut ret = Vec::<RRuleToks>::new();
+        for rrt in v.iter().map(Self::to_rrules).collect::<Option<Vec<_>>>()? {
+          for part in rrt {
+            if ret.iter().any(|r| r.tag == part.tag) {
+              return None;
+            }
+            ret.push(part);
+          }
+        }
+        Some(ret)
+      }
+      SetPos(_, _, positions, inner) => {
+        let mut ret = inner.to_rrules()?;
+        let content = positions.iter().map(i64::to_string).collect();
+        ret.push(RRuleToks { tag: Token::BYSETPOS, content });
+        Some(ret)
+      }
+    }
   }
 }
 
@@ -369,4 +824,22 @@ mod test {
 
     assert_eq!(treeday.day_in_yr(), 31 + 28 + 12);
   }
+
+  #[test]
+  fn week_numbers() {
+    let day = |y: u16, mon: Month, day: u32| Date {
+      yr: CeYear::new(y).unwrap(),
+      mon,
+      day,
+      hr: 0,
+      min: 0,
+      tz: ZoneOffset::utc(),
+    };
+
+    // 2021/Jan/1 is a Friday, hence in the last week of 2020.
+    assert_eq!((53, 53), day(2021, Month::Jan, 1).week_no(Weekday::MO));
+    assert_eq!((1, 52), day(2021, Month::Jan, 4).week_no(Weekday::MO));
+    assert_eq!((1, 52), day(2024, Month::Dec, 30).week_no(Weekday::MO));
+    assert_eq!((20, 52), day(1997, Month::May, 12).week_no(Weekday::MO));
+  }
 }
@@ -0,0 +1,201 @@
+//! Durations as in ISO 8601 and RFC 5545, ie. `P1W`, `P1DT12H`, `PT1H30M`.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+  fact::{MIN_IN_DAY, SEC_IN_MIN_U32},
+  timezone::TimeZone,
+  MinInstant, TimeError,
+};
+
+/// A non-negative duration, in terms of nominal days plus exact minutes. As
+/// per RFC 5545, days keep the wall-clock time across daylight saving time
+/// transitions, while minutes do not. Seconds are rounded up to minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MinDuration {
+  days: u32,
+  min: u32,
+}
+
+impl MinDuration {
+  /// Creates a new duration of some days plus some minutes. Returns an error
+  /// if its total number of minutes overflows.
+  pub fn new(days: u32, min: u32) -> Result<Self, TimeError> {
+    days
+      .checked_mul(MIN_IN_DAY)
+      .and_then(|n| n.checked_add(min))
+      .ok_or(TimeError::DurationOverflowErr(days, min))?;
+    Ok(MinDuration { days, min })
+  }
+
+  /// Computes the number of minutes, taking every day as 24 hours.
+  pub fn num_min(&self) -> u32 {
+    self.days * MIN_IN_DAY + self.min
+  }
+
+  /// Computes the time instant that is this long after `mi`, where the days
+  /// are counted in wall-clock time of timezone `tz`.
+  pub fn after(
+    &self,
+    mi: MinInstant,
+    tz: &impl TimeZone,
+  ) -> Result<MinInstant, TimeError> {
+    let days_later = mi.in_zone(tz).advance(self.days * MIN_IN_DAY)?;
+    days_later.localize(tz).advance(self.min)
+  }
+}
+
+/// Splits some `[n][unit][n][unit]..` expression into numbers and units. The
+/// units must appear at most once each, in the same order as in `units`.
+fn components(expr: &str, units: &str) -> Option<Vec<(u64, char)>> {
+  let mut ret = Vec::<(u64, char)>::new();
+  let mut rest = units;
+  let mut num = String::new();
+  for c in expr.chars() {
+    if c.is_ascii_digit() {
+      num.push(c);
+      continue;
+    }
+    let idx = rest.find(c)?;
+    rest = &rest[idx + 1..];
+    ret.push((num.parse().ok()?, c));
+    num.clear();
+  }
+  if num.is_empty() {
+    Some(ret)
+  } else {
+    None
+  }
+}
+
+impl FromStr for MinDuration {
+  type Err = TimeError;
+
+  /// Parses some duration like `P2W`, `P1DT12H` or `PT1H30M15S`. Durations in
+  /// years or months, as well as negative durations, are not supported.
+  fn from_str(s: &str) -> Result<Self, TimeError> {
+    let bad = || TimeError::DurationParseErr(s.to_string());
+
+    let expr = s.trim().to_ascii_uppercase();
+    let expr = expr.strip_prefix('+').unwrap_or(&expr);
+    let expr = expr.strip_prefix('P').ok_or_else(bad)?;
+    let (date, time) = match expr.split_once('T') {
+      Some((date, time)) if !time.is_empty() => (date, time),
+      Some(_) => return Err(bad()),
+      None if !expr.is_empty() => (expr, ""),
+      None => return Err(bad()),
+    };
+
+    let mut days: u64 = 0;
+    for (n, unit) in components(date, "WD").ok_or_else(bad)? {
+      let n = if unit == 'W' { n.checked_mul(7) } else { Some(n) };
+      days = n.and_then(|n| days.checked_add(n)).ok_or_else(bad)?;
+    }
+
+    let mut secs: u64 = 0;
+    for (n, unit) in components(time, "HMS").ok_or_else(bad)? {
+      let unit_secs = match unit {
+        'H' => 3600,
+        'M' => 60,
+        _ => 1,
+      };
+      secs = n
+        .checked_mul(unit_secs)
+        .and_then(|n| secs.checked_add(n))
+        .ok_or_else(bad)?;
+    }
+
+    let min = secs.div_ceil(u64::from(SEC_IN_MIN_U32));
+    match (u32::try_from(days), u32::try_from(min)) {
+      (Ok(days), Ok(min)) => MinDuration::new(days, min),
+      _ => Err(bad()),
+    }
+  }
+}
+
+impl std::fmt::Display for MinDuration {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "P")?;
+    if self.days > 0 {
+      write!(f, "{}D", self.days)?;
+    }
+    let (hr, min) = (self.min / 60, self.min % 60);
+    if hr > 0 || min > 0 || self.days == 0 {
+      write!(f, "T")?;
+    }
+    if hr > 0 {
+      write!(f, "{}H", hr)?;
+    }
+    if min > 0 || (hr == 0 && self.days == 0) {
+      write!(f, "{}M", min)?;
+    }
+    Ok(())
+  }
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::time::{date::Date, tzdata};
+
+  use super::*;
+
+  #[test]
+  fn parse_durations() {
+    let parse = |s: &str| s.parse::<MinDuration>().unwrap();
+    assert_eq!(MinDuration::new(0, 90).unwrap(), parse("PT1H30M"));
+    assert_eq!(MinDuration::new(14, 0).unwrap(), parse("P2W"));
+    assert_eq!(MinDuration::new(1, 720).unwrap(), parse("P1DT12H"));
+    assert_eq!(MinDuration::new(0, 2).unwrap(), parse("PT1M15S"));
+    assert_eq!(MinDuration::new(0, 90).unwrap(), parse("+pt90m"));
+    assert_eq!(MinDuration::new(15, 5).unwrap(), parse("P2W1DT5M"));
+  }
+
+  #[test]
+  fn invalid_durations() {
+    for s in [
+      "",
+      "P",
+      "PT",
+      "1H",
+      "PT1H30",
+      "P1Y",
+      "P1M",
+      "-PT1H",
+      "PT30M1H",
+      "PTH",
+      "PT1H1H",
+      "P1DT",
+      "P99999999D",
+    ] {
+      assert!(s.parse::<MinDuration>().is_err(), "{}", s);
+    }
+  }
+
+  #[test]
+  fn display() {
+    for s in ["PT0M", "PT1H30M", "P1D", "P1DT12H", "P14DT5M", "PT2H"] {
+      assert_eq!(s, s.parse::<MinDuration>().unwrap().to_string());
+    }
+  }
+
+  #[test]
+  fn after_across_dst() {
+    let ny = tzdata::lookup("America/New_York").unwrap();
+    let utc_string =
+      |mi: MinInstant| Date::from_min_instant(mi.normalize()).no_tz_string();
+
+    // 2023/Mar/11 09:00 EST
+    let start = MinInstant::from_raw_utc(27_974_880 + 14 * 60).unwrap();
+    let day: MinDuration = "P1D".parse().unwrap();
+    let hours: MinDuration = "PT24H".parse().unwrap();
+    assert_eq!("2023/Mar/12 13:00", utc_string(day.after(start, &ny).unwrap()));
+    assert_eq!(
+      "2023/Mar/12 14:00",
+      utc_string(hours.after(start, &ny).unwrap())
+    );
+  }
+}
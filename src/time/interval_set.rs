@@ -0,0 +1,187 @@
+//! Sets of minutes, made of disjoint `MinInterval`s.
+
+use std::cmp::max;
+
+use super::MinInterval;
+
+/// Some set of minutes, stored as sorted, disjoint and non-empty
+/// `MinInterval`s in utc. Intervals that touch are merged, so that the measure
+/// of the set is simply the sum of their lengths.
+#[derive(Clone, Default)]
+pub struct IntervalSet {
+  mivs: Vec<MinInterval>,
+}
+
+impl IntervalSet {
+  /// Computes the number of minutes in the set.
+  pub fn num_min(&self) -> u32 {
+    self.mivs.iter().map(MinInterval::num_min).sum()
+  }
+
+  /// Computes the minutes in either set.
+  pub fn union(&self, rhs: &IntervalSet) -> IntervalSet {
+    self.mivs.iter().chain(rhs.mivs.iter()).copied().collect()
+  }
+
+  /// Computes the minutes in both sets.
+  pub fn intersection(&self, rhs: &IntervalSet) -> IntervalSet {
+    let mut mivs = Vec::<MinInterval>::new();
+    let (mut i, mut j) = (0, 0);
+    while let (Some(lhs), Some(rhs)) = (self.mivs.get(i), rhs.mivs.get(j)) {
+      if lhs.overlap_duration(*rhs) > 0 {
+        mivs.push(*lhs * *rhs);
+      }
+
+      // the interval that ends first cannot meet any later interval.
+      if lhs.end <= rhs.end {
+        i += 1;
+      } else {
+        j += 1;
+      }
+    }
+    IntervalSet { mivs }
+  }
+
+  /// Computes the minutes in this set but not in `rhs`.
+  pub fn difference(&self, rhs: &IntervalSet) -> IntervalSet {
+    let mut mivs = Vec::<MinInterval>::new();
+    let mut j = 0;
+    for lhs in &self.mivs {
+      let mut rest = *lhs;
+
+      // skips the intervals of `rhs` that end before `rest`.
+      while rhs.mivs.get(j).is_some_and(|r| r.end <= rest.start) {
+        j += 1;
+      }
+      let mut k = j;
+      while let Some(cut) = rhs.mivs.get(k).filter(|r| r.start < rest.end) {
+        if rest.start < cut.start {
+          mivs.push(MinInterval::new(rest.start, cut.start));
+        }
+        rest.start = max(rest.start, cut.end);
+        k += 1;
+      }
+      if rest.num_min() > 0 {
+        mivs.push(rest);
+      }
+    }
+    IntervalSet { mivs }
+  }
+}
+
+impl FromIterator<MinInterval> for IntervalSet {
+  /// Collects the union of some intervals, where empty intervals are dropped.
+  fn from_iter<I: IntoIterator<Item = MinInterval>>(iter: I) -> Self {
+    let mut sorted: Vec<MinInterval> = iter
+      .into_iter()
+      .map(MinInterval::normalize)
+      .filter(|miv| miv.num_min() > 0)
+      .collect();
+    sorted.sort_by_key(|miv| miv.start);
+
+    let mut mivs = Vec::<MinInterval>::with_capacity(sorted.len());
+    for miv in sorted {
+      match mivs.last_mut() {
+        Some(last) if miv.start <= last.end => {
+          last.end = max(last.end, miv.end)
+        }
+        _ => mivs.push(miv),
+      }
+    }
+    IntervalSet { mivs }
+  }
+}
+
+impl From<MinInterval> for IntervalSet {
+  fn from(miv: MinInterval) -> Self {
+    [miv].into_iter().collect()
+  }
+}
+
+impl std::fmt::Display for IntervalSet {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{{")?;
+    for (i, miv) in self.mivs.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{}", miv)?;
+    }
+    write!(f, "}}")
+  }
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[cfg(test)]
+#[allow(dead_code, unused_imports)]
+mod test {
+  use crate::test_util::OCT_9_2023;
+  use crate::time::{timezone::ZoneOffset, MinInstant};
+
+  use super::*;
+
+  /// Some utc minute that the raw minutes in tests are relative to.
+  const BASE: u32 = OCT_9_2023;
+
+  /// Constructs some set from `(start, end)` pairs of minutes after `BASE`.
+  fn set(pairs: &[(u32, u32)]) -> IntervalSet {
+    pairs
+      .iter()
+      .map(|&(start, end)| {
+        MinInterval::new(
+          MinInstant::from_raw_utc(BASE + start).unwrap(),
+          MinInstant::from_raw_utc(BASE + end).unwrap(),
+        )
+      })
+      .collect()
+  }
+
+  /// Lists the intervals of some set as `(start, end)` pairs of minutes after
+  /// `BASE`.
+  fn pairs(set: &IntervalSet) -> Vec<(u32, u32)> {
+    set
+      .mivs
+      .iter()
+      .map(|miv| (miv.start.raw - BASE, miv.end.raw - BASE))
+      .collect()
+  }
+
+  #[test]
+  fn union_merges_overlaps() {
+    let a = set(&[(100, 200), (150, 250), (300, 300), (400, 500)]);
+    assert_eq!(vec![(100, 250), (400, 500)], pairs(&a));
+    assert_eq!(250, a.num_min());
+
+    let b = set(&[(250, 260), (450, 600), (700, 710)]);
+    assert_eq!(vec![(100, 260), (400, 600), (700, 710)], pairs(&a.union(&b)));
+    assert!(pairs(&set(&[(10, 10)])).is_empty());
+  }
+
+  #[test]
+  fn intersection_and_difference() {
+    let a = set(&[(100, 200), (300, 400)]);
+    let b = set(&[(50, 120), (150, 350), (390, 500)]);
+    assert_eq!(
+      vec![(100, 120), (150, 200), (300, 350), (390, 400)],
+      pairs(&a.intersection(&b))
+    );
+    assert_eq!(vec![(120, 150), (350, 390)], pairs(&a.difference(&b)));
+    assert_eq!(
+      vec![(50, 100), (200, 300), (400, 500)],
+      pairs(&b.difference(&a))
+    );
+    assert_eq!(0, a.difference(&a).num_min());
+    assert_eq!(a.num_min(), a.intersection(&a).num_min());
+  }
+
+  #[test]
+  fn zoned_intervals() {
+    let start = MinInstant::from_raw_utc(BASE + 100)
+      .unwrap()
+      .in_zone(&ZoneOffset::new(-240).unwrap());
+    let miv = MinInterval::new(start, start.advance(60).unwrap());
+    let zoned = set(&[(130, 200)]).union(&miv.into());
+    assert_eq!(vec![(100, 200)], pairs(&zoned));
+  }
+}
@@ -16,6 +16,14 @@ pub mod fact;
 
 pub mod timezone;
 
+pub mod duration;
+
+pub mod interval_set;
+
+pub mod zone_rules;
+
+pub mod tzdata;
+
 pub mod time_parser;
 
 use crate::{
@@ -24,7 +32,11 @@ use crate::{
   util_typs::{percent::PercentError, RefinementError},
 };
 
-use self::{fact::*, timezone::ZoneOffset, year::CeYear};
+use self::{
+  fact::*,
+  timezone::{TimeZone, Tz, ZoneOffset},
+  year::CeYear,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -45,9 +57,12 @@ pub enum TimeError {
   NafErr(String), // not a float
   MonthBoundErr(u32),
   MonthParseErr(String),
+  WeekdayParseErr(String),
   NumOutOfBoundsErr(u32),
   TimeParseErr(String),
   TimeZoneParseErr(String),
+  DurationParseErr(String),
+  DurationOverflowErr(u32, u32),
   DateParsingErr(String),
   UnixYearConstructorOverflow(u16),
   CeYearConstructorUnderflow(u16),
@@ -168,8 +183,9 @@ pub struct MinInterval {
 // TODO still contains magic number
 // TODO improve human interaction
 impl MinInstant {
-  /// Constructs a MinInstant using current system time. Sets to given timezone.
-  pub fn now(tz: ZoneOffset) -> Self {
+  /// Constructs a MinInstant using current system time. Sets to the offset
+  /// of given timezone at such a time.
+  pub fn now(tz: &impl TimeZone) -> Self {
     let t: i64 = Instant::now().seconds() / SEC_IN_MIN;
 
     if t > MINUTE_UPPERBOUND {
@@ -179,10 +195,8 @@ impl MinInstant {
       panic!("datetime seconds negative")
     };
 
-    let mut ret = Self { raw: t as u32, offset: ZoneOffset::utc() };
-
-    ret.adjust_to_zone(tz);
-    ret
+    let ret = Self { raw: t as u32, offset: ZoneOffset::utc() };
+    ret.in_zone(tz)
   }
 
   pub fn from_raw_utc(raw: u32) -> Result<Self, TimeError> {
@@ -222,6 +236,23 @@ impl MinInstant {
     ret
   }
 
+  /// Adjusts to the offset of some timezone that is in effect at this time
+  /// instant, without shifting the represented time instance.
+  pub fn in_zone(self, tz: &impl TimeZone) -> MinInstant {
+    let mut ret = self;
+    ret.adjust_to_zone(tz.offset_at(self));
+    ret
+  }
+
+  /// Reinterprets the wall-clock time of this `MinInstant` in some timezone,
+  /// which may shift the represented time instance.
+  pub fn localize(self, tz: &impl TimeZone) -> MinInstant {
+    MinInstant {
+      raw: self.raw,
+      offset: tz.offset_at_local(self.raw),
+    }
+  }
+
   /// Decomposes the `MinInstant` into whole year plus number of minutes.
   pub fn decomp_yr_min(&self) -> (UnixYear, u32) {
     let mut curr_yr = UnixYear::new(0).expect("year 1970 is valid");
@@ -266,7 +297,7 @@ impl MinInstant {
   /// Given a default timezone, parses a string as some `MinInstant`.
   pub fn parse_from_str(
     args: &[&str],
-    default_tz: ZoneOffset,
+    default_tz: &Tz,
   ) -> Result<Self, TimeError> {
     MinInstant::from_date(&Date::parse_from_str(args, default_tz)?)
   }
@@ -292,9 +323,8 @@ impl MinInstant {
   }
 
   /// Prints as the date at given timezone
-  pub fn as_tz_date_string(self, tz: ZoneOffset) -> String {
-    let mut mi = self;
-    mi.adjust_to_zone(tz);
+  pub fn as_tz_date_string(self, tz: &impl TimeZone) -> String {
+    let mi = self.in_zone(tz);
     format!("{}", Date::from_min_instant(mi).no_tz_string())
   }
 }
@@ -308,7 +338,7 @@ impl MinInterval {
 
   /// Creates a `MinInterval` from now till the given `MinInstant`.
   pub fn from_now_till(end: MinInstant) -> MinInterval {
-    MinInterval { start: MinInstant::now(end.offset), end }
+    MinInterval { start: MinInstant::now(&end.offset), end }
   }
 
   /// Normalizes to utc timezone.
@@ -319,6 +349,16 @@ impl MinInterval {
     }
   }
 
+  /// Reinterprets the wall-clock starting time in some timezone, while keeping
+  /// the exact duration in minutes.
+  pub fn localize(self, tz: &impl TimeZone) -> Result<MinInterval, TimeError> {
+    let start = self.start.localize(tz);
+    Ok(MinInterval {
+      start,
+      end: start.advance(self.num_min())?,
+    })
+  }
+
   /// Computes the duration of overlap of two `MinInterval` in minutes.
   pub fn overlap_duration(&self, rhs: MinInterval) -> u32 {
     let (lhs, rhs) = (self.normalize(), rhs.normalize());
@@ -341,41 +381,39 @@ impl MinInterval {
     })
   }
 
-  /// Advances the `MinInterval` by given number of minutes. Checks bounds while
-  /// advancing. Panics if encounters overflow.
-  pub fn advance_unwrap(&self, num_min: u32) -> MinInterval {
-    self.advance(num_min).unwrap()
-  }
-
-  /// Advances the `MinInterval` until its starting time matches the
-  /// provided `DateProperty`, or if `start` exceeds the `until` mininstant.
-  pub fn advance_until(
+  /// Advances the `MinInterval` day by day, keeping the wall-clock time of
+  /// `start` in timezone `tz`, until the `Date` of its starting time
+  /// satisfies `pred`, or if `start` exceeds the `until` mininstant. Also
+  /// returns `None` if no such day is found within `max_days` days, or before
+  /// `MinInstant` overflows.
+  pub fn advance_until<F>(
     &self,
-    dp: &DateProperty,
+    pred: F,
     until_opt: Option<MinInstant>,
-  ) -> Option<MinInterval> {
+    max_days: u32,
+    tz: &impl TimeZone,
+  ) -> Option<MinInterval>
+  where
+    F: Fn(Date) -> bool,
+  {
     let mut new_miv = self.clone();
-    match until_opt {
-      Some(until) => {
-        while !dp.check(Date::from_min_instant(new_miv.start)) {
-          new_miv = new_miv.advance_unwrap(MIN_IN_DAY);
-          if new_miv.start > until {
-            return None;
-          }
-        }
-
-        // catch the case where while loops was not entered
-        if new_miv.start > until {
-          return None;
-        }
+    let mut days: u32 = 0;
+    while !pred(Date::from_min_instant(new_miv.start)) {
+      days += 1;
+      if days > max_days {
+        return None;
       }
-      None => {
-        while !dp.check(Date::from_min_instant(new_miv.start)) {
-          new_miv = new_miv.advance_unwrap(MIN_IN_DAY);
-        }
+      new_miv = new_miv.advance(MIN_IN_DAY).ok()?;
+      if until_opt.is_some_and(|until| new_miv.start.localize(tz) > until) {
+        return None;
       }
     }
-    Some(new_miv)
+
+    // catch the case where while loops was not entered
+    if until_opt.is_some_and(|until| new_miv.start.localize(tz) > until) {
+      return None;
+    }
+    new_miv.localize(tz).ok()
   }
 
   /// Computes the number of minutes in the `MinInterval` instance. Returns `0`
@@ -455,7 +493,7 @@ mod test {
   fn mininstant_order() {
     let mi = MinInstant { raw: 27905591, offset: ZoneOffset::utc() };
 
-    let mi_now = MinInstant::now(ZoneOffset::utc());
+    let mi_now = MinInstant::now(&ZoneOffset::utc());
 
     assert!(mi < mi_now);
   }
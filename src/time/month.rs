@@ -4,11 +4,13 @@ use super::time_parser::parse_u32_bound;
 use super::year::{Year, YearLength};
 use super::{fact::*, TimeError};
 
+use serde::{Deserialize, Serialize};
+
 use Month::*;
 const MONTH_LIST: [Month; 12] =
   [Jan, Feb, Mar, Apr, May, Jun, Jul, Aug, Sep, Oct, Nov, Dec];
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Month {
   Jan = 0,
   Feb,
@@ -36,8 +38,7 @@ impl Month {
 
   pub fn num_days(&self, y: &dyn Year) -> u32 {
     use Month::*;
-    if *self == Jan {
-      // feb
+    if *self == Feb {
       match y.get_year_length() {
         YearLength::Leap => 29,
         _ => 28,
@@ -123,4 +124,15 @@ mod test {
     let jan = feb.prev().unwrap();
     assert!(jan.prev().is_none());
   }
+
+  #[test]
+  fn num_days() {
+    use crate::time::year::CeYear;
+    let (leap, common) =
+      (CeYear::new(2024).unwrap(), CeYear::new(2023).unwrap());
+    assert_eq!(31, Month::Jan.num_days(&common));
+    assert_eq!(28, Month::Feb.num_days(&common));
+    assert_eq!(29, Month::Feb.num_days(&leap));
+    assert_eq!(30, Month::Apr.num_days(&leap));
+  }
 }
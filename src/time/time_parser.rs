@@ -5,7 +5,7 @@ use super::{
   month::Month,
   year::{CeYear, Year},
 };
-use super::{timezone::ZoneOffset, MinInstant, TimeError};
+use super::{timezone::TimeZone, MinInstant, TimeError};
 
 // Attempts to parse some expression as u16.
 fn parse_u16(expr: &str) -> Result<u16, TimeError> {
@@ -44,7 +44,7 @@ pub fn parse_u32_bound(expr: &str, lb: u32, ub: u32) -> Result<u32, TimeError> {
 /// Parses some str as year, month, and day.
 pub fn parse_ymd(
   expr: &str,
-  tz: ZoneOffset,
+  tz: &impl TimeZone,
 ) -> Result<(CeYear, Month, u32), TimeError> {
   let args: Vec<&str> = expr.split("/").map(|s| s.trim()).collect();
   match args[..] {
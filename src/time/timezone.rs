@@ -1,8 +1,11 @@
 use std::str::FromStr;
 
-use super::{fact::MIN_IN_HR, time_parser::parse_hr_min, TimeError};
+use super::{
+  date::Date, fact::MIN_IN_HR, time_parser::parse_hr_min, tzdata,
+  zone_rules::ZoneRules, MinInstant, TimeError,
+};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub const UTC_LB: i64 = -720;
 pub const UTC_UB: i64 = 840;
@@ -62,6 +65,127 @@ impl FromStr for ZoneOffset {
   }
 }
 
+/// Something that decides the offset of local time at any time instant.
+pub trait TimeZone {
+  /// Computes the offset in effect at some time instant.
+  fn offset_at(&self, mi: MinInstant) -> ZoneOffset;
+
+  /// Computes the offset of some local wall-clock time, in raw minutes. As
+  /// per RFC 5545, a time skipped by some transition is interpreted with the
+  /// offset before the transition, and a time that occurs twice is
+  /// interpreted as its first occurrence.
+  fn offset_at_local(&self, local: u32) -> ZoneOffset;
+
+  /// Interprets the wall-clock time of some `Date` in this timezone,
+  /// disregarding its `tz` field.
+  fn localize(&self, date: &Date) -> Result<MinInstant, TimeError> {
+    let raw =
+      MinInstant::from_date(&Date { tz: ZoneOffset::utc(), ..*date })?.raw;
+    Ok(MinInstant { raw, offset: self.offset_at_local(raw) })
+  }
+}
+
+impl TimeZone for ZoneOffset {
+  fn offset_at(&self, _mi: MinInstant) -> ZoneOffset {
+    *self
+  }
+
+  fn offset_at_local(&self, _local: u32) -> ZoneOffset {
+    *self
+  }
+}
+
+/// A timezone, which is either some fixed offset, ie. `-4:00`, or some named
+/// timezone with daylight saving time, ie. `America/New_York`. Named
+/// timezones are either IANA timezones, which are stored as their names and
+/// looked up upon loading, or else defined by some `VTIMEZONE`, which are
+/// stored with their rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Tz {
+  Fixed(ZoneOffset),
+  Iana(#[serde(with = "tzid")] ZoneRules),
+  Named(ZoneRules),
+}
+
+impl Tz {
+  /// Gets the rules of some named timezone.
+  pub fn rules(&self) -> Option<&ZoneRules> {
+    match self {
+      Tz::Fixed(_) => None,
+      Tz::Iana(zone) | Tz::Named(zone) => Some(zone),
+    }
+  }
+}
+
+impl TimeZone for Tz {
+  fn offset_at(&self, mi: MinInstant) -> ZoneOffset {
+    match self {
+      Tz::Fixed(offset) => *offset,
+      Tz::Iana(zone) | Tz::Named(zone) => zone.offset_at(mi),
+    }
+  }
+
+  fn offset_at_local(&self, local: u32) -> ZoneOffset {
+    match self {
+      Tz::Fixed(offset) => *offset,
+      Tz::Iana(zone) | Tz::Named(zone) => zone.offset_at_local(local),
+    }
+  }
+}
+
+/// Stores IANA timezones as their names, ie. `"America/New_York"`.
+mod tzid {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(
+    zone: &ZoneRules,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(zone.name())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<ZoneRules, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    tzdata::lookup(&name).ok_or_else(|| {
+      serde::de::Error::custom(format!("unknown timezone `{}`", name))
+    })
+  }
+}
+
+impl From<ZoneOffset> for Tz {
+  fn from(value: ZoneOffset) -> Self {
+    Tz::Fixed(value)
+  }
+}
+
+impl FromStr for Tz {
+  type Err = TimeError;
+
+  /// Parses either some offset like `-4:00`, or some IANA timezone name like
+  /// `America/New_York`.
+  fn from_str(s: &str) -> Result<Self, TimeError> {
+    if let Ok(offset) = s.parse() {
+      return Ok(Tz::Fixed(offset));
+    }
+    match tzdata::lookup(s) {
+      Some(zone) => Ok(Tz::Iana(zone)),
+      None => Err(TimeError::TimeZoneParseErr(s.to_string())),
+    }
+  }
+}
+
+impl std::fmt::Display for Tz {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Tz::Fixed(offset) => write!(f, "{}", offset),
+      Tz::Iana(zone) | Tz::Named(zone) => write!(f, "{}", zone),
+    }
+  }
+}
+
 impl std::fmt::Display for ZoneOffset {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let sign_char = if self.0 >= 0 { '+' } else { '-' };
@@ -80,4 +204,29 @@ mod test {
   fn construction_constraint() {
     assert!(ZoneOffset::new(-23333).is_err())
   }
+
+  #[test]
+  fn parse_tz() {
+    assert_eq!("-04:00", "-4:00".parse::<Tz>().unwrap().to_string());
+    assert_eq!(
+      "America/New_York",
+      "America/New_York".parse::<Tz>().unwrap().to_string()
+    );
+    assert!("Mars/Olympus_Mons".parse::<Tz>().is_err());
+  }
+
+  #[test]
+  fn iana_stored_as_tzid() {
+    let ny: Tz = "America/New_York".parse().unwrap();
+    let stored = serde_json::to_string(&ny).unwrap();
+    assert_eq!("\"America/New_York\"", stored);
+
+    let ny: Tz = serde_json::from_str(&stored).unwrap();
+    let jul = MinInstant::from_raw_utc(28_150_000).unwrap(); // 2023/Jul/10
+    assert_eq!(-240, ny.offset_at(jul).raw());
+    assert!(serde_json::from_str::<Tz>("\"Mars/Olympus_Mons\"").is_err());
+
+    let fixed: Tz = serde_json::from_str("-240").unwrap();
+    assert_eq!("-04:00", fixed.to_string());
+  }
 }
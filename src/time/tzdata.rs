@@ -0,0 +1,257 @@
+//! Compiled-in timezone database.
+//!
+//! Every IANA timezone is read from its TZif file, as bundled by `jiff-tzdb`.
+//! Its transitions since the Unix Epoch are recorded as is, and the POSIX TZ
+//! string at the end of file describes its rules after the last transition.
+
+use super::{
+  fact::MIN_IN_HR,
+  month::Month,
+  timezone::ZoneOffset,
+  week::Weekday,
+  zone_rules::{Observance, TransitionRule, ZoneRules},
+};
+
+/// Looks up some IANA timezone by its case-insensitive name, ie.
+/// `America/New_York`.
+pub fn lookup(name: &str) -> Option<ZoneRules> {
+  let (name, tzif) = jiff_tzdb::get(name)?;
+  from_tzif(name, tzif)
+}
+
+/// Reads the big-endian number of `N` bytes at `at` of some TZif file.
+fn tzif_bytes<const N: usize>(tzif: &[u8], at: usize) -> Option<[u8; N]> {
+  tzif.get(at..at + N)?.try_into().ok()
+}
+
+/// Reads the six counts in the header at `at` of some TZif file, ie.
+/// `isutcnt`, `isstdcnt`, `leapcnt`, `timecnt`, `typecnt` and `charcnt`.
+fn tzif_counts(tzif: &[u8], at: usize) -> Option<[usize; 6]> {
+  if tzif.get(at..at + 4)? != b"TZif" {
+    return None;
+  }
+  let mut counts = [0; 6];
+  for (i, count) in counts.iter_mut().enumerate() {
+    let n = u32::from_be_bytes(tzif_bytes(tzif, at + 20 + 4 * i)?);
+    *count = usize::try_from(n).ok()?;
+  }
+  Some(counts)
+}
+
+/// Converts some TZif file of version 2 or later, as per RFC 8536, into the
+/// rules of timezone `name`.
+fn from_tzif(name: &str, tzif: &[u8]) -> Option<ZoneRules> {
+  if *tzif.get(4)? < b'2' {
+    return None;
+  }
+
+  // skips the data block of version 1, which has 32-bit transition times.
+  let [isut, isstd, leap, time, typ, chr] = tzif_counts(tzif, 0)?;
+  let v2 = 44 + time * 5 + typ * 6 + chr + leap * 8 + isstd + isut;
+  let [isut, isstd, leap, time, typ, chr] = tzif_counts(tzif, v2)?;
+  let times_at = v2 + 44;
+  let types_at = times_at + time * 9;
+  let footer_at = types_at + typ * 6 + chr + leap * 12 + isstd + isut;
+
+  // offsets are kept in minutes, as those of local mean time may be out of
+  // range, ie. `+15:02` of `America/Juneau` before 1867.
+  let offset_of = |typ: u8| -> Option<i64> {
+    let at = types_at + 6 * usize::from(typ);
+    Some(i64::from(i32::from_be_bytes(tzif_bytes(tzif, at)?)).div_euclid(60))
+  };
+
+  // groups the transitions since the Unix Epoch by their offsets, where
+  // local type 0 is in effect before the first one.
+  let mut offset = offset_of(0)?;
+  let mut last_utc = None;
+  let mut groups = Vec::<(i64, i64, Vec<i64>)>::new();
+  for i in 0..time {
+    let secs = i64::from_be_bytes(tzif_bytes(tzif, times_at + 8 * i)?);
+    let to = offset_of(*tzif.get(times_at + time * 8 + i)?)?;
+    if secs >= 0 && to != offset {
+      let utc = secs.div_euclid(60);
+      match groups.iter_mut().find(|(f, t, _)| *f == offset && *t == to) {
+        Some((_, _, locals)) => locals.push(utc + offset),
+        None => groups.push((offset, to, vec![utc + offset])),
+      }
+      last_utc = Some(utc);
+    }
+    offset = to;
+  }
+
+  let mut observances = groups
+    .into_iter()
+    .map(|(from, to, locals)| {
+      let (from, to) = (ZoneOffset::new(from).ok()?, ZoneOffset::new(to).ok()?);
+      Some(Observance::new(from, to, locals[0], None, locals[1..].to_vec()))
+    })
+    .collect::<Option<Vec<Observance>>>()?;
+
+  // the POSIX TZ string, ie. `\nEST5EDT,M3.2.0,M11.1.0\n`, takes over after
+  // the last transition.
+  let footer = tzif.get(footer_at..)?.strip_prefix(b"\n")?;
+  let len = footer.iter().position(|b| *b == b'\n')?;
+  let posix = std::str::from_utf8(&footer[..len]).ok()?;
+  if !posix.is_empty() {
+    let current = from_posix(posix)?;
+    observances.extend(current.into_iter().map(|o| match last_utc {
+      Some(utc) => o.since(utc),
+      None => o,
+    }));
+  }
+  Some(ZoneRules::new(name, observances))
+}
+
+/// Converts some POSIX TZ string, ie. `EST5EDT,M3.2.0,M11.1.0`, into the
+/// observances of some timezone, which have been in effect forever.
+fn from_posix(posix: &str) -> Option<Vec<Observance>> {
+  let rest = skip_abbrev(posix)?;
+  let (std_min, rest) = posix_minutes(rest)?;
+  let std = ZoneOffset::new(-std_min).ok()?;
+  if rest.is_empty() {
+    return Some(vec![Observance::new(std, std, i64::MIN, None, vec![])]);
+  }
+
+  // daylight saving time is one hour ahead, unless specified otherwise.
+  let rest = skip_abbrev(rest)?;
+  let (dst_min, rest) = match rest.strip_prefix(',') {
+    Some(_) => (std_min - i64::from(MIN_IN_HR), rest),
+    None => posix_minutes(rest)?,
+  };
+  let dst = ZoneOffset::new(-dst_min).ok()?;
+
+  let (start, rest) = posix_rule(rest.strip_prefix(',')?)?;
+  let (end, rest) = posix_rule(rest.strip_prefix(',')?)?;
+  if !rest.is_empty() {
+    return None;
+  }
+
+  Some(vec![
+    Observance::new(std, dst, i64::MIN, Some(start), vec![]),
+    Observance::new(dst, std, i64::MIN, Some(end), vec![]),
+  ])
+}
+
+/// Skips the abbreviation of some offset, ie. `EST` or `<+0530>`.
+fn skip_abbrev(s: &str) -> Option<&str> {
+  if let Some(quoted) = s.strip_prefix('<') {
+    return Some(&quoted[quoted.find('>')? + 1..]);
+  }
+  let len = s.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(s.len());
+  (len >= 3).then(|| &s[len..])
+}
+
+/// Takes the leading decimal digits of some string as a number.
+fn posix_number(s: &str) -> Option<(i64, &str)> {
+  let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+  Some((s[..len].parse().ok()?, &s[len..]))
+}
+
+/// Parses some POSIX duration `[+-]hh[:mm[:ss]]` as minutes, ignoring seconds.
+fn posix_minutes(s: &str) -> Option<(i64, &str)> {
+  let (sign, s) = match s.strip_prefix('-') {
+    Some(rest) => (-1, rest),
+    None => (1, s.strip_prefix('+').unwrap_or(s)),
+  };
+  let (hr, mut rest) = posix_number(s)?;
+  let mut min = 0;
+  if let Some(after_colon) = rest.strip_prefix(':') {
+    (min, rest) = posix_number(after_colon)?;
+  }
+  if let Some(after_colon) = rest.strip_prefix(':') {
+    rest = posix_number(after_colon)?.1;
+  }
+  Some((sign * (hr * i64::from(MIN_IN_HR) + min), rest))
+}
+
+/// Parses some POSIX transition rule `Mm.w.d[/time]`, where the `w`th
+/// weekday `d` (with `5` meaning the last) of month `m` is meant. Such a
+/// transition happens at 02:00 local time by default.
+fn posix_rule(s: &str) -> Option<(TransitionRule, &str)> {
+  use Weekday::*;
+
+  let (m, rest) = posix_number(s.strip_prefix('M')?)?;
+  let (w, rest) = posix_number(rest.strip_prefix('.')?)?;
+  let (d, rest) = posix_number(rest.strip_prefix('.')?)?;
+
+  let (time, rest) = match rest.strip_prefix('/') {
+    Some(t) => posix_minutes(t)?,
+    None => (2 * i64::from(MIN_IN_HR), rest),
+  };
+
+  let mon = Month::try_from(u32::try_from(m - 1).ok()?).ok()?;
+  let from_day = match w {
+    1..=4 => 7 * (w - 1) + 1,
+    5 => -6,
+    _ => return None,
+  };
+  let wd = *[SU, MO, TU, WE, TH, FR, SA].get(usize::try_from(d).ok()?)?;
+  Some((TransitionRule::new(mon, from_day, Some(wd), time), rest))
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use super::*;
+  use crate::time::{date::Date, timezone::TimeZone, MinInstant};
+
+  /// Computes the offset of timezone `name` at some UTC time.
+  fn offset_at_utc(name: &str, ymd: &str, hms: &str) -> i64 {
+    let zone = lookup(name).unwrap();
+    let dt = Date::from_ics_time_string(ymd, hms, ZoneOffset::utc()).unwrap();
+    zone.offset_at(MinInstant::from_date(&dt).unwrap()).raw()
+  }
+
+  #[test]
+  fn every_zone_parses() {
+    for name in jiff_tzdb::available() {
+      assert!(lookup(name).is_some(), "{} failed to parse", name);
+    }
+    assert!(lookup("america/new_york").is_some());
+    assert!(lookup("Mars/Olympus_Mons").is_none());
+  }
+
+  #[test]
+  fn northern_hemisphere() {
+    assert_eq!(-300, offset_at_utc("America/New_York", "20230115", "120000"));
+    assert_eq!(-240, offset_at_utc("America/New_York", "20230704", "120000"));
+    assert_eq!(0, offset_at_utc("Europe/London", "20231029", "010000"));
+    assert_eq!(60, offset_at_utc("Europe/London", "20231029", "005900"));
+    assert_eq!(-150, offset_at_utc("America/St_Johns", "20230704", "120000"));
+  }
+
+  #[test]
+  fn southern_hemisphere_and_negative_dst() {
+    assert_eq!(660, offset_at_utc("Australia/Sydney", "20230115", "120000"));
+    assert_eq!(600, offset_at_utc("Australia/Sydney", "20230704", "120000"));
+    assert_eq!(0, offset_at_utc("Europe/Dublin", "20230115", "120000"));
+    assert_eq!(60, offset_at_utc("Europe/Dublin", "20230704", "120000"));
+  }
+
+  #[test]
+  fn historical_rules() {
+    // before 2007, daylight saving time began on the first Sunday of April.
+    assert_eq!(-300, offset_at_utc("America/New_York", "20060320", "120000"));
+    assert_eq!(-240, offset_at_utc("America/New_York", "20060403", "120000"));
+    assert_eq!(-240, offset_at_utc("America/New_York", "20070320", "120000"));
+    assert_eq!(240, offset_at_utc("Europe/Moscow", "20120115", "120000"));
+    assert_eq!(180, offset_at_utc("Europe/Moscow", "20150115", "120000"));
+  }
+
+  #[test]
+  fn negative_transition_time() {
+    // daylight saving time begins at -1:00 of the last Sunday of March, ie.
+    // 23:00 of the day before.
+    assert_eq!(-120, offset_at_utc("America/Nuuk", "20240331", "005900"));
+    assert_eq!(-60, offset_at_utc("America/Nuuk", "20240331", "010000"));
+    assert_eq!(-60, offset_at_utc("America/Nuuk", "20240704", "120000"));
+  }
+
+  #[test]
+  fn fixed_offsets() {
+    assert_eq!(330, offset_at_utc("Asia/Kolkata", "20230704", "120000"));
+    assert_eq!(-420, offset_at_utc("America/Phoenix", "20230704", "120000"));
+    assert_eq!(345, offset_at_utc("Asia/Kathmandu", "20230115", "120000"));
+  }
+}
@@ -1,4 +1,6 @@
-use super::{year::*, Date};
+use std::str::FromStr;
+
+use super::{year::*, Date, TimeError};
 
 use crate::time::DatePropertyElt;
 
@@ -7,8 +9,12 @@ use serde::{Deserialize, Serialize};
 use Weekday::*;
 const WEEKDAY_LIST: [Weekday; 7] = [MO, TU, WE, TH, FR, SA, SU];
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[derive(
+  Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize,
+)]
 pub enum Weekday {
+  /// Weeks start on Monday unless specified otherwise, as per RFC 5545.
+  #[default]
   MO,
   TU,
   WE,
@@ -33,17 +39,20 @@ impl Weekday {
   }
 }
 
-impl From<&str> for Weekday {
-  fn from(value: &str) -> Self {
-    match value {
-      "MO" => MO,
-      "TU" => TU,
-      "WE" => WE,
-      "TH" => TH,
-      "FR" => FR,
-      "SA" => SA,
-      "SU" => SU,
-      s => panic!("Failed to convert {} to weekday", s),
+impl FromStr for Weekday {
+  type Err = TimeError;
+
+  /// Parses some two-letter weekday abbreviation, ie. `MO`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "MO" => Ok(MO),
+      "TU" => Ok(TU),
+      "WE" => Ok(WE),
+      "TH" => Ok(TH),
+      "FR" => Ok(FR),
+      "SA" => Ok(SA),
+      "SU" => Ok(SU),
+      _ => Err(TimeError::WeekdayParseErr(s.to_string())),
     }
   }
 }
@@ -85,4 +94,10 @@ mod test {
   fn iterate() {
     assert_eq!(TU, SA.next_wrap().next_wrap().next_wrap())
   }
+
+  #[test]
+  fn parse() {
+    assert_eq!(FR, "FR".parse().unwrap());
+    assert!("1FR".parse::<Weekday>().is_err());
+  }
 }
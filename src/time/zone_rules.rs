@@ -0,0 +1,572 @@
+//! Timezones whose offsets change over time, ie. due to daylight saving time.
+//!
+//! Such a timezone is a collection of observances, each of which describes
+//! when some offset comes into effect, just like the `STANDARD` and
+//! `DAYLIGHT` components of some ICalendar `VTIMEZONE`.
+
+use std::cmp::Reverse;
+
+use crate::ics_parser::{
+  ics_syntax::{Freq, FreqAndRRules, RRuleToks},
+  lexer::Token,
+  ICSProcessError,
+};
+
+use super::{
+  date::{parse_byday, rrule_nums, Date},
+  month::Month,
+  timezone::{TimeZone, ZoneOffset},
+  week::Weekday,
+  year::{CeYear, Year},
+  MinInstant,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A yearly rule of offset transition, ie. "the first Sunday on or after
+/// March 8th, at 02:00 local time".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransitionRule {
+  mon: Month,
+
+  /// Day in month from which `wd` is looked for. Non-positive values count
+  /// from the end of month, ie. `-6` for the last seven days.
+  from_day: i64,
+
+  /// Weekday of transition. If `None`, transition happens on `from_day`.
+  wd: Option<Weekday>,
+
+  /// Local time of transition, in minutes since midnight, which may be
+  /// negative or beyond a day, ie. `-60` for 23:00 of the day before.
+  time: i64,
+
+  /// Time of the last transition, in raw UTC minutes, ie. `UNTIL`.
+  until: Option<i64>,
+}
+
+impl TransitionRule {
+  /// Creates a transition rule that never terminates.
+  pub fn new(
+    mon: Month,
+    from_day: i64,
+    wd: Option<Weekday>,
+    time: i64,
+  ) -> Self {
+    Self { mon, from_day, wd, time, until: None }
+  }
+
+  /// Converts the yearly `RRULE` of some observance into a transition rule,
+  /// where transitions happen at `time` minutes since local midnight. Only
+  /// rules of the form `BYMONTH=3;BYDAY=2SU`, `BYMONTH=3;BYDAY=-1SU`,
+  /// `BYMONTH=3;BYMONTHDAY=8,9,..,14;BYDAY=SU` and `BYMONTH=3;BYMONTHDAY=8`
+  /// are supported, which cover the timezones in use.
+  pub fn from_rrules(
+    frq: &FreqAndRRules,
+    time: u32,
+  ) -> Result<Self, ICSProcessError> {
+    if frq.freq != Freq::Yearly {
+      return Err(ICSProcessError::Msg("timezone rules must be yearly"));
+    }
+
+    let (mut mon, mut byday, mut monthdays) = (None, None, None);
+    for rrt in &frq.content {
+      match (&rrt.tag, rrt.content.as_slice()) {
+        (Token::BYMONTH, _) => {
+          let bad = || {
+            ICSProcessError::InvalidRRule(Token::BYMONTH, rrt.content.join(","))
+          };
+          mon = match rrule_nums(rrt, 12)?.as_slice() {
+            [n] if *n > 0 => {
+              Some(Month::try_from(*n as u32 - 1).map_err(|_| bad())?)
+            }
+            _ => return Err(bad()),
+          };
+        }
+        (Token::BYDAY, [s]) => byday = Some(parse_byday(s)?),
+        (Token::BYMONTHDAY, _) => monthdays = Some(rrule_nums(rrt, 31)?),
+        (t, _) => return Err(ICSProcessError::UnsupportedRRule(*t)),
+      }
+    }
+
+    let mon = mon.ok_or(ICSProcessError::Msg("timezone rule lacks BYMONTH"))?;
+    let first_monthday = monthdays.and_then(|v| v.into_iter().min());
+    let (from_day, wd) = match (byday, first_monthday) {
+      (Some((Some(n), wd)), None) if n > 0 => (7 * (n - 1) + 1, Some(wd)),
+      (Some((Some(n), wd)), None) => (7 * (n + 1) - 6, Some(wd)),
+      (Some((None, wd)), Some(d)) => (d, Some(wd)),
+      (None, Some(d)) => (d, None),
+      _ => return Err(ICSProcessError::UnsupportedRRule(Token::BYDAY)),
+    };
+
+    let until = frq.until.map(|mi| i64::from(mi.normalize().raw));
+    Ok(Self {
+      until,
+      ..Self::new(mon, from_day, wd, i64::from(time))
+    })
+  }
+
+  /// Converts this rule back into the yearly `RRULE` of some observance,
+  /// which is the inverse of `from_rrules()`.
+  pub fn to_rrules(&self) -> FreqAndRRules {
+    let bymonth = RRuleToks {
+      tag: Token::BYMONTH,
+      content: vec![(self.mon as u32 + 1).to_string()],
+    };
+    let byday = |s: String| RRuleToks { tag: Token::BYDAY, content: vec![s] };
+    let monthdays = |days: std::ops::RangeInclusive<i64>| RRuleToks {
+      tag: Token::BYMONTHDAY,
+      content: days.map(|d| d.to_string()).collect(),
+    };
+
+    let mut content = vec![bymonth];
+    content.extend(match self.wd {
+      None => vec![monthdays(self.from_day..=self.from_day)],
+      Some(wd) if self.from_day > 0 && (self.from_day - 1) % 7 == 0 => {
+        vec![byday(format!("{}{:?}", (self.from_day - 1) / 7 + 1, wd))]
+      }
+      Some(wd) if self.from_day <= 0 && (self.from_day + 6) % 7 == 0 => {
+        vec![byday(format!("{}{:?}", (self.from_day + 6) / 7 - 1, wd))]
+      }
+      Some(wd) => vec![
+        monthdays(self.from_day..=self.from_day + 6),
+        byday(format!("{:?}", wd)),
+      ],
+    });
+
+    let until = self.until.and_then(|u| {
+      let raw = u32::try_from(u).ok()?;
+      Some(MinInstant { raw, offset: ZoneOffset::utc() })
+    });
+    FreqAndRRules {
+      freq: Freq::Yearly,
+      content,
+      interval: 1,
+      count: None,
+      until,
+      wkst: Weekday::MO,
+      floating_until: false,
+      span: None,
+    }
+  }
+
+  /// Local time of transition, in minutes since midnight.
+  pub fn time(&self) -> i64 {
+    self.time
+  }
+
+  /// Computes the local time of transition in some year, in raw minutes.
+  fn local_in_year(&self, yr: CeYear) -> Option<i64> {
+    let num_days = i64::from(self.mon.num_days(&yr));
+    let mut day =
+      if self.from_day > 0 { self.from_day } else { num_days + self.from_day };
+
+    let mut date = Date {
+      yr,
+      mon: self.mon,
+      day: u32::try_from(day).ok()?,
+      hr: 0,
+      min: 0,
+      tz: ZoneOffset::utc(),
+    };
+    if let Some(wd) = self.wd {
+      day += (wd as i64 - Weekday::from(date) as i64).rem_euclid(7);
+      date.day = u32::try_from(day).ok()?;
+    }
+
+    if day < 1 || day > num_days {
+      return None;
+    }
+    let midnight = i64::from(MinInstant::from_date(&date).ok()?.raw);
+    Some(midnight + self.time)
+  }
+}
+
+/// A period in which some timezone observes the same offset, ie. the
+/// `STANDARD` or `DAYLIGHT` components of some `VTIMEZONE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Observance {
+  offset_from: ZoneOffset,
+  offset_to: ZoneOffset,
+
+  /// Local time of the first onset, in raw minutes.
+  onset: i64,
+
+  /// Rule of recurring onsets, ie. `RRULE`.
+  rule: Option<TransitionRule>,
+
+  /// Local time of additional onsets, in raw minutes, ie. `RDATE`.
+  rdates: Vec<i64>,
+}
+
+impl Observance {
+  /// Creates an observance which changes the offset from `offset_from` to
+  /// `offset_to` at the local raw minute `onset` and every transition since.
+  pub fn new(
+    offset_from: ZoneOffset,
+    offset_to: ZoneOffset,
+    onset: i64,
+    rule: Option<TransitionRule>,
+    rdates: Vec<i64>,
+  ) -> Self {
+    Self {
+      offset_from,
+      offset_to,
+      onset,
+      rule,
+      rdates,
+    }
+  }
+
+  /// Offset before the onsets of this observance.
+  pub fn offset_from(&self) -> ZoneOffset {
+    self.offset_from
+  }
+
+  /// Offset since the onsets of this observance.
+  pub fn offset_to(&self) -> ZoneOffset {
+    self.offset_to
+  }
+
+  /// Local time of the first onset, in raw minutes.
+  pub fn onset(&self) -> i64 {
+    self.onset
+  }
+
+  /// Rule of recurring onsets, ie. `RRULE`.
+  pub fn rule(&self) -> Option<&TransitionRule> {
+    self.rule.as_ref()
+  }
+
+  /// Local time of additional onsets, in raw minutes, ie. `RDATE`.
+  pub fn rdates(&self) -> &[i64] {
+    &self.rdates
+  }
+
+  /// Drops the onsets of this observance no later than the raw UTC minute
+  /// `utc`, such that its first onset is the earliest one after `utc`.
+  pub fn since(mut self, utc: i64) -> Self {
+    let to_utc = |local: i64| local.saturating_sub(self.offset_from.raw());
+    self.onset = match &self.rule {
+      Some(rule) => {
+        let yr = ce_year_of(utc);
+        [Some(yr), yr.checked_add(1)]
+          .into_iter()
+          .flatten()
+          .filter_map(|y| rule.local_in_year(CeYear::new(y).ok()?))
+          .find(|l| to_utc(*l) > utc)
+          .unwrap_or(i64::MAX)
+      }
+      None => utc.saturating_add(self.offset_from.raw()),
+    };
+    self.rdates.retain(|l| to_utc(*l) > utc);
+    self
+  }
+
+  /// Drops the onsets of this observance outside the raw UTC minutes from
+  /// `start` (inclusive) to `end` (exclusive), except the local onset `kept`.
+  /// Recurring onsets are kept as a whole if any of them is needed. Returns
+  /// `None` if no onset is left.
+  fn within(&self, start: i64, end: i64, kept: Option<i64>) -> Option<Self> {
+    let to_utc = |local: i64| local.saturating_sub(self.offset_from.raw());
+    let needed =
+      |l: &i64| Some(*l) == kept || (start..end).contains(&to_utc(*l));
+    let rdates: Vec<i64> = self.rdates.iter().copied().filter(needed).collect();
+
+    let rule_needed = self.rule.as_ref().is_some_and(|rule| {
+      let in_range =
+        to_utc(self.onset) < end && rule.until.is_none_or(|u| u >= start);
+      in_range || kept.is_some_and(|k| !self.rdates.contains(&k))
+    });
+    if rule_needed {
+      return Some(Self { rdates, ..self.clone() });
+    }
+
+    let mut onsets = rdates;
+    if needed(&self.onset) {
+      onsets.push(self.onset);
+    }
+    onsets.sort();
+    let (onset, rdates) = onsets.split_first()?;
+    Some(Self::new(
+      self.offset_from,
+      self.offset_to,
+      *onset,
+      None,
+      rdates.to_vec(),
+    ))
+  }
+
+  /// Finds the latest onset no later than the raw UTC minute `utc`, in raw
+  /// UTC minutes.
+  fn latest_onset(&self, utc: i64) -> Option<i64> {
+    let to_utc = |local: i64| local.saturating_sub(self.offset_from.raw());
+
+    let mut candidates = vec![self.onset];
+    candidates.extend(&self.rdates);
+    if let Some(rule) = &self.rule {
+      let yr = ce_year_of(utc);
+      let years = [yr.checked_sub(1), Some(yr)];
+      for yr in years.into_iter().flatten().filter_map(|y| CeYear::new(y).ok())
+      {
+        match rule.local_in_year(yr) {
+          Some(l) if rule.until.is_none_or(|u| to_utc(l) <= u) => {
+            candidates.push(l)
+          }
+          _ => {}
+        }
+      }
+    }
+
+    candidates
+      .into_iter()
+      .filter(|l| *l >= self.onset)
+      .map(to_utc)
+      .filter(|u| *u <= utc)
+      .max()
+  }
+}
+
+/// Computes the CE year in which some raw UTC minute lies.
+fn ce_year_of(utc: i64) -> u16 {
+  let raw = u32::try_from(utc).unwrap_or(0);
+  MinInstant { raw, offset: ZoneOffset::utc() }.decomp_yr_min().0.to_ce().raw()
+}
+
+/// A named timezone whose offset is decided by a collection of observances.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneRules {
+  name: String,
+  observances: Vec<Observance>,
+}
+
+impl ZoneRules {
+  pub fn new(name: &str, observances: Vec<Observance>) -> Self {
+    Self { name: name.to_string(), observances }
+  }
+
+  /// Gets the name of timezone, ie. `America/New_York`.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Gets the observances of timezone, ie. `STANDARD` and `DAYLIGHT`.
+  pub fn observances(&self) -> &[Observance] {
+    &self.observances
+  }
+
+  /// Computes the offset in effect at some raw UTC minute. Before the
+  /// earliest onset, the offset that the earliest observance changes from is
+  /// used.
+  fn offset_at_utc(&self, utc: i64) -> ZoneOffset {
+    let latest = self
+      .observances
+      .iter()
+      .filter_map(|o| Some((o.latest_onset(utc)?, o)))
+      .max_by_key(|(u, _)| *u);
+
+    match latest {
+      Some((_, o)) => o.offset_to,
+      None => self
+        .observances
+        .iter()
+        .min_by_key(|o| o.onset)
+        .map_or(ZoneOffset::utc(), |o| o.offset_from),
+    }
+  }
+}
+
+impl ZoneRules {
+  /// Drops the observances and onsets that do not decide the offsets between
+  /// `start` and `end`, or after `start` if `end` is `None`.
+  pub fn within(&self, start: MinInstant, end: Option<MinInstant>) -> Self {
+    let start = i64::from(start.normalize().raw);
+    let end = end.map_or(i64::MAX, |mi| i64::from(mi.normalize().raw));
+
+    // the onset in effect at `start`, in local time of its observance.
+    let in_effect = self
+      .observances
+      .iter()
+      .enumerate()
+      .filter_map(|(i, o)| Some((o.latest_onset(start)?, i)))
+      .max();
+    let kept = |i: usize| {
+      let (utc, j) = in_effect.filter(|(_, j)| *j == i)?;
+      Some(utc + self.observances[j].offset_from.raw())
+    };
+
+    let mut observances: Vec<Observance> = self
+      .observances
+      .iter()
+      .enumerate()
+      .filter_map(|(i, o)| o.within(start, end, kept(i)))
+      .collect();
+
+    // keeps the offset before the earliest onset if all onsets are dropped.
+    if observances.is_empty() {
+      observances.extend(self.observances.iter().min_by_key(|o| o.onset).map(
+        |o| Observance::new(o.offset_from, o.offset_to, o.onset, None, vec![]),
+      ));
+    }
+    Self { name: self.name.clone(), observances }
+  }
+}
+
+impl TimeZone for ZoneRules {
+  fn offset_at(&self, mi: MinInstant) -> ZoneOffset {
+    self.offset_at_utc(i64::from(mi.normalize().raw))
+  }
+
+  fn offset_at_local(&self, local: u32) -> ZoneOffset {
+    let local = i64::from(local);
+    let mut candidates: Vec<ZoneOffset> = self
+      .observances
+      .iter()
+      .flat_map(|o| [o.offset_from, o.offset_to])
+      .collect();
+
+    // larger offsets correspond to earlier time instants.
+    candidates.sort_by_key(|off| Reverse(off.raw()));
+
+    for off in &candidates {
+      if self.offset_at_utc(local - off.raw()).raw() == off.raw() {
+        return *off;
+      }
+    }
+
+    match candidates.first() {
+      Some(off) => self.offset_at_utc(local - off.raw()),
+      None => ZoneOffset::utc(),
+    }
+  }
+}
+
+impl std::fmt::Display for ZoneRules {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name)
+  }
+}
+
+// --------------------------------- Testing ---------------------------------
+
+#[allow(dead_code, unused_imports)]
+mod test {
+  use super::*;
+
+  /// Current rules of `America/New_York`.
+  fn new_york() -> ZoneRules {
+    let est = ZoneOffset::new(-300).unwrap();
+    let edt = ZoneOffset::new(-240).unwrap();
+    let dst = TransitionRule::new(Month::Mar, 8, Some(Weekday::SU), 120);
+    let std = TransitionRule::new(Month::Nov, 1, Some(Weekday::SU), 120);
+    ZoneRules::new(
+      "America/New_York",
+      vec![
+        Observance::new(est, edt, 0, Some(dst), vec![]),
+        Observance::new(edt, est, 0, Some(std), vec![]),
+      ],
+    )
+  }
+
+  /// Localizes some wall-clock time in `zone`, and prints it in UTC.
+  fn utc_of(zone: &ZoneRules, ymd: &str, hms: &str) -> String {
+    let date = Date::from_ics_time_string(ymd, hms, ZoneOffset::utc()).unwrap();
+    let mi = zone.localize(&date).unwrap().normalize();
+    Date::from_min_instant(mi).no_tz_string()
+  }
+
+  #[test]
+  fn offsets_across_year() {
+    let ny = new_york();
+    assert_eq!("2023/Jan/15 14:00", utc_of(&ny, "20230115", "090000"));
+    assert_eq!("2023/Jul/4 13:00", utc_of(&ny, "20230704", "090000"));
+    assert_eq!("2023/Dec/6 14:00", utc_of(&ny, "20231206", "090000"));
+  }
+
+  #[test]
+  fn transition_days() {
+    let ny = new_york();
+    assert_eq!("2023/Mar/12 06:59", utc_of(&ny, "20230312", "015900"));
+    assert_eq!("2023/Mar/12 07:00", utc_of(&ny, "20230312", "030000"));
+    assert_eq!("2024/Mar/10 07:00", utc_of(&ny, "20240310", "030000"));
+    assert_eq!("2023/Nov/5 07:00", utc_of(&ny, "20231105", "020000"));
+  }
+
+  #[test]
+  fn gap_and_overlap() {
+    let ny = new_york();
+
+    // 02:30 is skipped, and is interpreted with the offset before the gap.
+    assert_eq!("2023/Mar/12 07:30", utc_of(&ny, "20230312", "023000"));
+
+    // 01:30 happens twice, and is interpreted as the first occurrence.
+    assert_eq!("2023/Nov/5 05:30", utc_of(&ny, "20231105", "013000"));
+  }
+
+  #[test]
+  fn last_sunday_rule() {
+    // Europe/London, with transitions at 01:00 UTC.
+    let gmt = ZoneOffset::utc();
+    let bst = ZoneOffset::new(60).unwrap();
+    let dst = TransitionRule::new(Month::Mar, -6, Some(Weekday::SU), 60);
+    let std = TransitionRule::new(Month::Oct, -6, Some(Weekday::SU), 120);
+    let london = ZoneRules::new(
+      "Europe/London",
+      vec![
+        Observance::new(gmt, bst, 0, Some(dst), vec![]),
+        Observance::new(bst, gmt, 0, Some(std), vec![]),
+      ],
+    );
+    assert_eq!("2023/Mar/26 00:59", utc_of(&london, "20230326", "005900"));
+    assert_eq!("2023/Mar/26 01:00", utc_of(&london, "20230326", "020000"));
+    assert_eq!("2023/Oct/29 00:00", utc_of(&london, "20231029", "010000"));
+    assert_eq!("2023/Oct/29 02:00", utc_of(&london, "20231029", "020000"));
+  }
+
+  #[test]
+  fn within_span() {
+    // one transition each year from 2000 to 2002, and yearly ones since.
+    let (est, edt) =
+      (ZoneOffset::new(-300).unwrap(), ZoneOffset::new(-240).unwrap());
+    let local = |ymd: &str| {
+      let date = Date::from_ics_time_string(ymd, "020000", ZoneOffset::utc());
+      i64::from(MinInstant::from_date(&date.unwrap()).unwrap().raw)
+    };
+    let dst = TransitionRule::new(Month::Mar, 8, Some(Weekday::SU), 120);
+    let zone = ZoneRules::new(
+      "Test/Zone",
+      vec![
+        Observance::new(
+          est,
+          edt,
+          local("20000402"),
+          None,
+          vec![local("20010401"), local("20020407")],
+        ),
+        Observance::new(est, edt, local("20100314"), Some(dst), vec![]),
+      ],
+    );
+    let at = |ymd: &str| {
+      let date = Date::from_ics_time_string(ymd, "000000", ZoneOffset::utc());
+      MinInstant::from_date(&date.unwrap()).unwrap()
+    };
+
+    // the onset in 2001 is in effect throughout, and the rule never occurs.
+    let trimmed = zone.within(at("20010601"), Some(at("20010901")));
+    assert_eq!(1, trimmed.observances().len());
+    assert_eq!(local("20010401"), trimmed.observances()[0].onset());
+    assert!(trimmed.observances()[0].rdates().is_empty());
+
+    let trimmed = zone.within(at("20010601"), None);
+    assert_eq!(2, trimmed.observances().len());
+    assert_eq!(vec![local("20020407")], trimmed.observances()[0].rdates());
+    assert!(trimmed.observances()[1].rule().is_some());
+
+    let trimmed = zone.within(at("20200101"), None);
+    assert_eq!(1, trimmed.observances().len());
+    assert_eq!(local("20100314"), trimmed.observances()[0].onset());
+
+    // before any onset, the offset before the earliest one is kept.
+    let trimmed = zone.within(at("19990101"), Some(at("19990201")));
+    assert_eq!(-300, trimmed.offset_at(at("19990115")).raw());
+  }
+}
@@ -49,6 +49,11 @@ impl<const MIN: i64, const MAX: i64> RangedI64<MIN, MAX> {
     }
   }
 
+  /// Gets the raw `i64` value of self.
+  pub fn raw(&self) -> i64 {
+    self.0
+  }
+
   /// Attempts to increment the ranged number; returns an error if fails.
   pub fn increment(&self) -> RefineResult<Self> {
     let new_raw = self.0.checked_add(1);